@@ -0,0 +1,43 @@
+use crate::service::clipboard::{
+    history::{ClipboardHistory, HistorySummary},
+    sync::ClipboardSync,
+};
+
+/// 获取剪贴板历史，新的在前
+#[tauri::command]
+pub async fn list_clipboard_history() -> Result<Vec<HistorySummary>, String> {
+    Ok(ClipboardHistory::instance().list())
+}
+
+/// 按文本内容或来源设备搜索剪贴板历史
+#[tauri::command]
+pub async fn search_clipboard_history(
+    query: String,
+) -> Result<Vec<HistorySummary>, String> {
+    Ok(ClipboardHistory::instance().search(&query))
+}
+
+/// 将历史条目重新复制到剪贴板，并同步给其他设备
+#[tauri::command]
+pub async fn recopy_clipboard_history(id: u64) -> Result<(), String> {
+    let entry = ClipboardHistory::instance()
+        .get(id)
+        .ok_or_else(|| format!("Clipboard history {} not found", id))?;
+    ClipboardSync::instance().restore(entry).await.map_err(|e| e.to_string())
+}
+
+/// 删除剪贴板历史条目，`id` 为空时清空
+#[tauri::command]
+pub async fn delete_clipboard_history(id: Option<u64>) -> Result<(), String> {
+    let history = ClipboardHistory::instance();
+    match id {
+        Some(id) if !history.remove(id) => {
+            Err(format!("Clipboard history {} not found", id))
+        }
+        Some(_) => Ok(()),
+        None => {
+            history.clear();
+            Ok(())
+        }
+    }
+}
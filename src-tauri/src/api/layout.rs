@@ -0,0 +1,39 @@
+use crate::{
+    config,
+    service::layout::{Crossing, Edge, Layout, Screen},
+};
+
+/// 获取屏幕布局
+#[tauri::command]
+pub async fn get_layout() -> Result<Layout, String> {
+    Ok(config::layout::get_config())
+}
+
+/// 整体替换屏幕布局
+#[tauri::command]
+pub async fn set_layout(layout: Layout) -> Result<(), String> {
+    config::layout::set_config(layout).map_err(|e| e.to_string())
+}
+
+/// 放置或移动设备
+#[tauri::command]
+pub async fn place_screen(screen: Screen) -> Result<Layout, String> {
+    config::layout::place(screen).map_err(|e| e.to_string())
+}
+
+/// 从布局中移除设备
+#[tauri::command]
+pub async fn remove_screen(device_id: String) -> Result<Layout, String> {
+    config::layout::remove(&device_id).map_err(|e| e.to_string())
+}
+
+/// 查询从设备边缘离开时进入的设备与位置
+#[tauri::command]
+pub async fn cross_edge(
+    device_id: String,
+    edge: Edge,
+    x: f64,
+    y: f64,
+) -> Result<Option<Crossing>, String> {
+    Ok(config::layout::get_config().cross(&device_id, edge, x, y))
+}
@@ -1,4 +1,9 @@
+pub mod clipboard;
+pub mod layout;
 pub mod log;
+pub mod router;
 pub mod service;
+pub mod sync;
 pub mod sys;
+pub mod transfer;
 pub mod util;
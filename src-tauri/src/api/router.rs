@@ -0,0 +1,45 @@
+use crate::service::{
+    input::cursor_lock::LockReason,
+    server::router::{InputRouter, SwitchReason, Target},
+};
+
+/// 获取当前输入目标
+#[tauri::command]
+pub async fn get_input_target() -> Result<Target, String> {
+    Ok(InputRouter::instance().target())
+}
+
+/// 切换输入目标
+#[tauri::command]
+pub async fn switch_input_target(target: Target) -> Result<(), String> {
+    InputRouter::instance()
+        .switch_to(target, SwitchReason::Api)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取光标锁定原因，未锁定时为空
+#[tauri::command]
+pub async fn get_cursor_lock() -> Result<Option<LockReason>, String> {
+    Ok(InputRouter::instance().cursor_lock())
+}
+
+/// 将剪贴板文本作为按键输入到当前目标，输入完成后返回
+#[tauri::command]
+pub async fn type_clipboard() -> Result<(), String> {
+    InputRouter::instance().type_clipboard().await.map_err(|e| e.to_string())
+}
+
+/// 取消正在进行的按键输入
+#[tauri::command]
+pub async fn cancel_typing() -> Result<(), String> {
+    InputRouter::instance().cancel_typing();
+    Ok(())
+}
+
+/// 手动锁定或解锁光标
+#[tauri::command]
+pub async fn set_cursor_lock(locked: bool) -> Result<(), String> {
+    InputRouter::instance().set_cursor_lock(locked).await;
+    Ok(())
+}
@@ -1,6 +1,11 @@
 use spdlog::{error, info};
 
-use crate::service::{client, server};
+use crate::config;
+use crate::service::{
+    client,
+    clipboard::{primary, sync::ClipboardSync},
+    server,
+};
 
 #[tauri::command]
 pub async fn start_service(service_type: String) -> Result<(), String> {
@@ -23,7 +28,20 @@ pub async fn start_service(service_type: String) -> Result<(), String> {
         })?;
     }
 
-    Ok(())
+    start_clipboard().await
+}
+
+/// 开始或重新开始剪贴板同步，丢弃之前连接的对端提供的内容
+async fn start_clipboard() -> Result<(), String> {
+    ClipboardSync::instance().start().await.map_err(|e| {
+        error!("Failed to start clipboard sync: {}", e);
+        e.to_string()
+    })?;
+
+    primary::sync().await.map_err(|e| {
+        error!("Failed to start primary selection sync: {}", e);
+        e.to_string()
+    })
 }
 
 #[tauri::command]
@@ -66,7 +84,7 @@ pub async fn restart_service(service_type: String) -> Result<(), String> {
         })?;
     }
 
-    Ok(())
+    start_clipboard().await
 }
 
 #[tauri::command]
@@ -94,6 +112,8 @@ pub async fn handle_service_type_change(
             error!("Failed to start tcp server: {}", e);
             e.to_string()
         })?;
+        // 作为服务端时使用本地配置
+        config::sync::clear();
     } else {
         server::mdns::MdnsServer::instance().stop().await.map_err(|e| {
             error!("Failed to stop mdns server: {}", e);
@@ -110,5 +130,5 @@ pub async fn handle_service_type_change(
         })?;
     }
 
-    Ok(())
+    start_clipboard().await
 }
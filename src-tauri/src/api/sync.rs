@@ -0,0 +1,7 @@
+use crate::config::{self, sync::ManagedSummary};
+
+/// 获取已应用的服务端配置版本与管理项，未由服务端管理时为空
+#[tauri::command]
+pub async fn get_managed_config() -> Result<Option<ManagedSummary>, String> {
+    Ok(config::sync::applied())
+}
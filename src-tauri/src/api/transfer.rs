@@ -0,0 +1,17 @@
+use crate::service::transfer::{FileTransfers, TransferProgress};
+
+/// 获取文件传输的进度，包括最近结束的传输
+#[tauri::command]
+pub async fn list_file_transfers() -> Result<Vec<TransferProgress>, String> {
+    Ok(FileTransfers::instance().list())
+}
+
+/// 取消文件传输
+#[tauri::command]
+pub async fn cancel_file_transfer(id: u64) -> Result<(), String> {
+    if FileTransfers::instance().cancel(id).await {
+        Ok(())
+    } else {
+        Err(format!("File transfer {:x} not found", id))
+    }
+}
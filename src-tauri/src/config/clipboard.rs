@@ -0,0 +1,100 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, info, warn};
+use std::sync::LazyLock;
+use tauri::AppHandle;
+use tauri_plugin_valtio::ManagerExt as _;
+
+use crate::{
+    config::sync,
+    constant,
+    service::{
+        clipboard::{
+            history::HistorySettings,
+            image::ImageSettings,
+            policy::PolicySettings,
+            primary::{self, PrimarySettings},
+        },
+        input::typing::TypingSettings,
+        transfer::TransferSettings,
+    },
+};
+
+const KEY: &str = "clipboard";
+
+static CONFIG: LazyLock<RwLock<ClipboardSettings>> =
+    LazyLock::new(|| RwLock::new(ClipboardSettings::default()));
+
+/// 剪贴板同步设置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardSettings {
+    // 图片转换与缩放
+    pub image: ImageSettings,
+    // 历史记录
+    pub history: HistorySettings,
+    // 过滤策略
+    pub policy: PolicySettings,
+    // PRIMARY 选区
+    pub primary: PrimarySettings,
+    // 将文本作为按键输入
+    pub typing: TypingSettings,
+    // 复制的文件的传输
+    pub files: TransferSettings,
+}
+
+// 服务端下发的过滤策略，优先于本地策略，只保存在内存中
+static MANAGED_POLICY: LazyLock<RwLock<Option<PolicySettings>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// 获取剪贴板设置
+pub fn get_config() -> ClipboardSettings {
+    let mut config = CONFIG.read().clone();
+    if let Some(policy) = MANAGED_POLICY.read().as_ref() {
+        config.policy = policy.clone();
+    }
+    config
+}
+
+/// 应用服务端下发的过滤策略，为空时恢复使用本地策略
+pub fn set_managed_policy(policy: Option<PolicySettings>) {
+    if let Some(policy) = &policy {
+        info!("应用服务端下发的剪贴板过滤策略: {:?}", policy);
+    }
+    *MANAGED_POLICY.write() = policy;
+}
+
+pub fn set_config(config: ClipboardSettings) {
+    info!("更新剪贴板设置: {:?}", config);
+    let policy_changed = CONFIG.read().policy != config.policy;
+    let previous = std::mem::replace(&mut *CONFIG.write(), config);
+    primary::on_settings_changed(previous.primary);
+    if policy_changed {
+        sync::changed();
+    }
+}
+
+pub fn setup_config_watcher(
+    app: &AppHandle,
+) -> Result<(), tauri_plugin_valtio::Error> {
+    info!("初始化剪贴板设置监听器");
+    match app.valtio().try_get::<ClipboardSettings>(constant::STORE_ID, KEY) {
+        Ok(config) => set_config(config),
+        Err(err) => warn!("无法从存储加载剪贴板设置: {}", err),
+    }
+
+    app.valtio().watch(constant::STORE_ID, move |handle| {
+        if let Ok(config) = handle
+            .valtio()
+            .try_get::<ClipboardSettings>(constant::STORE_ID, KEY)
+            && config != *CONFIG.read()
+        {
+            debug!("检测到剪贴板设置变更: {:?}", config);
+            set_config(config);
+        }
+        Ok(())
+    })?;
+
+    info!("剪贴板设置监听器设置完成");
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, info, warn};
+use std::{collections::HashMap, sync::LazyLock};
+use tauri::AppHandle;
+use tauri_plugin_valtio::ManagerExt as _;
+
+use crate::{
+    config::sync,
+    constant,
+    service::{
+        clipboard::policy::ClipboardAccess,
+        input::{remap::RemapRules, tuning::PointerTuning},
+        protocols::{
+            base::{DeviceInfo, OsType},
+            input::RepeatPolicy,
+        },
+        server::tcp::TcpServer,
+    },
+};
+
+const KEY: &str = "device-settings";
+
+static CONFIG: LazyLock<RwLock<HashMap<String, DeviceSettings>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+// 服务端下发的设置，优先于本地设置
+static MANAGED: LazyLock<RwLock<HashMap<String, DeviceSettings>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// 单个设备的设置，以设备ID为键存储
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    // 按键映射规则，为空时使用内置预设
+    remap: Option<RemapRules>,
+    // 键盘自动重复策略，为空时使用默认策略
+    repeat: Option<RepeatPolicy>,
+    // 是否将目标设备的锁定键状态同步到本机键盘指示灯
+    mirror_lock_leds: bool,
+    // 指针灵敏度、加速与滚动调节
+    pointer: PointerTuning,
+    // 各方向是否同步剪贴板
+    clipboard: ClipboardAccess,
+}
+
+impl DeviceSettings {
+    pub fn remap(&self) -> Option<RemapRules> {
+        self.remap.clone()
+    }
+
+    pub fn repeat(&self) -> RepeatPolicy {
+        self.repeat.unwrap_or_default()
+    }
+
+    pub fn mirror_lock_leds(&self) -> bool {
+        self.mirror_lock_leds
+    }
+
+    pub fn pointer(&self) -> PointerTuning {
+        self.pointer
+    }
+
+    pub fn clipboard(&self) -> ClipboardAccess {
+        self.clipboard
+    }
+
+    /// 服务端为本机下发的设置转换为本机对服务端的设置
+    ///
+    /// 剪贴板的发送与接收方向互换，服务端发送即本机接收
+    pub fn mirrored(&self) -> Self {
+        Self { clipboard: self.clipboard.mirrored(), ..self.clone() }
+    }
+
+    /// 实际生效的按键映射规则
+    pub fn effective_remap(&self, device: &DeviceInfo) -> RemapRules {
+        self.remap().unwrap_or_else(|| {
+            RemapRules::preset(&OsType::current(), &device.os)
+        })
+    }
+}
+
+/// 获取设备设置，未配置时返回默认值
+pub fn get_config(device_id: &str) -> DeviceSettings {
+    if let Some(settings) = MANAGED.read().get(device_id) {
+        return settings.clone();
+    }
+    CONFIG.read().get(device_id).cloned().unwrap_or_default()
+}
+
+/// 设备设置是否由服务端管理
+pub fn is_managed(device_id: &str) -> bool {
+    MANAGED.read().contains_key(device_id)
+}
+
+/// 应用服务端下发的设备设置
+pub fn set_managed(device_id: &str, settings: DeviceSettings) {
+    info!("应用服务端下发的设备设置: {:?}", settings);
+    MANAGED.write().insert(device_id.to_string(), settings);
+}
+
+/// 清除服务端下发的设备设置，恢复使用本地设置
+pub fn clear_managed() {
+    MANAGED.write().clear();
+}
+
+pub fn set_config(config: HashMap<String, DeviceSettings>) {
+    info!("更新设备设置: {:?}", config);
+    *CONFIG.write() = config;
+}
+
+pub fn setup_config_watcher(
+    app: &AppHandle,
+) -> Result<(), tauri_plugin_valtio::Error> {
+    info!("初始化设备设置监听器");
+    match app
+        .valtio()
+        .try_get::<HashMap<String, DeviceSettings>>(constant::STORE_ID, KEY)
+    {
+        Ok(config) => set_config(config),
+        Err(err) => warn!("无法从存储加载设备设置: {}", err),
+    }
+
+    app.valtio().watch(constant::STORE_ID, move |handle| {
+        if let Ok(config) = handle
+            .valtio()
+            .try_get::<HashMap<String, DeviceSettings>>(constant::STORE_ID, KEY)
+        {
+            debug!("检测到设备设置变更: {:?}", config);
+            let changed = *CONFIG.read() != config;
+            set_config(config);
+            TcpServer::instance().reload_device_settings();
+            if changed {
+                sync::changed();
+            }
+        }
+        Ok(())
+    })?;
+
+    info!("设备设置监听器设置完成");
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use parking_lot::RwLock;
+use spdlog::{debug, info, warn};
+use std::sync::LazyLock;
+use tauri::AppHandle;
+use tauri_plugin_valtio::ManagerExt as _;
+
+use crate::{constant, service::input::hotkey::HotkeySettings};
+
+const KEY: &str = "hotkeys";
+
+static CONFIG: LazyLock<RwLock<HotkeySettings>> =
+    LazyLock::new(|| RwLock::new(HotkeySettings::default()));
+
+/// 获取热键设置
+pub fn get_config() -> HotkeySettings {
+    CONFIG.read().clone()
+}
+
+pub fn set_config(config: HotkeySettings) {
+    info!("更新热键设置: {:?}", config);
+    *CONFIG.write() = config;
+}
+
+pub fn setup_config_watcher(
+    app: &AppHandle,
+) -> Result<(), tauri_plugin_valtio::Error> {
+    info!("初始化热键设置监听器");
+    match app.valtio().try_get::<HotkeySettings>(constant::STORE_ID, KEY) {
+        Ok(config) => set_config(config),
+        Err(err) => warn!("无法从存储加载热键设置: {}", err),
+    }
+
+    app.valtio().watch(constant::STORE_ID, move |handle| {
+        if let Ok(config) =
+            handle.valtio().try_get::<HotkeySettings>(constant::STORE_ID, KEY)
+            && config != get_config()
+        {
+            debug!("检测到热键设置变更: {:?}", config);
+            set_config(config);
+        }
+        Ok(())
+    })?;
+
+    info!("热键设置监听器设置完成");
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use parking_lot::RwLock;
+use spdlog::{info, warn};
+use std::sync::LazyLock;
+use tauri::AppHandle;
+use tauri_plugin_valtio::ManagerExt as _;
+
+use crate::{
+    config::sync,
+    constant, core,
+    service::{
+        layout::{Layout, Screen},
+        protocols::base::DisplayInfo,
+    },
+};
+
+const KEY: &str = "layout";
+
+static CONFIG: LazyLock<RwLock<Layout>> =
+    LazyLock::new(|| RwLock::new(Layout::default()));
+
+// 服务端下发的布局，优先于本地布局，只保存在内存中
+static MANAGED: LazyLock<RwLock<Option<Layout>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// 获取当前布局
+pub fn get_config() -> Layout {
+    if let Some(layout) = MANAGED.read().as_ref() {
+        return layout.clone();
+    }
+    CONFIG.read().clone()
+}
+
+/// 应用服务端下发的布局，为空时恢复使用本地布局
+pub fn set_managed(layout: Option<Layout>) -> Result<()> {
+    if let Some(layout) = &layout {
+        layout.validate()?;
+        info!("应用服务端下发的屏幕布局: {:?}", layout);
+    }
+    *MANAGED.write() = layout;
+    Ok(())
+}
+
+/// 检查并更新布局，之后持久化
+pub fn set_config(layout: Layout) -> Result<()> {
+    layout.validate()?;
+    info!("更新屏幕布局: {:?}", layout);
+    *CONFIG.write() = layout.clone();
+    save(&layout)
+}
+
+/// 放置设备并持久化
+pub fn place(screen: Screen) -> Result<Layout> {
+    let layout = {
+        let mut config = CONFIG.write();
+        config.place(screen)?;
+        config.clone()
+    };
+    save(&layout)?;
+    Ok(layout)
+}
+
+/// 设备显示器变化后调整其屏幕尺寸并持久化，返回尺寸是否变化
+pub fn resize(
+    device_id: &str,
+    from: &[DisplayInfo],
+    to: &[DisplayInfo],
+) -> Result<bool> {
+    let layout = {
+        let mut config = CONFIG.write();
+        if !config.resize(device_id, from, to)? {
+            return Ok(false);
+        }
+        config.clone()
+    };
+    info!("设备 {} 显示器变化，更新屏幕布局: {:?}", device_id, layout);
+    save(&layout)?;
+    Ok(true)
+}
+
+/// 移除设备并持久化
+pub fn remove(device_id: &str) -> Result<Layout> {
+    let layout = {
+        let mut config = CONFIG.write();
+        config.remove(device_id);
+        config.clone()
+    };
+    save(&layout)?;
+    Ok(layout)
+}
+
+fn save(layout: &Layout) -> Result<()> {
+    if let Some(handle) = core::handle::Handle::instance().app_handle() {
+        handle.valtio().set(
+            constant::STORE_ID,
+            KEY,
+            serde_json::to_value(layout)?,
+        )?;
+    }
+    sync::changed();
+    Ok(())
+}
+
+/// 从存储加载布局
+pub fn load(app: &AppHandle) {
+    match app.valtio().try_get::<Layout>(constant::STORE_ID, KEY) {
+        Ok(layout) => {
+            info!("从存储加载屏幕布局: {:?}", layout);
+            *CONFIG.write() = layout;
+        }
+        Err(err) => warn!("无法从存储加载屏幕布局: {}", err),
+    }
+}
@@ -1,3 +1,8 @@
+pub mod clipboard;
+pub mod device;
+pub mod hotkey;
+pub mod layout;
 pub mod log;
 pub mod network;
+pub mod sync;
 pub mod system;
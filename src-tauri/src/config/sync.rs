@@ -0,0 +1,253 @@
+use anyhow::Result;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, info, warn};
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicU64, Ordering},
+};
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_valtio::ManagerExt as _;
+
+use crate::{
+    config::{self, device::DeviceSettings, network::ServiceType},
+    constant, core,
+    service::{
+        clipboard::policy::PolicySettings, layout::Layout,
+        protocols::base::ManagedConfig, server::tcp::TcpServer,
+    },
+};
+
+const KEY: &str = "managed";
+
+// 服务端当前配置版本，首次使用时以当前时间初始化，保证重启后仍递增
+static VERSION: AtomicU64 = AtomicU64::new(0);
+
+static APPLIED: LazyLock<RwLock<Option<ManagedState>>> =
+    LazyLock::new(|| RwLock::new(None));
+
+/// 客户端已应用的集中管理配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ManagedState {
+    pub version: u64,
+    // 下发配置的服务端
+    pub server_id: String,
+    pub layout: Layout,
+    // 服务端对本机的设置
+    pub settings: DeviceSettings,
+    // 剪贴板过滤策略
+    pub policy: PolicySettings,
+}
+
+/// 由服务端管理的配置项，客户端上对应的本地配置不生效
+const MANAGED_KEYS: &[&str] = &[
+    "layout",
+    "remap",
+    "repeat",
+    "mirror_lock_leds",
+    "pointer",
+    "clipboard",
+    "clipboard_policy",
+];
+
+/// 供前端展示的集中管理信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ManagedSummary {
+    pub version: u64,
+    pub server_id: String,
+    pub keys: Vec<&'static str>,
+}
+
+impl ManagedState {
+    pub fn summary(&self) -> ManagedSummary {
+        ManagedSummary {
+            version: self.version,
+            server_id: self.server_id.clone(),
+            keys: MANAGED_KEYS.to_vec(),
+        }
+    }
+}
+
+fn is_server() -> bool {
+    matches!(config::network::get_config().service_type(), ServiceType::Server)
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// 服务端当前配置版本
+pub fn version() -> u64 {
+    let _ = VERSION.compare_exchange(
+        0,
+        now_millis(),
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    );
+    VERSION.load(Ordering::SeqCst)
+}
+
+/// 集中管理的配置发生变化，作为服务端时递增版本并推送给所有客户端
+pub fn changed() {
+    if !is_server() {
+        return;
+    }
+    let now = now_millis();
+    let _ = VERSION.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+        Some(next_version(v, now))
+    });
+    debug!("集中管理配置版本更新: {}", version());
+    TcpServer::instance().push_managed_config();
+}
+
+/// 新版本使用当前时间，时钟回拨时仍比当前版本大
+fn next_version(current: u64, now: u64) -> u64 {
+    now.max(current + 1)
+}
+
+/// 下发给设备 `device_id` 的配置
+pub fn managed_config(device_id: &str) -> Result<ManagedConfig> {
+    Ok(ManagedConfig {
+        version: version(),
+        layout: serde_json::to_string(&config::layout::get_config())?,
+        settings: serde_json::to_string(&config::device::get_config(
+            device_id,
+        ))?,
+        policy: serde_json::to_string(&config::clipboard::get_config().policy)?,
+    })
+}
+
+/// 客户端已应用的配置
+pub fn applied() -> Option<ManagedSummary> {
+    APPLIED.read().as_ref().map(ManagedState::summary)
+}
+
+/// 是否应用服务端 `server_id` 下发的版本 `version`
+///
+/// 版本只在同一服务端内比较，换了服务端时总是应用
+fn should_apply(
+    applied: Option<&ManagedState>,
+    server_id: &str,
+    version: u64,
+) -> bool {
+    applied.is_none_or(|applied| {
+        applied.server_id != server_id || applied.version < version
+    })
+}
+
+/// 应用服务端 `server_id` 下发的配置，版本不高于已应用的版本时忽略
+///
+/// 返回是否应用
+pub fn apply(server_id: &str, config: ManagedConfig) -> Result<bool> {
+    if !should_apply(APPLIED.read().as_ref(), server_id, config.version) {
+        debug!("忽略旧版本的集中管理配置: {}", config.version);
+        return Ok(false);
+    }
+    let state = ManagedState {
+        version: config.version,
+        server_id: server_id.to_string(),
+        layout: serde_json::from_str(&config.layout)?,
+        settings: serde_json::from_str(&config.settings)?,
+        policy: serde_json::from_str(&config.policy)?,
+    };
+    info!("应用集中管理配置，版本: {}", state.version);
+    install(&state)?;
+    save_and_notify(&state)?;
+    *APPLIED.write() = Some(state);
+    Ok(true)
+}
+
+/// 不再作为客户端时清除已应用的配置，恢复使用本地配置
+pub fn clear() {
+    if APPLIED.write().take().is_none() {
+        return;
+    }
+    info!("清除集中管理配置");
+    if let Err(e) = config::layout::set_managed(None) {
+        warn!("无法恢复本地屏幕布局: {}", e);
+    }
+    config::device::clear_managed();
+    config::clipboard::set_managed_policy(None);
+    if let Some(handle) = core::handle::Handle::instance().app_handle()
+        && let Err(e) =
+            handle.emit(constant::EVENT_MANAGED_CONFIG, None::<ManagedSummary>)
+    {
+        warn!("无法通知集中管理配置变化: {}", e);
+    }
+}
+
+/// 布局、设置与过滤策略只保存在内存中，不覆盖本地配置
+///
+/// 客户端按服务端的设备 id 查询设置，设置以服务端 id 为键并转换为本机视角
+fn install(state: &ManagedState) -> Result<()> {
+    config::layout::set_managed(Some(state.layout.clone()))?;
+    config::device::set_managed(&state.server_id, state.settings.mirrored());
+    config::clipboard::set_managed_policy(Some(state.policy.clone()));
+    Ok(())
+}
+
+fn save_and_notify(state: &ManagedState) -> Result<()> {
+    if let Some(handle) = core::handle::Handle::instance().app_handle() {
+        handle.valtio().set(
+            constant::STORE_ID,
+            KEY,
+            serde_json::to_value(state)?,
+        )?;
+        handle.emit(constant::EVENT_MANAGED_CONFIG, state.summary())?;
+    }
+    Ok(())
+}
+
+/// 作为客户端时从存储加载上次应用的配置
+pub fn load(app: &AppHandle) {
+    if is_server() {
+        return;
+    }
+    match app.valtio().try_get::<ManagedState>(constant::STORE_ID, KEY) {
+        Ok(state) => {
+            info!("从存储加载集中管理配置，版本: {}", state.version);
+            if let Err(e) = install(&state) {
+                warn!("无法应用集中管理配置: {}", e);
+                return;
+            }
+            *APPLIED.write() = Some(state);
+        }
+        Err(err) => warn!("无法从存储加载集中管理配置: {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(server_id: &str, version: u64) -> ManagedState {
+        ManagedState {
+            version,
+            server_id: server_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn next_version_is_monotonic() {
+        assert_eq!(next_version(5, 10), 10);
+        assert_eq!(next_version(10, 10), 11);
+        // 时钟回拨
+        assert_eq!(next_version(10, 3), 11);
+    }
+
+    #[test]
+    fn applies_only_newer_versions_from_the_same_server() {
+        let applied = state("server", 10);
+
+        assert!(should_apply(None, "server", 1));
+        assert!(should_apply(Some(&applied), "server", 11));
+        assert!(!should_apply(Some(&applied), "server", 10));
+        assert!(!should_apply(Some(&applied), "server", 9));
+        assert!(should_apply(Some(&applied), "other", 9));
+    }
+}
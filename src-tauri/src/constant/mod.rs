@@ -25,5 +25,22 @@ pub const MENU_ITEM_ID_QUIT: &str = "Quit";
 pub const MENU_ITEM_ID_RESTART: &str = "Restart";
 /// screen-layout 菜单按钮id
 pub const MENU_ITEM_ID_SCREEN_LAYOUT: &str = "ScreenLayout";
+/// cursor-lock 菜单按钮id
+pub const MENU_ITEM_ID_CURSOR_LOCK: &str = "CursorLock";
+/// type-clipboard 菜单按钮id
+pub const MENU_ITEM_ID_TYPE_CLIPBOARD: &str = "TypeClipboard";
 /// settings 菜单按钮id
 pub const MENU_ITEM_ID_SETTINGS: &str = "Settings";
+
+/// 页面跳转事件
+pub const EVENT_NAVIGATE: &str = "navigate";
+/// 输入目标切换事件
+pub const EVENT_TARGET_CHANGED: &str = "target-changed";
+/// 光标锁定状态变化事件
+pub const EVENT_CURSOR_LOCK: &str = "cursor-lock";
+/// 服务端下发的配置已应用事件
+pub const EVENT_MANAGED_CONFIG: &str = "managed-config";
+/// 剪贴板历史变化事件
+pub const EVENT_CLIPBOARD_HISTORY: &str = "clipboard-history";
+/// 文件传输进度事件
+pub const EVENT_FILE_TRANSFER: &str = "file-transfer";
@@ -0,0 +1,79 @@
+use parking_lot::Mutex;
+use spdlog::{debug, error, info, warn};
+
+use crate::{
+    config::{self, network::ServiceType},
+    core::handle::Handle,
+    service::{
+        client::tcp::TcpClient,
+        protocols::base::{DataPacket, DisplayInfo, PacketData},
+        server::{router::InputRouter, tcp::TcpServer},
+    },
+};
+
+// 上次获取的本机显示器，显示器变化时用于按比例调整布局
+static LAST_DISPLAYS: Mutex<Vec<DisplayInfo>> = Mutex::new(Vec::new());
+
+/// 记录本机当前的显示器，在开始监听显示器变化前调用
+pub fn init() {
+    *LAST_DISPLAYS.lock() = local_displays();
+}
+
+/// 本机所有显示器，坐标与尺寸均为物理像素
+pub fn local_displays() -> Vec<DisplayInfo> {
+    let Some(app_handle) = Handle::instance().app_handle() else {
+        return Vec::new();
+    };
+    let primary = app_handle.primary_monitor().ok().flatten();
+    match app_handle.available_monitors() {
+        Ok(monitors) => monitors
+            .iter()
+            .map(|monitor| DisplayInfo {
+                x: monitor.position().x,
+                y: monitor.position().y,
+                width: monitor.size().width,
+                height: monitor.size().height,
+                scale: monitor.scale_factor(),
+                primary: primary.as_ref().is_some_and(|primary| {
+                    primary.position() == monitor.position()
+                }),
+            })
+            .collect(),
+        Err(e) => {
+            error!("Failed to get monitors: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// 本机显示器配置变化后调用，将新的显示器列表发送给对端
+///
+/// 作为服务端时按新的显示器调整布局并更新输入路由，布局变化随集中管理配置下发给客户端；
+/// 作为客户端时由服务端调整布局
+pub fn on_displays_changed() {
+    let displays = local_displays();
+    info!("Local displays changed: {:?}", displays);
+    let previous =
+        std::mem::replace(&mut *LAST_DISPLAYS.lock(), displays.clone());
+    tauri::async_runtime::spawn(async move {
+        let device_id = config::system::config().unwrap_or_default().id();
+        match config::network::get_config().service_type() {
+            ServiceType::Server => {
+                if let Err(e) =
+                    config::layout::resize(&device_id, &previous, &displays)
+                {
+                    warn!("Failed to resize local screen: {}", e);
+                }
+                TcpServer::instance().broadcast(PacketData::Displays(displays));
+                InputRouter::instance().on_displays_changed(&device_id).await;
+            }
+            ServiceType::Client => {
+                let packet =
+                    DataPacket::new(device_id, PacketData::Displays(displays));
+                if let Err(e) = TcpClient::instance().send(packet) {
+                    debug!("Failed to send displays: {}", e);
+                }
+            }
+        }
+    });
+}
@@ -1,2 +1,3 @@
+pub mod display;
 pub mod handle;
 pub mod tray;
@@ -1,26 +1,33 @@
 use std::sync::OnceLock;
 
 use anyhow::{Result, anyhow};
-use spdlog::info;
+use parking_lot::RwLock;
+use spdlog::{error, info};
 use tauri::{
-    AppHandle, Manager, Wry,
-    menu::{Menu, MenuBuilder, MenuEvent, MenuItem, PredefinedMenuItem},
+    AppHandle, Emitter, Manager, Wry,
+    menu::{
+        CheckMenuItem, Menu, MenuBuilder, MenuEvent, MenuItem,
+        PredefinedMenuItem,
+    },
     tray::{
         MouseButton, MouseButtonState, TrayIcon, TrayIconEvent, TrayIconId,
     },
 };
 
-use crate::constant;
+use crate::{constant, service::server::router::InputRouter};
 
 use super::handle::Handle;
 
-pub struct Tray {}
+pub struct Tray {
+    // 光标锁定菜单项
+    cursor_lock: RwLock<Option<CheckMenuItem<Wry>>>,
+}
 
 impl Tray {
     /// 获取单例
     pub fn instance() -> &'static Self {
         static TRAY: OnceLock<Tray> = OnceLock::new();
-        TRAY.get_or_init(|| Tray {})
+        TRAY.get_or_init(|| Tray { cursor_lock: RwLock::new(None) })
     }
 
     /// 初始化
@@ -54,14 +61,14 @@ impl Tray {
                 }
             }
         });
-        let menu = Self::create_menu(&app_handle)?;
+        let menu = self.create_menu(&app_handle)?;
         tray.set_menu(Some(menu))?;
         tray.on_menu_event(Self::on_menu_event);
         Ok(())
     }
 
     /// 创建菜单
-    fn create_menu(app_handle: &AppHandle) -> Result<Menu<Wry>> {
+    fn create_menu(&self, app_handle: &AppHandle) -> Result<Menu<Wry>> {
         // 退出按钮
         let quit = &MenuItem::with_id(
             app_handle,
@@ -89,6 +96,26 @@ impl Tray {
             None::<&str>,
         )?;
 
+        // 光标锁定
+        let cursor_lock = &CheckMenuItem::with_id(
+            app_handle,
+            constant::MENU_ITEM_ID_CURSOR_LOCK,
+            t!("menu.cursor-lock"),
+            true,
+            InputRouter::instance().cursor_lock().is_some(),
+            None::<&str>,
+        )?;
+        *self.cursor_lock.write() = Some(cursor_lock.clone());
+
+        // 将剪贴板作为按键输入
+        let type_clipboard = &MenuItem::with_id(
+            app_handle,
+            constant::MENU_ITEM_ID_TYPE_CLIPBOARD,
+            t!("menu.type-clipboard"),
+            true,
+            None::<&str>,
+        )?;
+
         // 设置按钮
         let settings = &MenuItem::with_id(
             app_handle,
@@ -102,7 +129,15 @@ impl Tray {
         let separator = &PredefinedMenuItem::separator(app_handle)?;
 
         MenuBuilder::new(app_handle)
-            .items(&[screen_layout, settings, separator, restart, quit])
+            .items(&[
+                screen_layout,
+                cursor_lock,
+                type_clipboard,
+                settings,
+                separator,
+                restart,
+                quit,
+            ])
             .build()
             .map_err(|e| anyhow!("Create Menu Error {:?}", e))
     }
@@ -120,7 +155,23 @@ impl Tray {
             }
             constant::MENU_ITEM_ID_SCREEN_LAYOUT => {
                 // 屏幕布局设置
-                todo!();
+                Self::navigate(app_handle, "/screen-layout");
+            }
+            constant::MENU_ITEM_ID_CURSOR_LOCK => {
+                // 手动锁定光标
+                tauri::async_runtime::spawn(async {
+                    InputRouter::instance().toggle_cursor_lock().await;
+                });
+            }
+            constant::MENU_ITEM_ID_TYPE_CLIPBOARD => {
+                // 将剪贴板文本输入到当前目标
+                tauri::async_runtime::spawn(async {
+                    if let Err(e) =
+                        InputRouter::instance().type_clipboard().await
+                    {
+                        error!("Failed to type clipboard: {}", e);
+                    }
+                });
             }
             constant::MENU_ITEM_ID_SETTINGS => {
                 // 设置
@@ -130,6 +181,28 @@ impl Tray {
         }
     }
 
+    /// 显示主窗口并跳转到指定页面
+    fn navigate(app_handle: &AppHandle, path: &str) {
+        if let Some(window) =
+            app_handle.get_webview_window(constant::MAIN_WINDOW_LABEL)
+        {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+        if let Err(e) = app_handle.emit(constant::EVENT_NAVIGATE, path) {
+            error!("Failed to emit navigate event: {}", e);
+        }
+    }
+
+    /// 同步光标锁定状态到菜单项
+    pub fn set_cursor_lock(&self, locked: bool) {
+        if let Some(item) = self.cursor_lock.read().as_ref()
+            && let Err(e) = item.set_checked(locked)
+        {
+            error!("Failed to update cursor lock menu: {}", e);
+        }
+    }
+
     /// 更新提示
     pub fn update_tooltip(&self) {
         unimplemented!()
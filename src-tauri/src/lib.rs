@@ -29,6 +29,74 @@ pub fn run() {
         
         // 设置网络配置监听
         config::network::setup_config_watcher(app.handle())?;
+        // 设置设备配置监听
+        config::device::setup_config_watcher(app.handle())?;
+        // 设置热键配置监听
+        config::hotkey::setup_config_watcher(app.handle())?;
+        // 设置剪贴板配置监听
+        config::clipboard::setup_config_watcher(app.handle())?;
+        // 加载剪贴板历史
+        service::clipboard::history::ClipboardHistory::instance()
+            .load(app_data_dir.join("clipboard-history.bin"));
+        // 未配置下载目录时接收的文件放到系统下载目录
+        let transfers = service::transfer::FileTransfers::instance();
+        match app.path().download_dir() {
+            Ok(dir) => transfers.set_default_dir(dir),
+            Err(e) => spdlog::warn!("Failed to get download dir: {}", e),
+        }
+        // 加载屏幕布局
+        config::layout::load(app.handle());
+        // 作为客户端时加载服务端下发的配置
+        config::sync::load(app.handle());
+        // 注册前台窗口查询，用于按前台应用自动锁定光标
+        #[cfg(target_os = "linux")]
+        match service::input::x11::X11FocusProbe::connect() {
+            Ok(probe) => service::input::cursor_lock::set_focus_probe(
+                std::sync::Arc::new(probe),
+            ),
+            Err(e) => spdlog::warn!("Failed to connect to X11: {}", e),
+        }
+        // 注册输入注入与捕获，其他平台暂无后端，使用空实现
+        #[cfg(target_os = "linux")]
+        match service::input::x11::X11Injector::connect() {
+            Ok(injector) => service::input::inject::set_injector(
+                std::sync::Arc::new(injector),
+            ),
+            Err(e) => spdlog::warn!("Failed to open input injector: {}", e),
+        }
+        #[cfg(target_os = "linux")]
+        match service::input::x11::X11Capture::start() {
+            Ok(capture) => service::input::capture::set_capture(
+                std::sync::Arc::new(capture),
+            ),
+            Err(e) => spdlog::warn!("Failed to start input capture: {}", e),
+        }
+        // 注册系统剪贴板，X11 下支持懒加载
+        #[cfg(target_os = "linux")]
+        let backend: anyhow::Result<
+            std::sync::Arc<dyn service::clipboard::backend::ClipboardBackend>,
+        > = service::clipboard::x11::X11Clipboard::connect(
+            service::protocols::clipboard::Selection::Clipboard,
+        )
+        .map(|backend| std::sync::Arc::new(backend) as _);
+        #[cfg(not(target_os = "linux"))]
+        let backend: anyhow::Result<
+            std::sync::Arc<dyn service::clipboard::backend::ClipboardBackend>,
+        > = service::clipboard::backend::SystemClipboard::new()
+            .map(|backend| std::sync::Arc::new(backend) as _);
+        match backend {
+            Ok(backend) => service::clipboard::sync::ClipboardSync::instance()
+                .set_backend(backend),
+            Err(e) => spdlog::warn!("Failed to open clipboard: {}", e),
+        }
+        // 监听显示器插拔
+        core::display::init();
+        #[cfg(target_os = "linux")]
+        if let Err(e) = service::input::x11::watch_displays(
+            core::display::on_displays_changed,
+        ) {
+            spdlog::warn!("Failed to watch display changes: {}", e);
+        }
         
         Ok(())
     });
@@ -58,6 +126,29 @@ pub fn run() {
             api::sys::local_ip,
             // util
             api::util::generate_uuid,
+            // layout
+            api::layout::get_layout,
+            api::layout::set_layout,
+            api::layout::place_screen,
+            api::layout::remove_screen,
+            api::layout::cross_edge,
+            // router
+            api::router::get_input_target,
+            api::router::switch_input_target,
+            api::router::get_cursor_lock,
+            api::router::set_cursor_lock,
+            api::router::type_clipboard,
+            api::router::cancel_typing,
+            // sync
+            api::sync::get_managed_config,
+            // clipboard
+            api::clipboard::list_clipboard_history,
+            api::clipboard::search_clipboard_history,
+            api::clipboard::recopy_clipboard_history,
+            api::clipboard::delete_clipboard_history,
+            // transfer
+            api::transfer::list_file_transfers,
+            api::transfer::cancel_file_transfer,
             // log
             api::log::trace,
             api::log::debug,
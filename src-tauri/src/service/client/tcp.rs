@@ -1,20 +1,36 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use futures_util::StreamExt;
 use parking_lot::RwLock;
-use spdlog::{error, info};
+use spdlog::{debug, error, info, warn};
 use std::sync::{Arc, OnceLock};
 use tokio::{
     net::TcpStream,
     select,
     sync::{mpsc, oneshot},
     task::JoinHandle,
+    time::Instant,
 };
 use tokio_util::codec::Framed;
 
+use crate::config;
 use crate::service::{
-    ServiceControl,
+    self, ServiceControl,
     client::mdns::MdnsClient,
-    codec::{DataPacketCodec, DataPacketReader, DataPacketWriter},
+    clipboard::{
+        compress,
+        policy::{self, Direction},
+        primary,
+        sync::ClipboardSync,
+    },
+    codec::{DataPacketCodec, DataPacketReader},
+    input::inject::InjectSession,
+    protocols::{
+        base::{DataPacket, PacketData},
+        clipboard::{Clipboard, Selection},
+        transfer::Transfer,
+    },
+    sender::PacketSender,
+    transfer::{self, FileTransfers},
 };
 
 use super::ServerInfo;
@@ -31,7 +47,12 @@ pub enum ConnectionState {
 }
 
 pub struct TcpClient {
-    writer: Arc<RwLock<Option<DataPacketWriter>>>,
+    // 连接的发送端，未连接时为空
+    sender: RwLock<Option<PacketSender>>,
+    // 服务端支持的能力，握手后更新
+    server_caps: RwLock<Vec<String>>,
+    // 服务端设备 id，握手后更新
+    server_id: RwLock<String>,
     service_control: ServiceControl,
     state_tx: mpsc::Sender<ConnectionState>,
 }
@@ -46,7 +67,9 @@ impl TcpClient {
             tokio::spawn(Self::handle_state_changes(rx));
 
             TcpClient {
-                writer: Arc::new(RwLock::new(None)),
+                sender: RwLock::new(None),
+                server_caps: RwLock::new(Vec::new()),
+                server_id: RwLock::new(String::new()),
                 service_control: ServiceControl::new("Tcp Client".to_string()),
                 state_tx: tx,
             }
@@ -87,7 +110,6 @@ impl TcpClient {
         if self.is_running() {
             self.stop().await?;
         }
-        let writer = self.writer.clone();
         let server_info = Arc::new(server_info);
         let state_tx = self.state_tx.clone();
 
@@ -103,11 +125,17 @@ impl TcpClient {
                             info!("Connected to server: {}", server_addr);
                             let framed =
                                 Framed::new(stream, DataPacketCodec::default());
-                            let (split_writer, reader) = framed.split();
-                            {
-                                let mut writer_guard = writer.write();
-                                *writer_guard = Some(split_writer);
-                            }
+                            let (writer, reader) = framed.split();
+                            let (sender, writer_task) =
+                                PacketSender::spawn(writer);
+                            *Self::instance().sender.write() =
+                                Some(sender.clone());
+
+                            // 握手，发送本机设备信息
+                            Self::reply(PacketData::Init(
+                                service::local_device_info(),
+                            ))
+                            .await;
 
                             // 发送连接成功状态
                             if let Err(e) =
@@ -129,9 +157,14 @@ impl TcpClient {
                                 error!("Failed to send state change: {}", e);
                             }
 
-                            // 清理 writer
-                            let mut writer_guard = writer.write();
-                            *writer_guard = None;
+                            // 发送完队列中的数据后关闭连接
+                            Self::instance().sender.write().take();
+                            sender.close();
+                            if let Err(e) = writer_task.await {
+                                error!("Failed to close connection: {}", e);
+                            }
+                            Self::instance().server_caps.write().clear();
+                            Self::instance().server_id.write().clear();
                         }
                         Err(e) => {
                             error!(
@@ -159,45 +192,192 @@ impl TcpClient {
     }
 
     pub async fn stop(&self) -> Result<()> {
-        // 清理 writer
-        {
-            let mut writer_guard = self.writer.write();
-            *writer_guard = None;
+        if let Some(sender) = self.sender.write().take() {
+            sender.close();
         }
         self.service_control.stop().await
     }
 
+    fn sender(&self) -> Result<PacketSender> {
+        self.sender.read().clone().ok_or_else(|| anyhow!("Not connected"))
+    }
+
+    /// 当前连接的服务端设备 id，未完成握手时为空
+    pub fn server_id(&self) -> String {
+        self.server_id.read().clone()
+    }
+
+    /// 向服务端发送数据，只放入发送队列，不等待网络
+    pub fn send(&self, data: DataPacket) -> Result<()> {
+        self.sender()?.send(data)
+    }
+
+    /// 向服务端发送剪贴板消息，按服务端支持的算法压缩
+    pub async fn send_clipboard(
+        &self,
+        selection: Selection,
+        message: &Clipboard,
+    ) -> Result<()> {
+        if !primary::supported_by(&self.server_caps.read(), selection) {
+            return Ok(());
+        }
+        let server_id = self.server_id.read().clone();
+        let Some(message) =
+            policy::filter(&server_id, Direction::Send, message)
+        else {
+            return Ok(());
+        };
+        let algorithm = compress::negotiate(&self.server_caps.read());
+        let message = compress::compress_message(message, algorithm).await?;
+        let device_id = config::system::config().unwrap_or_default().id();
+        self.send(DataPacket::new(
+            device_id,
+            PacketData::clipboard(selection, message),
+        ))
+    }
+
+    /// 向设备 `device_id` 发送文件传输消息，不是服务端时由服务端转发
+    pub async fn send_transfer(
+        &self,
+        device_id: &str,
+        message: Transfer,
+    ) -> Result<()> {
+        if !self.server_caps.read().iter().any(|cap| cap == transfer::CAP_FILES)
+        {
+            return Err(anyhow!("Server does not support file transfer"));
+        }
+        let local_id = config::system::config().unwrap_or_default().id();
+        let bulk = matches!(message, Transfer::Chunk { .. });
+        let data = PacketData::File { to: device_id.to_string(), msg: message };
+        let packet = DataPacket::new(local_id, data);
+        if bulk { self.sender()?.send_bulk(packet) } else { self.send(packet) }
+    }
+
+    /// 处理服务端转发的剪贴板消息
+    async fn on_clipboard(
+        origin: &str,
+        selection: Selection,
+        message: Clipboard,
+    ) {
+        let message = match compress::decompress_message(message).await {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Drop clipboard: {}", e);
+                return;
+            }
+        };
+        let Some(message) =
+            policy::filter(origin, Direction::Receive, &message)
+        else {
+            return;
+        };
+        let handled = primary::handle(selection, origin, message).await;
+        if let Some(reply) = handled.reply
+            && let Err(e) =
+                Self::instance().send_clipboard(selection, &reply).await
+        {
+            error!("Failed to reply clipboard: {}", e);
+        }
+    }
+
     async fn handle_connection(
         mut reader: DataPacketReader,
         mut rx: oneshot::Receiver<bool>,
         server_info: &ServerInfo,
     ) -> ConnectionState {
-        loop {
+        let mut inject = InjectSession::new();
+        let mut pointer_check =
+            tokio::time::interval(tokio::time::Duration::from_millis(250));
+        let state = loop {
+            let deadline = inject.next_deadline();
             select! {
+                _ = Self::sleep_until(deadline) => {
+                    inject.poll();
+                }
+                _ = pointer_check.tick() => {
+                    if let Some(reply) = inject.check_pointer_lock() {
+                        Self::reply(reply).await;
+                    }
+                }
                 _ = &mut rx => {
                     info!("Received shutdown signal");
-                    return ConnectionState::Disconnected;
+                    break ConnectionState::Disconnected;
                 },
                 result = reader.next() => {
                     match result {
                         None => {
                             // 连接断开
                             info!("Connection closed for {}", server_info.ip);
-                            return ConnectionState::Disconnected;
+                            break ConnectionState::Disconnected;
                         }
-                        Some(Ok(_data)) => {
-                            // Message received
-                            todo!("Not Implemented")
+                        Some(Ok(data)) => {
+                            match data.deserialize() {
+                                Ok(DataPacket { data: PacketData::Init(device_info), .. }) => {
+                                    info!(
+                                        "Handshake with {} ({}) completed, {} display(s)",
+                                        device_info.name,
+                                        device_info.id,
+                                        device_info.displays.len()
+                                    );
+                                    *Self::instance().server_id.write() = device_info.id;
+                                    *Self::instance().server_caps.write() = device_info.caps;
+                                    tauri::async_runtime::spawn(ClipboardSync::sync_history());
+                                }
+                                Ok(DataPacket { d, data: PacketData::Managed(managed), .. }) => {
+                                    if let Err(e) = config::sync::apply(&d, managed) {
+                                        error!("Failed to apply managed config: {}", e);
+                                    }
+                                }
+                                Ok(DataPacket { d, data: PacketData::Clip(message), .. }) => {
+                                    Self::on_clipboard(&d, Selection::Clipboard, message).await;
+                                }
+                                Ok(DataPacket { d, data: PacketData::Primary(message), .. }) => {
+                                    Self::on_clipboard(&d, Selection::Primary, message).await;
+                                }
+                                Ok(DataPacket { d, data: PacketData::File { msg, .. }, .. }) => {
+                                    FileTransfers::instance().dispatch(&d, msg);
+                                }
+                                Ok(DataPacket { data: PacketData::Displays(displays), .. }) => {
+                                    // 服务端已按新的显示器调整布局，布局随集中管理配置下发
+                                    debug!("Server now has {} display(s)", displays.len());
+                                }
+                                Ok(packet) => {
+                                    if let Some(reply) = inject.handle(&packet.data) {
+                                        Self::reply(reply).await;
+                                    }
+                                }
+                                Err(e) => error!("{}", e),
+                            }
                         }
                         Some(Err(e)) => {
                             // 连接错误
                             let error_msg = format!("Failed to read from connection: {}", e);
                             error!("{}", error_msg);
-                            return ConnectionState::Error(error_msg);
+                            break ConnectionState::Error(error_msg);
                         }
                     }
                 }
             }
+        };
+        // 控制端已消失，释放仍处于按下状态的键和按钮
+        inject.release_all();
+        state
+    }
+
+    /// 回复控制端
+    async fn reply(data: PacketData) {
+        let device_id = config::system::config().unwrap_or_default().id();
+        if let Err(e) = Self::instance().send(DataPacket::new(device_id, data))
+        {
+            error!("Failed to send reply: {}", e);
+        }
+    }
+
+    /// 等待到指定时间，未指定时永不完成
+    async fn sleep_until(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
         }
     }
 }
@@ -0,0 +1,272 @@
+use std::{borrow::Cow, sync::Arc};
+
+use anyhow::{Result, anyhow, bail};
+use image::{ImageFormat, RgbaImage};
+use parking_lot::Mutex;
+use spdlog::debug;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::service::protocols::clipboard::{ClipData, ClipType};
+
+use super::image::WIRE_FORMAT;
+
+/// 获取懒加载内容，会阻塞到对端返回数据，不能在后端的事件线程中调用
+pub type Fetch = Arc<dyn Fn(ClipType) -> Result<ClipData> + Send + Sync>;
+
+/// 懒加载内容，应用请求某种格式时才通过 `fetch` 获取
+#[derive(Clone)]
+pub struct LazyContent {
+    pub types: Vec<ClipType>,
+    pub fetch: Fetch,
+}
+
+/// 来源应用对内容设置的提示
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClipHints {
+    // 密码等敏感内容，不应同步或记录
+    pub concealed: bool,
+    // 临时内容，不应记录到历史
+    pub transient: bool,
+}
+
+/// 剪贴板后端
+pub trait ClipboardBackend: Send + Sync {
+    /// 读取当前内容的所有表示，剪贴板为空、格式不支持或内容由本后端提供时
+    /// 返回空列表
+    fn read(&self) -> Result<Vec<ClipData>>;
+
+    /// 写入同一内容的多种表示，由应用粘贴时选择合适的格式
+    fn write(&self, items: &[ClipData]) -> Result<()>;
+
+    /// 提供懒加载内容，返回 `false` 表示不支持，由调用方获取后再写入
+    fn offer(&self, _content: LazyContent) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// 清空剪贴板
+    fn clear(&self) -> Result<()>;
+
+    /// 剪贴板变化计数，不变时无需读取内容，`None` 表示后端无法感知变化
+    fn sequence(&self) -> Option<u64> {
+        None
+    }
+
+    /// 最近一次读取的内容附带的提示，平台不支持时为默认值
+    fn hints(&self) -> ClipHints {
+        ClipHints::default()
+    }
+
+    /// 写入图片时偏好的格式，对端的图片写入前转换为该格式
+    fn image_format(&self) -> ImageFormat {
+        WIRE_FORMAT
+    }
+}
+
+/// 内存剪贴板，未注册系统剪贴板时使用，也便于测试
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    content: Mutex<Vec<ClipData>>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ClipboardBackend for MemoryClipboard {
+    fn read(&self) -> Result<Vec<ClipData>> {
+        Ok(self.content.lock().clone())
+    }
+
+    fn write(&self, items: &[ClipData]) -> Result<()> {
+        *self.content.lock() = items.to_vec();
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.content.lock().clear();
+        Ok(())
+    }
+}
+
+/// 系统剪贴板，支持纯文本、HTML 富文本与图片，RTF 写入时丢弃
+///
+/// 不支持懒加载，对端提供的内容会立即获取后写入，懒加载只在 X11 下可用
+pub struct SystemClipboard {
+    inner: Mutex<arboard::Clipboard>,
+    // 上次读取或写入的图片，按像素哈希索引。像素不变时直接返回其编码，避免每次
+    // 轮询都重新编码，写入的图片读回时也不会因重新编码被当作新内容
+    encoded: Mutex<Option<(u64, ClipData)>>,
+    // 最近一次读取时来源应用设置的提示
+    hints: Mutex<ClipHints>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            inner: Mutex::new(arboard::Clipboard::new()?),
+            encoded: Mutex::new(None),
+            hints: Mutex::new(ClipHints::default()),
+        })
+    }
+
+    fn read_image(
+        &self,
+        clipboard: &mut arboard::Clipboard,
+    ) -> Result<Option<ClipData>> {
+        let Some(image) = available(clipboard.get_image())? else {
+            return Ok(None);
+        };
+        let hash = xxh3_64(&image.bytes);
+        if let Some((encoded, data)) = self.encoded.lock().as_ref()
+            && *encoded == hash
+        {
+            return Ok(Some(data.clone()));
+        }
+        let image = RgbaImage::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.bytes.into_owned(),
+        )
+        .ok_or_else(|| anyhow!("Invalid clipboard image"))?;
+        let mut bytes = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut bytes), WIRE_FORMAT)?;
+        let data = ClipData::image(bytes);
+        *self.encoded.lock() = Some((hash, data.clone()));
+        Ok(Some(data))
+    }
+
+    fn write_image(
+        &self,
+        clipboard: &mut arboard::Clipboard,
+        data: &ClipData,
+    ) -> Result<()> {
+        let image = image::load_from_memory(&data.data)?.to_rgba8();
+        let hash = xxh3_64(image.as_raw());
+        clipboard.set_image(arboard::ImageData {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            bytes: Cow::Owned(image.into_raw()),
+        })?;
+        *self.encoded.lock() = Some((hash, data.clone()));
+        Ok(())
+    }
+}
+
+impl ClipboardBackend for SystemClipboard {
+    fn read(&self) -> Result<Vec<ClipData>> {
+        let mut clipboard = self.inner.lock();
+        let mut items = Vec::new();
+        if let Some(image) = self.read_image(&mut clipboard)? {
+            items.push(image);
+        }
+        if let Some(html) = available(clipboard.get().html())? {
+            items.push(ClipData::html(html));
+        }
+        if let Some(text) = available(clipboard.get_text())? {
+            items.push(ClipData::text(text));
+        }
+        *self.hints.lock() = system_hints();
+        Ok(items)
+    }
+
+    fn write(&self, items: &[ClipData]) -> Result<()> {
+        let text_of = |ty: ClipType| {
+            items
+                .iter()
+                .find(|item| item.ty == ty)
+                .map(|item| String::from_utf8(item.data.clone()))
+                .transpose()
+        };
+        let html = text_of(ClipType::Rich)?;
+        let text = text_of(ClipType::Text)?;
+        if items.iter().any(|item| item.ty == ClipType::Rtf) {
+            debug!("System clipboard does not support RTF, drop it");
+        }
+        let mut clipboard = self.inner.lock();
+        // 系统剪贴板一次只能写入图片或文本，有图片时优先写入图片
+        if let Some(image) = items.iter().find(|item| item.ty == ClipType::Img)
+        {
+            return self.write_image(&mut clipboard, image);
+        }
+        match (html, text) {
+            (Some(html), text) => clipboard.set_html(html, text)?,
+            (None, Some(text)) => clipboard.set_text(text)?,
+            (None, None) => bail!(
+                "Unsupported clipboard types: {:?}",
+                items.iter().map(|item| &item.ty).collect::<Vec<_>>()
+            ),
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.inner.lock().clear()?;
+        Ok(())
+    }
+
+    fn hints(&self) -> ClipHints {
+        *self.hints.lock()
+    }
+}
+
+/// 读取来源应用设置的提示，参见
+/// <https://learn.microsoft.com/windows/win32/dataxchg/clipboard-formats#cloud-clipboard-and-clipboard-history-formats>
+#[cfg(target_os = "windows")]
+fn system_hints() -> ClipHints {
+    use clipboard_win::raw;
+
+    let format = |name: &str| {
+        raw::register_format(name)
+            .map(|format| format.get())
+            .filter(|format| raw::is_format_avail(*format))
+    };
+    // 值为 0 时不进入系统的剪贴板历史
+    let transient =
+        format("CanIncludeInClipboardHistory").is_some_and(|format| {
+            let Ok(_clipboard) = clipboard_win::Clipboard::new_attempts(10)
+            else {
+                return false;
+            };
+            let mut value = [0; 4];
+            matches!(raw::get(format, &mut value), Ok(4))
+                && u32::from_ne_bytes(value) == 0
+        });
+    ClipHints {
+        concealed: format("ExcludeClipboardContentFromMonitorProcessing")
+            .is_some(),
+        transient,
+    }
+}
+
+/// 读取来源应用设置的提示，参见 <http://nspasteboard.org>
+#[cfg(target_os = "macos")]
+fn system_hints() -> ClipHints {
+    use objc2_app_kit::NSPasteboard;
+    use objc2_foundation::NSString;
+
+    let Some(types) = NSPasteboard::generalPasteboard().types() else {
+        return ClipHints::default();
+    };
+    let has = |name: &str| types.containsObject(&NSString::from_str(name));
+    ClipHints {
+        concealed: has("org.nspasteboard.ConcealedType"),
+        transient: has("org.nspasteboard.TransientType"),
+    }
+}
+
+/// 其他平台的系统剪贴板没有提示，X11 由 `X11Clipboard` 读取
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn system_hints() -> ClipHints {
+    ClipHints::default()
+}
+
+/// 剪贴板中没有该格式时返回 `None`
+fn available<T>(result: Result<T, arboard::Error>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(arboard::Error::ContentNotAvailable) => Ok(None),
+        Err(e) => Err(anyhow!(e)),
+    }
+}
@@ -0,0 +1,210 @@
+use std::io::Read;
+
+use anyhow::{Result, anyhow, bail};
+use spdlog::{debug, warn};
+
+use crate::service::protocols::clipboard::{ClipData, Clipboard, Compression};
+
+// 小于该大小的内容不压缩
+const MIN_SIZE: usize = 1024;
+// 压缩后至少节省 1/8 才使用压缩结果，否则视为不可压缩
+const MIN_SAVING: usize = 8;
+const ZSTD_LEVEL: i32 = 3;
+/// 解压后的最大大小，超过时拒绝，防止解压炸弹
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+const CAP_ZSTD: &str = "clip-zstd";
+const CAP_LZ4: &str = "clip-lz4";
+
+/// 本机支持的压缩算法，通过 `DeviceInfo::caps` 告知对端
+pub fn capabilities() -> Vec<String> {
+    vec![CAP_ZSTD.to_string(), CAP_LZ4.to_string()]
+}
+
+/// 按对端能力选择压缩算法，优先 zstd
+pub fn negotiate(caps: &[String]) -> Compression {
+    if caps.iter().any(|cap| cap == CAP_ZSTD) {
+        Compression::Zstd
+    } else if caps.iter().any(|cap| cap == CAP_LZ4) {
+        Compression::Lz4
+    } else {
+        Compression::None
+    }
+}
+
+/// 压缩内容，内容过小或压缩收益不足时保持原样
+pub fn compress(data: &mut ClipData, algorithm: Compression) -> Result<()> {
+    if data.comp != Compression::None || data.data.len() < MIN_SIZE {
+        return Ok(());
+    }
+    let compressed = match algorithm {
+        Compression::None => return Ok(()),
+        Compression::Zstd => zstd::bulk::compress(&data.data, ZSTD_LEVEL)?,
+        Compression::Lz4 => lz4_flex::compress_prepend_size(&data.data),
+    };
+    if compressed.len() > data.data.len() - data.data.len() / MIN_SAVING {
+        debug!(
+            "Skip compression, {} -> {} bytes",
+            data.data.len(),
+            compressed.len()
+        );
+        return Ok(());
+    }
+    debug!(
+        "Compressed {:?} {} -> {} bytes",
+        algorithm,
+        data.data.len(),
+        compressed.len()
+    );
+    data.data = compressed;
+    data.comp = algorithm;
+    Ok(())
+}
+
+/// 解压内容，解压后超过 `MAX_DECOMPRESSED_SIZE` 时返回错误
+pub fn decompress(data: &mut ClipData) -> Result<()> {
+    decompress_limited(data, MAX_DECOMPRESSED_SIZE)
+}
+
+fn decompress_limited(data: &mut ClipData, limit: usize) -> Result<()> {
+    let raw = match data.comp {
+        Compression::None => return Ok(()),
+        Compression::Zstd => {
+            let mut raw = Vec::new();
+            zstd::stream::read::Decoder::new(data.data.as_slice())?
+                .take(limit as u64 + 1)
+                .read_to_end(&mut raw)?;
+            if raw.len() > limit {
+                bail!("Decompressed size exceeds {}", limit);
+            }
+            raw
+        }
+        Compression::Lz4 => {
+            // 先检查声明的大小，避免按恶意的大小分配内存
+            let size = data
+                .data
+                .first_chunk::<4>()
+                .map(|size| u32::from_le_bytes(*size) as usize)
+                .ok_or_else(|| anyhow!("Truncated lz4 data"))?;
+            if size > limit {
+                bail!("Decompressed size {} exceeds {}", size, limit);
+            }
+            lz4_flex::decompress_size_prepended(&data.data)?
+        }
+    };
+    data.data = raw;
+    data.comp = Compression::None;
+    Ok(())
+}
+
+/// 按对端支持的算法压缩消息中的内容，压缩失败时发送原始内容
+///
+/// 较大的内容压缩耗时较长，在后台线程中进行
+pub async fn compress_message(
+    mut message: Clipboard,
+    algorithm: Compression,
+) -> Result<Clipboard> {
+    if algorithm == Compression::None || contents(&mut message).is_empty() {
+        return Ok(message);
+    }
+    let message = tauri::async_runtime::spawn_blocking(move || {
+        for data in contents(&mut message) {
+            if let Err(e) = compress(data, algorithm) {
+                warn!("Failed to compress clipboard: {}", e);
+            }
+        }
+        message
+    })
+    .await?;
+    Ok(message)
+}
+
+/// 在后台线程中解压消息中的内容
+pub async fn decompress_message(mut message: Clipboard) -> Result<Clipboard> {
+    if contents(&mut message).iter().all(|data| data.comp == Compression::None)
+    {
+        return Ok(message);
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        contents(&mut message).into_iter().try_for_each(decompress)?;
+        Ok(message)
+    })
+    .await?
+}
+
+fn contents(message: &mut Clipboard) -> Vec<&mut ClipData> {
+    match message {
+        Clipboard::Set { items, .. } | Clipboard::Data { items, .. } => {
+            items.iter_mut().collect()
+        }
+        Clipboard::History { entries, .. } => entries
+            .iter_mut()
+            .flat_map(|entry| entry.items.iter_mut())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn text(size: usize) -> ClipData {
+        ClipData::text("sync-pointer ".repeat(size / 13 + 1))
+    }
+
+    #[test]
+    fn round_trip() {
+        for algorithm in [Compression::Zstd, Compression::Lz4] {
+            let original = text(64 * 1024);
+            let mut data = original.clone();
+
+            compress(&mut data, algorithm).unwrap();
+            assert_eq!(data.comp, algorithm);
+            assert!(data.data.len() < original.data.len());
+            decompress(&mut data).unwrap();
+            assert_eq!(data, original);
+        }
+    }
+
+    #[test]
+    fn small_content_is_not_compressed() {
+        let mut data = text(100);
+
+        compress(&mut data, Compression::Zstd).unwrap();
+        assert_eq!(data.comp, Compression::None);
+    }
+
+    #[test]
+    fn decompression_bomb_is_rejected() {
+        let mut encoder =
+            zstd::stream::write::Encoder::new(Vec::new(), 3).unwrap();
+        encoder.write_all(&[0; 64 * 1024]).unwrap();
+        let mut zstd = ClipData::image(encoder.finish().unwrap());
+        zstd.comp = Compression::Zstd;
+        let mut lz4 =
+            ClipData::image(lz4_flex::compress_prepend_size(&[0; 64 * 1024]));
+        lz4.comp = Compression::Lz4;
+
+        assert!(decompress_limited(&mut zstd, 1024).is_err());
+        assert!(decompress_limited(&mut lz4, 1024).is_err());
+        assert!(decompress_limited(&mut lz4, 64 * 1024).is_ok());
+    }
+
+    #[tokio::test]
+    async fn message_round_trip() {
+        let items = vec![text(64 * 1024), ClipData::html("<b>sync</b>")];
+        let message = Clipboard::Set { items: items.clone(), force: false };
+
+        let compressed =
+            compress_message(message, Compression::Zstd).await.unwrap();
+        let Clipboard::Set { items: packed, .. } = &compressed else {
+            panic!("unexpected message {:?}", compressed);
+        };
+        assert_eq!(packed[0].comp, Compression::Zstd);
+        let restored = decompress_message(compressed).await.unwrap();
+        assert_eq!(restored, Clipboard::Set { items, force: false });
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use rkyv::{Archive, rancor::Error as RancorError};
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, error, info, warn};
+use tauri::Emitter as _;
+use tokio::time::Duration;
+
+use crate::{
+    config, constant, core,
+    service::{
+        codec::MAX_FRAME_LENGTH,
+        protocols::clipboard::{ClipData, ClipEntry, ClipType},
+    },
+};
+
+use super::sync::hash;
+
+// 合并短时间内的多次修改后再写入文件
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+// 摘要中预览文本的最大字符数
+const PREVIEW_CHARS: usize = 200;
+// 同步历史时每页的最大条数与字节数，超过字节数的单条内容不同步
+const PAGE_ENTRIES: usize = 64;
+const PAGE_BYTES: u64 = MAX_FRAME_LENGTH as u64 / 2;
+
+/// 剪贴板历史设置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    // 最多保留的条数
+    pub max_entries: usize,
+    // 所有条目内容的总字节数上限
+    pub max_bytes: u64,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self { enabled: true, max_entries: 200, max_bytes: 64 * 1024 * 1024 }
+    }
+}
+
+/// 历史条目
+#[derive(
+    Archive, rkyv::Serialize, rkyv::Deserialize, PartialEq, Debug, Clone,
+)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub hash: u64,
+    // 本机记录或移到最前时的序号，对端按序号增量同步
+    pub seq: u64,
+    pub ts: u64,
+    pub origin: String,
+    pub items: Vec<ClipData>,
+}
+
+impl HistoryEntry {
+    fn size(&self) -> u64 {
+        self.items.iter().map(|item| item.data.len() as u64).sum()
+    }
+
+    fn text(&self) -> Option<String> {
+        [ClipType::Text, ClipType::Files, ClipType::Rich]
+            .iter()
+            .find_map(|ty| self.items.iter().find(|item| &item.ty == ty))
+            .map(|item| String::from_utf8_lossy(&item.data).into_owned())
+    }
+
+    fn summary(&self) -> HistorySummary {
+        HistorySummary {
+            id: self.id,
+            ts: self.ts,
+            origin: self.origin.clone(),
+            types: self
+                .items
+                .iter()
+                .map(|item| item.ty.mime().to_string())
+                .collect(),
+            size: self.size(),
+            preview: self
+                .text()
+                .map(|text| text.chars().take(PREVIEW_CHARS).collect()),
+        }
+    }
+}
+
+/// 历史条目摘要，供界面展示
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistorySummary {
+    pub id: u64,
+    pub ts: u64,
+    // 来源设备 id
+    pub origin: String,
+    // 各表示的 MIME 类型
+    pub types: Vec<String>,
+    pub size: u64,
+    // 文本内容的开头，图片等内容为空
+    pub preview: Option<String>,
+}
+
+/// 历史文件的内容
+#[derive(Archive, rkyv::Serialize, rkyv::Deserialize, Default)]
+struct HistoryFile {
+    seq: u64,
+    entries: Vec<HistoryEntry>,
+    cursors: HashMap<String, u64>,
+}
+
+/// 剪贴板历史
+///
+/// 记录本机与对端的内容，新的在前，按条数与总字节数淘汰旧条目。
+/// 相同内容再次出现时移到最前。修改后延迟写入文件，重启后恢复。
+///
+/// 每次记录分配递增的序号，对端记住同步到的序号，之后只获取更新的条目，
+/// 不依赖各设备的时钟
+pub struct ClipboardHistory {
+    entries: Mutex<VecDeque<HistoryEntry>>,
+    // 最近分配的序号，清空历史后仍递增
+    seq: AtomicU64,
+    // 已从各设备同步到的序号
+    cursors: Mutex<HashMap<String, u64>>,
+    path: RwLock<Option<PathBuf>>,
+    dirty: AtomicBool,
+}
+
+impl ClipboardHistory {
+    pub fn instance() -> &'static Self {
+        static INSTANCE: OnceLock<ClipboardHistory> = OnceLock::new();
+        INSTANCE.get_or_init(Self::new)
+    }
+
+    fn new() -> Self {
+        ClipboardHistory {
+            entries: Mutex::new(VecDeque::new()),
+            seq: AtomicU64::new(0),
+            cursors: Mutex::new(HashMap::new()),
+            path: RwLock::new(None),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// 从文件加载历史，之后的修改也写入该文件
+    pub fn load(&self, path: PathBuf) {
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                match rkyv::from_bytes::<HistoryFile, RancorError>(&bytes) {
+                    Ok(file) => {
+                        info!("加载剪贴板历史 {} 条", file.entries.len());
+                        let seq = file.entries.iter().map(|e| e.seq).max();
+                        self.seq.store(
+                            file.seq.max(seq.unwrap_or_default()),
+                            Ordering::SeqCst,
+                        );
+                        *self.entries.lock() = file.entries.into();
+                        *self.cursors.lock() = file.cursors;
+                    }
+                    Err(e) => warn!("无法解析剪贴板历史: {}", e),
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("无法读取剪贴板历史: {}", e),
+        }
+        *self.path.write() = Some(path);
+    }
+
+    /// 记录内容，相同哈希的条目合并后移到最前
+    pub fn record(&self, hash: u64, ts: u64, origin: &str, items: &[ClipData]) {
+        let settings = config::clipboard::get_config().history;
+        if !settings.enabled || items.is_empty() {
+            return;
+        }
+        {
+            let mut entries = self.entries.lock();
+            let mut entry = match entries.iter().position(|e| e.hash == hash) {
+                Some(index) => entries.remove(index).unwrap(),
+                None => HistoryEntry {
+                    id: next_id(&entries),
+                    hash,
+                    seq: 0,
+                    ts,
+                    origin: origin.to_string(),
+                    items: Vec::new(),
+                },
+            };
+            entry.seq = self.next_seq();
+            for item in items {
+                if !entry.items.iter().any(|it| it.ty == item.ty) {
+                    entry.items.push(item.clone());
+                }
+            }
+            entry.ts = entry.ts.max(ts);
+            entries.push_front(entry);
+            Self::evict(&mut entries, &settings);
+        }
+        self.changed();
+    }
+
+    /// 合并对端的历史，已有的内容不覆盖
+    pub fn merge(&self, incoming: Vec<ClipEntry>) {
+        let settings = config::clipboard::get_config().history;
+        if !settings.enabled {
+            return;
+        }
+        let merged = {
+            let mut entries = self.entries.lock();
+            let mut merged = 0;
+            for entry in incoming {
+                let hash = hash(&entry.items);
+                if entry.items.is_empty()
+                    || entries.iter().any(|e| e.hash == hash)
+                {
+                    continue;
+                }
+                let id = next_id(&entries);
+                entries.push_back(HistoryEntry {
+                    id,
+                    hash,
+                    seq: self.next_seq(),
+                    ts: entry.ts,
+                    origin: entry.origin,
+                    items: entry.items,
+                });
+                merged += 1;
+            }
+            // 合并的条目可能比已有条目旧，按时间重新排序
+            entries
+                .make_contiguous()
+                .sort_by_key(|entry| std::cmp::Reverse(entry.ts));
+            Self::evict(&mut entries, &settings);
+            merged
+        };
+        if merged > 0 {
+            debug!("Merged {} clipboard history entries", merged);
+            self.changed();
+        }
+    }
+
+    /// 序号大于 `since` 的条目，从旧到新按类型过滤并分页
+    ///
+    /// 返回本页的条目、本页对应的序号与之后是否还有条目。`since` 大于本机的
+    /// 序号时说明本机历史已重置，从头开始
+    pub fn page(
+        &self,
+        since: u64,
+        types: Option<&[ClipType]>,
+    ) -> (Vec<ClipEntry>, u64, bool) {
+        let since =
+            if since > self.seq.load(Ordering::SeqCst) { 0 } else { since };
+        let entries = self.entries.lock();
+        let mut pending: Vec<&HistoryEntry> =
+            entries.iter().filter(|entry| entry.seq > since).collect();
+        pending.sort_by_key(|entry| entry.seq);
+        let (mut page, mut bytes, mut cursor) = (Vec::new(), 0, since);
+        for entry in &pending {
+            let items: Vec<ClipData> = entry
+                .items
+                .iter()
+                .filter(|item| {
+                    types.is_none_or(|types| types.contains(&item.ty))
+                })
+                .cloned()
+                .collect();
+            let size: u64 =
+                items.iter().map(|item| item.data.len() as u64).sum();
+            if size <= PAGE_BYTES {
+                if page.len() >= PAGE_ENTRIES || bytes + size > PAGE_BYTES {
+                    break;
+                }
+                bytes += size;
+                if !items.is_empty() {
+                    page.push(ClipEntry {
+                        items,
+                        ts: entry.ts,
+                        origin: entry.origin.clone(),
+                    });
+                }
+            }
+            cursor = entry.seq;
+        }
+        let more = pending.last().is_some_and(|last| last.seq > cursor);
+        (page, cursor, more)
+    }
+
+    /// 已从设备 `origin` 同步到的序号
+    pub fn cursor(&self, origin: &str) -> u64 {
+        self.cursors.lock().get(origin).copied().unwrap_or_default()
+    }
+
+    /// 记录已从设备 `origin` 同步到的序号
+    pub fn set_cursor(&self, origin: &str, cursor: u64) {
+        let previous = self.cursors.lock().insert(origin.to_string(), cursor);
+        if previous != Some(cursor) {
+            self.changed();
+        }
+    }
+
+    /// 所有条目的摘要
+    pub fn list(&self) -> Vec<HistorySummary> {
+        self.entries.lock().iter().map(HistoryEntry::summary).collect()
+    }
+
+    /// 文本内容或来源设备包含 `query` 的条目，不区分大小写
+    pub fn search(&self, query: &str) -> Vec<HistorySummary> {
+        let query = query.to_lowercase();
+        self.entries
+            .lock()
+            .iter()
+            .filter(|entry| {
+                entry.origin.to_lowercase().contains(&query)
+                    || entry.text().is_some_and(|text| {
+                        text.to_lowercase().contains(&query)
+                    })
+            })
+            .map(HistoryEntry::summary)
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<HistoryEntry> {
+        self.entries.lock().iter().find(|entry| entry.id == id).cloned()
+    }
+
+    /// 删除条目，返回是否存在
+    pub fn remove(&self, id: u64) -> bool {
+        let removed = {
+            let mut entries = self.entries.lock();
+            let len = entries.len();
+            entries.retain(|entry| entry.id != id);
+            entries.len() != len
+        };
+        if removed {
+            self.changed();
+        }
+        removed
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+        self.changed();
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn evict(entries: &mut VecDeque<HistoryEntry>, settings: &HistorySettings) {
+        entries.truncate(settings.max_entries);
+        let mut total: u64 = entries.iter().map(HistoryEntry::size).sum();
+        // 至少保留最新的一条
+        while total > settings.max_bytes && entries.len() > 1 {
+            if let Some(entry) = entries.pop_back() {
+                total -= entry.size();
+            }
+        }
+    }
+
+    /// 通知界面并安排写入文件
+    fn changed(&self) {
+        if let Some(handle) = core::handle::Handle::instance().app_handle()
+            && let Err(e) = handle.emit(constant::EVENT_CLIPBOARD_HISTORY, ())
+        {
+            debug!("Failed to emit clipboard history: {}", e);
+        }
+        if self.dirty.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        tauri::async_runtime::spawn(async {
+            tokio::time::sleep(SAVE_DELAY).await;
+            let this = Self::instance();
+            this.dirty.store(false, Ordering::SeqCst);
+            let result =
+                tokio::task::spawn_blocking(|| Self::instance().save()).await;
+            if let Err(e) = result.map_err(anyhow::Error::from).and_then(|r| r)
+            {
+                error!("Failed to save clipboard history: {}", e);
+            }
+        });
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = self.path.read().clone() else {
+            return Ok(());
+        };
+        let file = HistoryFile {
+            seq: self.seq.load(Ordering::SeqCst),
+            entries: self.entries.lock().iter().cloned().collect(),
+            cursors: self.cursors.lock().clone(),
+        };
+        let bytes = rkyv::to_bytes::<RancorError>(&file)?;
+        // 先写入临时文件再替换，避免写入中途退出损坏历史
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, &bytes)?;
+        std::fs::rename(&temp, &path)?;
+        debug!("Saved {} clipboard history entries", file.entries.len());
+        Ok(())
+    }
+}
+
+fn next_id(entries: &VecDeque<HistoryEntry>) -> u64 {
+    entries.iter().map(|entry| entry.id + 1).max().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Vec<ClipData> {
+        vec![ClipData::text(text)]
+    }
+
+    fn record(history: &ClipboardHistory, ts: u64, content: &str) {
+        let items = text(content);
+        history.record(hash(&items), ts, "local", &items);
+    }
+
+    fn texts(history: &ClipboardHistory) -> Vec<String> {
+        history.list().into_iter().filter_map(|s| s.preview).collect()
+    }
+
+    #[tokio::test]
+    async fn recording_again_moves_to_front() {
+        let history = ClipboardHistory::new();
+        record(&history, 1, "a");
+        record(&history, 2, "b");
+        record(&history, 3, "a");
+
+        assert_eq!(texts(&history), ["a", "b"]);
+        let (entries, cursor, more) = history.page(2, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!((cursor, more), (3, false));
+    }
+
+    #[tokio::test]
+    async fn merge_skips_known_content_and_orders_by_time() {
+        let history = ClipboardHistory::new();
+        record(&history, 10, "local");
+        let entry = |ts: u64, content: &str| ClipEntry {
+            items: text(content),
+            ts,
+            origin: "peer".into(),
+        };
+        history.merge(vec![
+            entry(5, "old"),
+            entry(20, "local"),
+            entry(15, "new"),
+        ]);
+
+        assert_eq!(texts(&history), ["new", "local", "old"]);
+    }
+
+    #[test]
+    fn evict_keeps_limits_and_the_newest_entry() {
+        let entry = |id: u64, size: usize| HistoryEntry {
+            id,
+            hash: id,
+            seq: id,
+            ts: id,
+            origin: "local".into(),
+            items: vec![ClipData::text("x".repeat(size))],
+        };
+        let settings =
+            HistorySettings { enabled: true, max_entries: 3, max_bytes: 10 };
+        let mut entries: VecDeque<_> =
+            [entry(4, 4), entry(3, 4), entry(2, 4), entry(1, 4)].into();
+        ClipboardHistory::evict(&mut entries, &settings);
+        let ids: Vec<u64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [4, 3]);
+
+        let mut entries: VecDeque<_> = [entry(2, 20), entry(1, 1)].into();
+        ClipboardHistory::evict(&mut entries, &settings);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, 2);
+    }
+
+    #[tokio::test]
+    async fn pages_are_bounded_and_resume_from_the_cursor() {
+        let history = ClipboardHistory::new();
+        let count = PAGE_ENTRIES + 10;
+        for i in 0..count {
+            record(&history, i as u64, &i.to_string());
+        }
+
+        let (first, cursor, more) = history.page(0, None);
+        assert_eq!(first.len(), PAGE_ENTRIES);
+        assert!(more);
+        let (rest, end, more) = history.page(cursor, None);
+        assert_eq!(rest.len(), 10);
+        assert!(!more);
+        let last = &rest.last().unwrap().items[0];
+        assert_eq!(last.data, (count - 1).to_string().into_bytes());
+        // 对端的序号超过本机时从头开始
+        assert_eq!(history.page(end + 1, None), (first, cursor, true));
+    }
+
+    #[tokio::test]
+    async fn history_survives_reload() {
+        let path = std::env::temp_dir()
+            .join(format!("clipboard-history-{}.bin", std::process::id()));
+        let history = ClipboardHistory::new();
+        history.load(path.clone());
+        record(&history, 1, "a");
+        record(&history, 2, "b");
+        history.set_cursor("server", 7);
+        history.clear();
+        record(&history, 3, "c");
+        history.save().unwrap();
+
+        let loaded = ClipboardHistory::new();
+        loaded.load(path.clone());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(texts(&loaded), ["c"]);
+        assert_eq!(loaded.cursor("server"), 7);
+        // 清空后序号仍递增，对端不会漏掉新条目
+        assert_eq!(loaded.page(2, None).1, 3);
+        record(&loaded, 4, "d");
+        assert_eq!(loaded.page(3, None).1, 4);
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::Cursor;
+
+use anyhow::{Result, bail};
+use image::{
+    DynamicImage, ImageFormat, ImageReader, Limits, imageops::FilterType,
+};
+use serde::{Deserialize, Serialize};
+use spdlog::debug;
+
+use crate::service::protocols::clipboard::{ClipData, ClipType};
+
+/// 传输使用的图片格式
+pub const WIRE_FORMAT: ImageFormat = ImageFormat::Png;
+// 缩小前需要完整解码，像素数超过最大像素数的此倍数时直接拒绝
+const MAX_DOWNSCALE: u64 = 4;
+
+/// 剪贴板图片设置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageSettings {
+    // 最大像素数
+    pub max_pixels: u64,
+    // 超过最大像素数时缩小，为假时丢弃图片
+    pub downscale: bool,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self { max_pixels: 32 * 1024 * 1024, downscale: true }
+    }
+}
+
+/// 将 BMP、TIFF 等格式的图片统一转为传输使用的 PNG
+pub fn normalize(
+    data: &ClipData,
+    settings: &ImageSettings,
+) -> Result<ClipData> {
+    convert(data, WIRE_FORMAT, settings)
+}
+
+/// 将图片转为 `format` 格式，超过最大像素数时按设置缩小或返回错误
+///
+/// 非图片内容，以及格式相同且大小未超限的图片保持原样。解码前先按文件头中的
+/// 尺寸检查，解码时也不允许超过该尺寸。解码与编码较慢，在后台线程中调用
+pub fn convert(
+    data: &ClipData,
+    format: ImageFormat,
+    settings: &ImageSettings,
+) -> Result<ClipData> {
+    if data.ty != ClipType::Img {
+        return Ok(data.clone());
+    }
+    let source = image::guess_format(&data.data)?;
+    let reader = || ImageReader::with_format(Cursor::new(&data.data), source);
+    let (width, height) = reader().into_dimensions()?;
+    let pixels = width as u64 * height as u64;
+    let oversized = pixels > settings.max_pixels;
+    if source == format && !oversized {
+        return Ok(data.clone());
+    }
+    let limit = if settings.downscale {
+        settings.max_pixels.saturating_mul(MAX_DOWNSCALE)
+    } else {
+        settings.max_pixels
+    };
+    if pixels > limit {
+        bail!("Image {}x{} exceeds {} pixels", width, height, limit);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(width);
+    limits.max_image_height = Some(height);
+    // 足够容纳文件头中尺寸的 16 位 RGBA 图像
+    limits.max_alloc = Some(pixels.saturating_mul(8));
+    let mut reader = reader();
+    reader.limits(limits);
+    let mut image = reader.decode()?;
+    if oversized {
+        let scale = (settings.max_pixels as f64 / pixels as f64).sqrt();
+        let scaled = |size: u32| ((size as f64 * scale) as u32).max(1);
+        debug!(
+            "Downscale image {}x{} -> {}x{}",
+            width,
+            height,
+            scaled(width),
+            scaled(height)
+        );
+        image =
+            image.resize(scaled(width), scaled(height), FilterType::Triangle);
+    }
+    let mut converted = ClipData::image(encode(image, format)?);
+    converted.ts = data.ts;
+    debug!(
+        "Converted image {:?} {} bytes -> {:?} {} bytes",
+        source,
+        data.data.len(),
+        format,
+        converted.data.len()
+    );
+    Ok(converted)
+}
+
+fn encode(image: DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    // JPEG 不支持透明通道，BMP 不支持 16 位色深
+    let image = match format {
+        ImageFormat::Png | ImageFormat::Tiff => image,
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    };
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), format)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::RgbaImage;
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> ClipData {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(width, height));
+        ClipData::image(encode(image, ImageFormat::Png).unwrap())
+    }
+
+    #[test]
+    fn oversized_images_are_checked_before_decoding() {
+        let data = png(100, 100);
+        let keep = ImageSettings { max_pixels: 5000, downscale: false };
+        let downscale = ImageSettings { max_pixels: 5000, downscale: true };
+        let tiny = ImageSettings { max_pixels: 100, downscale: true };
+
+        assert!(convert(&data, ImageFormat::Png, &keep).is_err());
+        // 远超最大像素数时即使允许缩小也不解码
+        assert!(convert(&data, ImageFormat::Png, &tiny).is_err());
+        let converted = convert(&data, ImageFormat::Png, &downscale).unwrap();
+        let (width, height) = ImageReader::new(Cursor::new(&converted.data))
+            .with_guessed_format()
+            .unwrap()
+            .into_dimensions()
+            .unwrap();
+        assert!(width as u64 * height as u64 <= 5000);
+    }
+}
@@ -0,0 +1,20 @@
+pub mod backend;
+pub mod compress;
+pub mod history;
+pub mod image;
+pub mod policy;
+pub mod primary;
+pub mod sync;
+#[cfg(target_os = "linux")]
+pub mod x11;
+
+/// 本机支持的剪贴板能力，通过 `DeviceInfo::caps` 告知对端
+pub fn capabilities() -> Vec<String> {
+    let mut caps = compress::capabilities();
+    // 服务端不接收时也为其他设备转发 PRIMARY 选区
+    if sync::is_server() || primary::accepts() {
+        caps.push(primary::CAP_PRIMARY.to_string());
+    }
+    caps.push(super::transfer::CAP_FILES.to_string());
+    caps
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, warn};
+
+use crate::{
+    config::{self, network::ServiceType},
+    service::{
+        self,
+        client::tcp::TcpClient,
+        protocols::{
+            base::{DataPacket, PacketData},
+            clipboard::{Clipboard, Selection},
+        },
+    },
+};
+
+use super::sync::{ClipboardSync, Handled, is_server};
+
+/// 支持接收 PRIMARY 选区的能力，通过 `DeviceInfo::caps` 告知对端
+pub const CAP_PRIMARY: &str = "clip-primary";
+
+/// PRIMARY 选区设置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrimarySettings {
+    // 与其他 Linux 设备同步 PRIMARY 选区
+    pub enabled: bool,
+    // 本机没有 PRIMARY 选区或未启用同步时，将对端的 PRIMARY 写入常规剪贴板
+    pub map_to_clipboard: bool,
+}
+
+/// 是否同步本机的 PRIMARY 选区
+pub fn enabled() -> bool {
+    cfg!(target_os = "linux") && config::clipboard::get_config().primary.enabled
+}
+
+/// 是否接收对端的 PRIMARY 选区
+pub fn accepts() -> bool {
+    enabled() || config::clipboard::get_config().primary.map_to_clipboard
+}
+
+/// 交由对应的同步实例处理对端发来的选区内容
+///
+/// 本机不同步 PRIMARY 选区时写入常规剪贴板，不记录历史；不接收时作为服务端
+/// 仍为其他设备转发直接发送的内容
+pub async fn handle(
+    selection: Selection,
+    origin: &str,
+    message: Clipboard,
+) -> Handled {
+    match selection {
+        Selection::Clipboard => {
+            ClipboardSync::instance().handle(origin, message).await
+        }
+        Selection::Primary if enabled() => {
+            ClipboardSync::primary().handle(origin, message).await
+        }
+        Selection::Primary if accepts() => {
+            ClipboardSync::instance().handle_mapped(origin, message).await
+        }
+        Selection::Primary => match message {
+            Clipboard::Set { .. } if is_server() => {
+                Handled { reply: None, relay: Some(message) }
+            }
+            _ => {
+                debug!("Ignore primary selection from {}", origin);
+                Handled::default()
+            }
+        },
+    }
+}
+
+/// 对端是否接收该选区
+pub fn supported_by(caps: &[String], selection: Selection) -> bool {
+    selection == Selection::Clipboard
+        || caps.iter().any(|cap| cap == CAP_PRIMARY)
+}
+
+/// 设置变更后按新的设置开始或停止同步，并向服务端重新告知本机的能力
+pub fn on_settings_changed(previous: PrimarySettings) {
+    if config::clipboard::get_config().primary == previous {
+        return;
+    }
+    let toggled = enabled() != (cfg!(target_os = "linux") && previous.enabled);
+    tauri::async_runtime::spawn(async move {
+        if toggled && let Err(e) = sync().await {
+            warn!("Failed to toggle primary selection sync: {}", e);
+        }
+        // 服务端总是转发 PRIMARY 选区，能力不随设置变化
+        if let ServiceType::Client =
+            config::network::get_config().service_type()
+        {
+            let device_id = config::system::config().unwrap_or_default().id();
+            let data = PacketData::Init(service::local_device_info());
+            if let Err(e) =
+                TcpClient::instance().send(DataPacket::new(device_id, data))
+            {
+                debug!("Failed to announce capabilities: {}", e);
+            }
+        }
+    });
+}
+
+/// 按设置开始或重新开始同步本机的 PRIMARY 选区，未启用时停止
+///
+/// 常规剪贴板未在同步时只注册后端
+pub async fn sync() -> Result<()> {
+    let sync = ClipboardSync::primary();
+    if !enabled() {
+        return sync.stop().await;
+    }
+    register()?;
+    if ClipboardSync::instance().is_running() {
+        sync.start().await?;
+    }
+    Ok(())
+}
+
+/// 注册 X11 的 PRIMARY 选区作为后端，只注册一次
+#[cfg(target_os = "linux")]
+fn register() -> Result<()> {
+    static REGISTERED: parking_lot::Mutex<bool> =
+        parking_lot::Mutex::new(false);
+    let mut registered = REGISTERED.lock();
+    if !*registered {
+        let backend = super::x11::X11Clipboard::connect(Selection::Primary)?;
+        ClipboardSync::primary().set_backend(std::sync::Arc::new(backend));
+        *registered = true;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn register() -> Result<()> {
+    Ok(())
+}
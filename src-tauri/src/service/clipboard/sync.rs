@@ -0,0 +1,1029 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Result, anyhow, bail};
+use parking_lot::{Mutex, RwLock};
+use spdlog::{debug, error, info, warn};
+use tokio::{
+    select,
+    sync::oneshot,
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+    config::{self, network::ServiceType},
+    service::{
+        ServiceControl,
+        client::tcp::TcpClient,
+        protocols::clipboard::{ClipData, ClipType, Clipboard, Selection},
+        server::tcp::TcpServer,
+        transfer::FileTransfers,
+    },
+};
+
+use super::{
+    backend::{ClipboardBackend, Fetch, LazyContent, MemoryClipboard},
+    history::{ClipboardHistory, HistoryEntry},
+    image, policy,
+};
+
+// 本地剪贴板轮询间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// 两台设备在此时间内先后修改剪贴板时视为冲突
+const CONFLICT_WINDOW: Duration = Duration::from_millis(500);
+// 只有文本且总大小不超过该值的内容直接发送，其他内容只发送摘要，对端粘贴时再获取
+const EAGER_TEXT_SIZE: usize = 64 * 1024;
+// 获取对端内容的超时时间
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 剪贴板内容哈希，覆盖所有表示且与顺序无关，用于识别回环
+pub fn hash(items: &[ClipData]) -> u64 {
+    let mut items: Vec<&ClipData> = items.iter().collect();
+    items.sort_by_key(|item| type_tag(&item.ty));
+    let mut hasher = Xxh3::new();
+    for item in items {
+        hasher.update(&[type_tag(&item.ty)]);
+        hasher.update(&(item.data.len() as u64).to_le_bytes());
+        hasher.update(&item.data);
+    }
+    hasher.digest()
+}
+
+fn type_tag(ty: &ClipType) -> u8 {
+    match ty {
+        ClipType::Text => 0,
+        ClipType::Rich => 1,
+        ClipType::Img => 2,
+        ClipType::Files => 3,
+        ClipType::Rtf => 4,
+    }
+}
+
+/// 是否为可以直接发送的较小文本
+fn eager(items: &[ClipData]) -> bool {
+    let size: u64 = items.iter().map(|item| item.data.len() as u64).sum();
+    items.iter().all(|item| item.ty.is_text()) && size <= EAGER_TEXT_SIZE as u64
+}
+
+/// 较小的文本直接发送，其他内容只发送摘要
+fn announce(hash: u64, ts: u64, items: Vec<ClipData>) -> Clipboard {
+    if eager(&items) {
+        Clipboard::Set { items, force: false }
+    } else {
+        Clipboard::Offer {
+            items: items.iter().map(ClipData::meta).collect(),
+            hash,
+            ts,
+        }
+    }
+}
+
+/// 将本地读取的图片转为传输格式，无法转换的图片不同步
+///
+/// 图片解码与编码较慢，在后台线程中调用
+fn normalize(items: Vec<ClipData>) -> Vec<ClipData> {
+    let settings = config::clipboard::get_config().image;
+    items
+        .into_iter()
+        .filter_map(|item| {
+            image::normalize(&item, &settings)
+                .inspect_err(|e| warn!("Skip clipboard image: {}", e))
+                .ok()
+        })
+        .collect()
+}
+
+/// 将内容转换为后端偏好的图片格式后写入，返回写入内容的哈希
+///
+/// 图片转换与后端写入都可能阻塞，在后台线程中调用
+fn write_backend(
+    backend: &dyn ClipboardBackend,
+    items: &[ClipData],
+) -> Result<u64> {
+    let settings = config::clipboard::get_config().image;
+    let items: Vec<ClipData> = items
+        .iter()
+        .filter_map(|item| {
+            image::convert(item, backend.image_format(), &settings)
+                .inspect_err(|e| warn!("Drop clipboard image: {}", e))
+                .ok()
+        })
+        .collect();
+    if items.is_empty() {
+        bail!("No clipboard content left to write");
+    }
+    backend.write(&items)?;
+    Ok(hash(&items))
+}
+
+/// 内容的时间戳，取各表示中最新的
+fn timestamp(items: &[ClipData]) -> u64 {
+    items.iter().map(|item| item.ts).max().unwrap_or_default()
+}
+
+/// 剪贴板当前内容的版本
+#[derive(Debug, Clone)]
+struct Revision {
+    hash: u64,
+    // 内容产生时来源设备的时间戳
+    ts: u64,
+    // 来源设备 id
+    origin: String,
+    // 本机收到或读到的时间
+    at: Instant,
+}
+
+impl Revision {
+    fn new(hash: u64, ts: u64, origin: &str) -> Self {
+        Self { hash, ts, origin: origin.to_string(), at: Instant::now() }
+    }
+
+    /// 冲突时时间戳较大者胜出，相同时设备 id 较大者胜出，各设备得出相同结果
+    fn beats(&self, other: &Self) -> bool {
+        (self.ts, &self.origin) > (other.ts, &other.origin)
+    }
+
+    /// 是否接受新内容，与当前内容相同或在冲突中落败时不接受
+    fn accepts(current: Option<&Self>, incoming: &Self) -> bool {
+        let Some(current) = current else {
+            return true;
+        };
+        if current.hash == incoming.hash {
+            debug!("Ignore clipboard echo from {}", incoming.origin);
+            return false;
+        }
+        if current.origin != incoming.origin
+            && current.at.elapsed() < CONFLICT_WINDOW
+            && current.beats(incoming)
+        {
+            info!(
+                "Clipboard conflict, keep {} over {}",
+                current.origin, incoming.origin
+            );
+            return false;
+        }
+        true
+    }
+}
+
+/// 已有完整数据的内容，用于响应对端的获取请求
+struct Source {
+    hash: u64,
+    items: Vec<ClipData>,
+}
+
+/// 对端提供、尚未获取的内容
+struct RemoteOffer {
+    hash: u64,
+    origin: String,
+}
+
+/// 处理对端剪贴板消息的结果
+#[derive(Debug, Default)]
+pub struct Handled {
+    // 回复发送方的消息
+    pub reply: Option<Clipboard>,
+    // 需要转发给其他设备的消息
+    pub relay: Option<Clipboard>,
+}
+
+/// 剪贴板同步
+///
+/// 轮询本地剪贴板，内容变化时发送给对端；对端的内容写入本地剪贴板。
+/// 一条内容可同时包含 HTML、纯文本等多种表示，整体按哈希记录，写入后再读回
+/// 的相同内容不会再发送出去。较小的文本直接发送，其他内容只发送摘要，对端的
+/// 应用粘贴时才按哈希获取。
+///
+/// 常规剪贴板与 X11 的 PRIMARY 选区各有一个实例，PRIMARY 只同步较小的文本，
+/// 不记录历史
+pub struct ClipboardSync {
+    selection: Selection,
+    backend: RwLock<Arc<dyn ClipboardBackend>>,
+    current: Mutex<Option<Revision>>,
+    source: Mutex<Option<Source>>,
+    offer: Mutex<Option<RemoteOffer>>,
+    // 等待中的获取请求，按内容哈希索引
+    pending: Mutex<HashMap<u64, Vec<oneshot::Sender<Vec<ClipData>>>>>,
+    // 上次读取时后端的变化计数
+    sequence: Mutex<Option<u64>>,
+    // 上次读取或写入本地剪贴板的原始内容哈希，图片转换前的内容按此识别
+    written: Mutex<Option<u64>>,
+    // 按接受的顺序依次写入本地剪贴板
+    writing: tokio::sync::Mutex<()>,
+    service_control: ServiceControl,
+}
+
+impl ClipboardSync {
+    /// 常规剪贴板
+    pub fn instance() -> &'static Self {
+        static INSTANCE: OnceLock<ClipboardSync> = OnceLock::new();
+        INSTANCE.get_or_init(|| Self::new(Selection::Clipboard))
+    }
+
+    /// PRIMARY 选区
+    pub fn primary() -> &'static Self {
+        static INSTANCE: OnceLock<ClipboardSync> = OnceLock::new();
+        INSTANCE.get_or_init(|| Self::new(Selection::Primary))
+    }
+
+    pub fn of(selection: Selection) -> &'static Self {
+        match selection {
+            Selection::Clipboard => Self::instance(),
+            Selection::Primary => Self::primary(),
+        }
+    }
+
+    fn new(selection: Selection) -> Self {
+        let name = match selection {
+            Selection::Clipboard => "Clipboard Sync",
+            Selection::Primary => "Primary Selection Sync",
+        };
+        ClipboardSync {
+            selection,
+            backend: RwLock::new(Arc::new(MemoryClipboard::new())),
+            current: Mutex::new(None),
+            source: Mutex::new(None),
+            offer: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+            sequence: Mutex::new(None),
+            written: Mutex::new(None),
+            writing: tokio::sync::Mutex::new(()),
+            service_control: ServiceControl::new(name.to_string()),
+        }
+    }
+
+    /// 是否记录历史，PRIMARY 选区变化过于频繁，不记录
+    fn keeps_history(&self) -> bool {
+        self.selection == Selection::Clipboard
+    }
+
+    /// 注册剪贴板后端
+    pub fn set_backend(&self, backend: Arc<dyn ClipboardBackend>) {
+        *self.backend.write() = backend;
+        *self.current.lock() = None;
+        *self.sequence.lock() = None;
+        *self.written.lock() = None;
+    }
+
+    fn backend(&self) -> Arc<dyn ClipboardBackend> {
+        self.backend.read().clone()
+    }
+
+    /// 开始监听本地剪贴板，已在监听时重新开始
+    pub async fn start(&self) -> Result<()> {
+        // 之前连接的对端提供的内容已无法获取
+        *self.offer.lock() = None;
+        self.pending.lock().clear();
+        let selection = self.selection;
+        let start_logic =
+            move |mut rx: oneshot::Receiver<bool>| -> Result<JoinHandle<()>> {
+                let task = tokio::spawn(async move {
+                    let this = Self::of(selection);
+                    // 启动时已有的内容只作为基准，不发送
+                    this.poll_local().await;
+                    let mut interval = tokio::time::interval(POLL_INTERVAL);
+                    loop {
+                        select! {
+                            _ = &mut rx => {
+                                info!("Received shutdown signal");
+                                break;
+                            }
+                            _ = interval.tick() => {
+                                if let Some(message) = this.poll_local().await {
+                                    this.publish(message).await;
+                                }
+                            }
+                        }
+                    }
+                });
+                Ok(task)
+            };
+        self.service_control.start(start_logic).await
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        self.service_control.stop().await
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.service_control.is_running()
+    }
+
+    /// 读取本地剪贴板，内容变化时返回需要发送的消息
+    ///
+    /// 读取时需要等待其他应用响应，在后台线程中进行
+    pub async fn poll_local(&self) -> Option<Clipboard> {
+        let backend = self.backend();
+        let sequence = backend.sequence();
+        if sequence.is_some() && *self.sequence.lock() == sequence {
+            return None;
+        }
+        let read = tauri::async_runtime::spawn_blocking(move || {
+            backend.read().map(|items| (items, backend.hints()))
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|read| read);
+        let (items, hints) = match read {
+            Ok(read) => read,
+            Err(e) => {
+                debug!("Failed to read clipboard: {}", e);
+                return None;
+            }
+        };
+        *self.sequence.lock() = sequence;
+        if items.is_empty() {
+            return None;
+        }
+        let raw = hash(&items);
+        if self.written.lock().replace(raw) == Some(raw) {
+            return None;
+        }
+        let items =
+            tauri::async_runtime::spawn_blocking(move || normalize(items))
+                .await
+                .inspect_err(|e| error!("Failed to normalize clipboard: {}", e))
+                .ok()?;
+        if items.is_empty() || !policy::check_local(&items, &hints) {
+            return None;
+        }
+        if self.selection == Selection::Primary && !eager(&items) {
+            debug!("Skip large primary selection");
+            return None;
+        }
+        let hash = hash(&items);
+        let mut current = self.current.lock();
+        if current.as_ref().is_some_and(|current| current.hash == hash) {
+            return None;
+        }
+        let ts = timestamp(&items);
+        debug!(
+            "Local clipboard changed: {:?}",
+            items.iter().map(ClipData::meta).collect::<Vec<_>>()
+        );
+        let origin = local_device_id();
+        *current = Some(Revision::new(hash, ts, &origin));
+        *self.offer.lock() = None;
+        *self.source.lock() = Some(Source { hash, items: items.clone() });
+        if self.keeps_history() && policy::keep_history(&hints) {
+            ClipboardHistory::instance().record(hash, ts, &origin, &items);
+        }
+        Some(announce(hash, ts, items))
+    }
+
+    /// 将历史中的内容重新写入本地剪贴板，并作为本机的新内容发送给对端
+    pub async fn restore(&self, entry: HistoryEntry) -> Result<()> {
+        let ts = now_ms();
+        let origin = local_device_id();
+        {
+            let mut current = self.current.lock();
+            *current = Some(Revision::new(entry.hash, ts, &origin));
+            *self.offer.lock() = None;
+            *self.source.lock() =
+                Some(Source { hash: entry.hash, items: entry.items.clone() });
+        }
+        self.write_current(entry.hash, entry.items.clone()).await?;
+        ClipboardHistory::instance().record(
+            entry.hash,
+            ts,
+            &origin,
+            &entry.items,
+        );
+        self.publish(announce(entry.hash, ts, entry.items)).await;
+        Ok(())
+    }
+
+    /// 向服务端获取上次同步之后的历史，作为客户端连接后调用
+    pub async fn sync_history() {
+        let server_id = TcpClient::instance().server_id();
+        let since = ClipboardHistory::instance().cursor(&server_id);
+        let message =
+            Clipboard::Get { types: None, since: Some(since), hash: None };
+        if let Err(e) = TcpClient::instance()
+            .send_clipboard(Selection::Clipboard, &message)
+            .await
+        {
+            debug!("Failed to request clipboard history: {}", e);
+        }
+    }
+
+    /// 写入来自设备 `origin` 的内容，`items` 为同一内容的多种表示
+    ///
+    /// 与当前内容相同，或与本机近乎同时的修改冲突且落败时忽略，`force`
+    /// 为真时总是写入。对端的文件路径在本机无意义，改为接收文件，完成后再写入。
+    /// 接受后在后台线程中写入，返回是否接受
+    pub async fn apply_remote(
+        &self,
+        origin: &str,
+        items: &[ClipData],
+        force: bool,
+    ) -> Result<bool> {
+        self.apply(origin, items, force, self.keeps_history()).await
+    }
+
+    /// 写入来自设备 `origin` 的内容，`history` 为真时记录历史
+    async fn apply(
+        &self,
+        origin: &str,
+        items: &[ClipData],
+        force: bool,
+        history: bool,
+    ) -> Result<bool> {
+        let incoming = Revision::new(hash(items), timestamp(items), origin);
+        let (hash, ts) = (incoming.hash, incoming.ts);
+        {
+            let mut current = self.current.lock();
+            if !force && !Revision::accepts(current.as_ref(), &incoming) {
+                return Ok(false);
+            }
+            *current = Some(incoming);
+            *self.offer.lock() = None;
+            *self.source.lock() = Some(Source { hash, items: items.to_vec() });
+        }
+        let local: Vec<ClipData> = items
+            .iter()
+            .filter(|item| item.ty != ClipType::Files)
+            .cloned()
+            .collect();
+        if local.len() < items.len() {
+            FileTransfers::instance().receive(origin, hash);
+        }
+        if !local.is_empty() {
+            self.write_current(hash, local).await?;
+        }
+        if history {
+            ClipboardHistory::instance().record(hash, ts, origin, items);
+        }
+        Ok(true)
+    }
+
+    /// 接受设备 `origin` 提供的内容，后端支持时懒加载，否则立即获取
+    ///
+    /// 返回是否接受
+    pub fn apply_offer(
+        &self,
+        origin: &str,
+        types: Vec<ClipType>,
+        hash: u64,
+        ts: u64,
+    ) -> Result<bool> {
+        let incoming = Revision::new(hash, ts, origin);
+        let mut current = self.current.lock();
+        if !Revision::accepts(current.as_ref(), &incoming) {
+            return Ok(false);
+        }
+        *self.offer.lock() =
+            Some(RemoteOffer { hash, origin: origin.to_string() });
+        *self.source.lock() = None;
+        // 文件另行传输，完成后再写入
+        let files = types.contains(&ClipType::Files);
+        let types: Vec<ClipType> =
+            types.into_iter().filter(|ty| *ty != ClipType::Files).collect();
+        if files {
+            FileTransfers::instance().receive(origin, hash);
+            if types.is_empty() {
+                *current = Some(incoming);
+                return Ok(true);
+            }
+        }
+        let selection = self.selection;
+        // 由后端在单独的线程中调用，可以阻塞
+        let fetch: Fetch = Arc::new(move |ty| {
+            let this = Self::of(selection);
+            let data = tauri::async_runtime::block_on(this.fetch(hash, ty))?;
+            let format = this.backend().image_format();
+            image::convert(
+                &data,
+                format,
+                &config::clipboard::get_config().image,
+            )
+        });
+        let lazy = types.clone();
+        if !self.backend().offer(LazyContent { types: lazy, fetch })? {
+            debug!("Clipboard backend is not lazy, fetch {:x} now", hash);
+            tauri::async_runtime::spawn(async move {
+                let this = Self::of(selection);
+                let result = match this.fetch_items(hash, Some(types)).await {
+                    Ok(items) => this.write_current(hash, items).await,
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    error!("Failed to fetch clipboard: {}", e);
+                }
+            });
+        }
+        *current = Some(incoming);
+        Ok(true)
+    }
+
+    /// 当前内容是否为 `hash`
+    fn is_current(&self, hash: u64) -> bool {
+        self.current.lock().as_ref().is_some_and(|current| current.hash == hash)
+    }
+
+    /// 对端的文件接收完成后，将本地路径与同一内容的其他表示一起写入
+    ///
+    /// 期间剪贴板已变化时放弃，返回是否写入
+    pub async fn apply_files(
+        &self,
+        hash: u64,
+        paths: Vec<String>,
+    ) -> Result<bool> {
+        if !self.is_current(hash) {
+            debug!("Clipboard changed, skip received files");
+            return Ok(false);
+        }
+        let mut items: Vec<ClipData> = self
+            .source
+            .lock()
+            .as_ref()
+            .filter(|source| source.hash == hash)
+            .map(|source| {
+                source
+                    .items
+                    .iter()
+                    .filter(|item| item.ty != ClipType::Files)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        items.insert(0, ClipData::files(paths));
+        self.write_current(hash, items).await
+    }
+
+    /// 本机复制的内容 `hash` 中的文件路径，内容已变化或不含文件时为 `None`
+    pub fn files(&self, hash: u64) -> Option<Vec<PathBuf>> {
+        let items = self.cached(hash, Some(&[ClipType::Files]))?;
+        let files = items.first()?;
+        Some(
+            String::from_utf8_lossy(&files.data)
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+        )
+    }
+
+    /// 将内容 `hash` 写入本地剪贴板，图片先转换为后端偏好的格式并按设置缩小
+    ///
+    /// 在后台线程中写入，不持有任何状态锁。等待写入期间已被更新的内容取代时放弃，
+    /// 返回是否写入
+    async fn write_current(
+        &self,
+        hash: u64,
+        items: Vec<ClipData>,
+    ) -> Result<bool> {
+        let _writing = self.writing.lock().await;
+        if !self.is_current(hash) {
+            return Ok(false);
+        }
+        let backend = self.backend();
+        let written = tauri::async_runtime::spawn_blocking(move || {
+            write_backend(&*backend, &items)
+        })
+        .await??;
+        *self.written.lock() = Some(written);
+        Ok(true)
+    }
+
+    /// 获取哈希为 `hash` 的内容中 `ty` 类型的数据
+    pub async fn fetch(&self, hash: u64, ty: ClipType) -> Result<ClipData> {
+        self.fetch_items(hash, Some(vec![ty.clone()]))
+            .await?
+            .into_iter()
+            .find(|item| item.ty == ty)
+            .ok_or_else(|| anyhow!("Clipboard content has no {:?}", ty))
+    }
+
+    /// 获取哈希为 `hash` 的内容，本机没有时向提供方请求
+    async fn fetch_items(
+        &self,
+        hash: u64,
+        types: Option<Vec<ClipType>>,
+    ) -> Result<Vec<ClipData>> {
+        if let Some(items) = self.cached(hash, types.as_deref()) {
+            return Ok(items);
+        }
+        let origin = self
+            .offer
+            .lock()
+            .as_ref()
+            .filter(|offer| offer.hash == hash)
+            .map(|offer| offer.origin.clone())
+            .ok_or_else(|| anyhow!("Clipboard content {:x} is gone", hash))?;
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().entry(hash).or_default().push(tx);
+        debug!("Fetch clipboard {:x} from {}", hash, origin);
+        let result = async {
+            self.request(
+                &origin,
+                Clipboard::Get { types, since: None, hash: Some(hash) },
+            )
+            .await?;
+            tokio::time::timeout(FETCH_TIMEOUT, rx)
+                .await
+                .map_err(|_| {
+                    anyhow!("Timed out fetching clipboard {:x}", hash)
+                })?
+                .map_err(|_| anyhow!("Clipboard content {:x} is gone", hash))
+        }
+        .await;
+        self.prune_pending(hash);
+        result
+    }
+
+    /// 移除已放弃等待的获取请求
+    fn prune_pending(&self, hash: u64) {
+        let mut pending = self.pending.lock();
+        if let Some(senders) = pending.get_mut(&hash) {
+            senders.retain(|tx| !tx.is_closed());
+            if senders.is_empty() {
+                pending.remove(&hash);
+            }
+        }
+    }
+
+    /// 本机已有的内容，`types` 中的类型都存在时返回
+    fn cached(
+        &self,
+        hash: u64,
+        types: Option<&[ClipType]>,
+    ) -> Option<Vec<ClipData>> {
+        let source = self.source.lock();
+        let source = source.as_ref().filter(|source| source.hash == hash)?;
+        let Some(types) = types else {
+            return Some(source.items.clone());
+        };
+        let items: Vec<ClipData> = source
+            .items
+            .iter()
+            .filter(|item| types.contains(&item.ty))
+            .cloned()
+            .collect();
+        (items.len() == types.len()).then_some(items)
+    }
+
+    /// 收到获取请求的数据，缓存后唤醒等待者
+    fn on_fetched(&self, hash: u64, items: Vec<ClipData>) {
+        let items = {
+            let mut source = self.source.lock();
+            match source.as_mut().filter(|source| source.hash == hash) {
+                Some(source) => {
+                    for item in items {
+                        if !source.items.iter().any(|it| it.ty == item.ty) {
+                            source.items.push(item);
+                        }
+                    }
+                    source.items.clone()
+                }
+                None => {
+                    let offered = self
+                        .offer
+                        .lock()
+                        .as_ref()
+                        .is_some_and(|offer| offer.hash == hash);
+                    if offered {
+                        *source = Some(Source { hash, items: items.clone() });
+                    }
+                    items
+                }
+            }
+        };
+        if let Some(current) = self
+            .current
+            .lock()
+            .as_ref()
+            .filter(|current| current.hash == hash && self.keeps_history())
+        {
+            ClipboardHistory::instance().record(
+                hash,
+                current.ts,
+                &current.origin,
+                &items,
+            );
+        }
+        for tx in self.pending.lock().remove(&hash).unwrap_or_default() {
+            let _ = tx.send(items.clone());
+        }
+    }
+
+    /// 当前内容，按类型过滤
+    ///
+    /// 对端提供的内容尚未获取时先向提供方获取
+    pub async fn items(&self, types: Option<&[ClipType]>) -> Vec<ClipData> {
+        let filter = |data: &ClipData| {
+            types.is_none_or(|types| types.contains(&data.ty))
+        };
+        if let Some(source) = self.source.lock().as_ref() {
+            return source
+                .items
+                .iter()
+                .filter(|it| filter(it))
+                .cloned()
+                .collect();
+        }
+        let offered = self.offer.lock().as_ref().map(|offer| offer.hash);
+        if let Some(hash) = offered {
+            let types = types.map(<[ClipType]>::to_vec);
+            return match self.fetch_items(hash, types).await {
+                Ok(items) => items.into_iter().filter(filter).collect(),
+                Err(e) => {
+                    error!("Failed to fetch clipboard: {}", e);
+                    Vec::new()
+                }
+            };
+        }
+        let backend = self.backend();
+        let read = tauri::async_runtime::spawn_blocking(move || backend.read())
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|read| read);
+        match read {
+            Ok(items) => items.into_iter().filter(filter).collect(),
+            Err(e) => {
+                error!("Failed to read clipboard: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// 清空本地剪贴板
+    pub fn clear(&self) -> Result<()> {
+        self.backend().clear()?;
+        *self.current.lock() = None;
+        *self.written.lock() = None;
+        *self.source.lock() = None;
+        *self.offer.lock() = None;
+        Ok(())
+    }
+
+    /// 处理设备 `origin` 发来的剪贴板消息
+    ///
+    /// 作为服务端时，对端获取的内容本机没有，会先向提供方获取
+    pub async fn handle(&self, origin: &str, message: Clipboard) -> Handled {
+        match message {
+            Clipboard::Set { items, force } => {
+                let history = self.keeps_history();
+                self.handle_set(origin, items, force, history).await
+            }
+            Clipboard::Offer { .. } if self.selection == Selection::Primary => {
+                debug!("Ignore primary selection offer from {}", origin);
+                Handled::default()
+            }
+            Clipboard::Offer { items, hash, ts } => {
+                let types = items.iter().map(|item| item.ty.clone()).collect();
+                match self.apply_offer(origin, types, hash, ts) {
+                    Ok(true) => Handled {
+                        reply: None,
+                        relay: Some(Clipboard::Offer { items, hash, ts }),
+                    },
+                    Ok(false) => Handled::default(),
+                    Err(e) => {
+                        error!(
+                            "Failed to offer clipboard from {}: {}",
+                            origin, e
+                        );
+                        Handled::default()
+                    }
+                }
+            }
+            Clipboard::Get { types, hash: Some(hash), .. } => {
+                let items = match self.cached(hash, types.as_deref()) {
+                    Some(items) => items,
+                    None if is_server() => {
+                        match self.fetch_items(hash, types).await {
+                            Ok(items) => items,
+                            Err(e) => {
+                                error!("Failed to fetch clipboard: {}", e);
+                                Vec::new()
+                            }
+                        }
+                    }
+                    None => Vec::new(),
+                };
+                Handled {
+                    reply: Some(Clipboard::Data { items, hash: Some(hash) }),
+                    relay: None,
+                }
+            }
+            Clipboard::Get { types, since: Some(since), .. }
+                if self.keeps_history() =>
+            {
+                let (entries, cursor, more) =
+                    ClipboardHistory::instance().page(since, types.as_deref());
+                Handled {
+                    reply: Some(Clipboard::History { entries, cursor, more }),
+                    relay: None,
+                }
+            }
+            Clipboard::Get { types, .. } => Handled {
+                reply: Some(Clipboard::Data {
+                    items: self.items(types.as_deref()).await,
+                    hash: None,
+                }),
+                relay: None,
+            },
+            Clipboard::Data { items, hash: Some(hash) } => {
+                self.on_fetched(hash, items);
+                Handled::default()
+            }
+            Clipboard::Data { items, hash: None } if items.is_empty() => {
+                Handled::default()
+            }
+            Clipboard::Data { items, hash: None } => {
+                let history = self.keeps_history();
+                self.handle_set(origin, items, false, history).await
+            }
+            Clipboard::History { entries, cursor, more } => {
+                let history = ClipboardHistory::instance();
+                history.merge(entries);
+                history.set_cursor(origin, cursor);
+                // 继续获取下一页
+                let next = more.then_some(Clipboard::Get {
+                    types: None,
+                    since: Some(cursor),
+                    hash: None,
+                });
+                Handled { reply: next, relay: None }
+            }
+            Clipboard::Clear => match self.clear() {
+                Ok(()) => Handled {
+                    reply: Some(Clipboard::Cleared),
+                    relay: Some(Clipboard::Clear),
+                },
+                Err(e) => {
+                    error!("Failed to clear clipboard: {}", e);
+                    Handled::default()
+                }
+            },
+            Clipboard::Cleared => Handled::default(),
+        }
+    }
+
+    /// 处理设备 `origin` 发来的 PRIMARY 选区，写入本实例的选区但不记录历史
+    ///
+    /// 用于本机不同步 PRIMARY 选区时写入常规剪贴板，只接受直接发送的文本
+    pub async fn handle_mapped(
+        &self,
+        origin: &str,
+        message: Clipboard,
+    ) -> Handled {
+        match message {
+            Clipboard::Set { items, force } => {
+                self.handle_set(origin, items, force, false).await
+            }
+            _ => {
+                debug!("Ignore primary selection message from {}", origin);
+                Handled::default()
+            }
+        }
+    }
+
+    async fn handle_set(
+        &self,
+        origin: &str,
+        items: Vec<ClipData>,
+        force: bool,
+        history: bool,
+    ) -> Handled {
+        match self.apply(origin, &items, force, history).await {
+            Ok(true) => Handled {
+                reply: None,
+                relay: Some(Clipboard::Set { items, force }),
+            },
+            Ok(false) => Handled::default(),
+            Err(e) => {
+                error!("Failed to write clipboard from {}: {}", origin, e);
+                Handled::default()
+            }
+        }
+    }
+
+    /// 将本地剪贴板的变化发送给对端
+    async fn publish(&self, message: Clipboard) {
+        match config::network::get_config().service_type() {
+            ServiceType::Server => {
+                TcpServer::instance()
+                    .broadcast_clipboard(
+                        &local_device_id(),
+                        self.selection,
+                        &message,
+                        None,
+                    )
+                    .await
+            }
+            ServiceType::Client => {
+                if let Err(e) = TcpClient::instance()
+                    .send_clipboard(self.selection, &message)
+                    .await
+                {
+                    debug!("Failed to send clipboard: {}", e);
+                }
+            }
+        }
+    }
+
+    /// 向内容的提供方发送请求，作为客户端时总是经由服务端
+    async fn request(&self, origin: &str, message: Clipboard) -> Result<()> {
+        match config::network::get_config().service_type() {
+            ServiceType::Server => {
+                TcpServer::instance()
+                    .send_clipboard_to(
+                        origin,
+                        &local_device_id(),
+                        self.selection,
+                        &message,
+                    )
+                    .await
+            }
+            ServiceType::Client => {
+                TcpClient::instance()
+                    .send_clipboard(self.selection, &message)
+                    .await
+            }
+        }
+    }
+}
+
+pub(super) fn is_server() -> bool {
+    matches!(config::network::get_config().service_type(), ServiceType::Server)
+}
+
+fn local_device_id() -> String {
+    config::system::config().unwrap_or_default().id()
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_or_larger_origin_wins_conflict() {
+        let a = Revision::new(1, 100, "a");
+        let b = Revision::new(2, 100, "b");
+        let later = Revision::new(3, 101, "a");
+
+        // 时间戳相同时设备 id 较大者胜出
+        assert!(b.beats(&a));
+        assert!(!a.beats(&b));
+        assert!(later.beats(&b));
+        assert!(!Revision::accepts(Some(&b), &a));
+        assert!(Revision::accepts(Some(&a), &b));
+        assert!(Revision::accepts(Some(&b), &later));
+    }
+
+    #[test]
+    fn same_content_is_an_echo() {
+        let current = Revision::new(1, 100, "a");
+
+        assert!(!Revision::accepts(
+            Some(&current),
+            &Revision::new(1, 200, "b")
+        ));
+        assert!(Revision::accepts(None, &current));
+    }
+
+    #[tokio::test]
+    async fn written_content_is_not_sent_back() {
+        let sync = ClipboardSync::new(Selection::Primary);
+        let backend = Arc::new(MemoryClipboard::new());
+        sync.set_backend(backend.clone());
+        let items = vec![ClipData::text("remote")];
+
+        assert!(sync.apply_remote("peer", &items, false).await.unwrap());
+        assert_eq!(backend.read().unwrap(), items);
+        assert!(sync.poll_local().await.is_none());
+        assert!(!sync.apply_remote("other", &items, false).await.unwrap());
+
+        backend.write(&[ClipData::text("local")]).unwrap();
+        assert!(matches!(sync.poll_local().await, Some(Clipboard::Set { .. })));
+        assert!(sync.poll_local().await.is_none());
+    }
+
+    #[test]
+    fn abandoned_fetches_are_pruned() {
+        let sync = ClipboardSync::new(Selection::Clipboard);
+        let (tx, rx) = oneshot::channel();
+        let (waiting, _rx) = oneshot::channel();
+        sync.pending.lock().insert(1, vec![tx]);
+        sync.pending.lock().insert(2, vec![waiting]);
+        drop(rx);
+
+        sync.prune_pending(1);
+        sync.prune_pending(2);
+        assert!(!sync.pending.lock().contains_key(&1));
+        assert_eq!(sync.pending.lock()[&2].len(), 1);
+    }
+}
@@ -0,0 +1,840 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, bail};
+use parking_lot::Mutex;
+use spdlog::{debug, error};
+use x11rb::{
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+    connection::{Connection, RequestConnection},
+    protocol::{
+        Event,
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _,
+            CreateWindowAux, EventMask, PropMode, Property,
+            PropertyNotifyEvent, SELECTION_NOTIFY_EVENT, SelectionNotifyEvent,
+            SelectionRequestEvent, Window, WindowClass,
+        },
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+};
+
+use super::{
+    backend::{ClipHints, ClipboardBackend, Fetch, LazyContent},
+    compress::MAX_DECOMPRESSED_SIZE,
+};
+use crate::service::protocols::clipboard::{ClipData, ClipType, Selection};
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        TARGETS,
+        TIMESTAMP,
+        INCR,
+        UTF8_STRING,
+        TEXT_PLAIN_UTF8: b"text/plain;charset=utf-8",
+        TEXT_PLAIN: b"text/plain",
+        TEXT_HTML: b"text/html",
+        TEXT_RTF: b"text/rtf",
+        APPLICATION_RTF: b"application/rtf",
+        IMAGE_PNG: b"image/png",
+        IMAGE_BMP: b"image/bmp",
+        IMAGE_X_BMP: b"image/x-bmp",
+        IMAGE_TIFF: b"image/tiff",
+        IMAGE_JPEG: b"image/jpeg",
+        URI_LIST: b"text/uri-list",
+        // 密码管理器等应用设置的提示
+        KDE_PASSWORD_HINT: b"x-kde-passwordManagerHint",
+        CONCEALED_TYPE: b"org.nspasteboard.ConcealedType",
+        TRANSIENT_TYPE: b"org.nspasteboard.TransientType",
+        // 读取其他应用的剪贴板时用于接收数据的属性
+        SYNC_POINTER_CLIPBOARD,
+        // 修改本窗口的该属性以获取 X 服务器时间
+        SYNC_POINTER_TIMESTAMP,
+    }
+}
+
+// 等待剪贴板所有者响应的超时时间
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+// 单次写入属性的最大字节数，超过时使用 INCR 分块传输
+const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+impl Atoms {
+    /// 各类型对应的目标格式，按优先级排列
+    fn targets(&self, ty: &ClipType) -> Vec<Atom> {
+        match ty {
+            ClipType::Text => {
+                vec![self.UTF8_STRING, self.TEXT_PLAIN_UTF8, self.TEXT_PLAIN]
+            }
+            ClipType::Rich => vec![self.TEXT_HTML],
+            ClipType::Img => vec![self.IMAGE_PNG],
+            ClipType::Files => vec![self.URI_LIST],
+            ClipType::Rtf => vec![self.TEXT_RTF, self.APPLICATION_RTF],
+        }
+    }
+
+    /// 读取其他应用的内容时接受的目标格式，图片在同步前统一转为 PNG
+    fn read_targets(&self, ty: &ClipType) -> Vec<Atom> {
+        match ty {
+            ClipType::Img => vec![
+                self.IMAGE_PNG,
+                self.IMAGE_BMP,
+                self.IMAGE_X_BMP,
+                self.IMAGE_TIFF,
+                self.IMAGE_JPEG,
+            ],
+            _ => self.targets(ty),
+        }
+    }
+
+    fn clip_type(&self, target: Atom) -> Option<ClipType> {
+        ClipType::ALL.into_iter().find(|ty| self.targets(ty).contains(&target))
+    }
+}
+
+/// 本机作为剪贴板所有者时提供的内容
+struct Content {
+    id: u64,
+    types: Vec<ClipType>,
+    // 已有的数据，懒加载的内容获取后也缓存在这里
+    items: Vec<ClipData>,
+    fetch: Option<Fetch>,
+}
+
+/// 正在进行的 INCR 分块传输
+struct Transfer {
+    data: Vec<u8>,
+    offset: usize,
+    target: Atom,
+}
+
+/// 请求的格式对应的内容
+enum Lookup {
+    Targets(Vec<Atom>),
+    Timestamp(u32),
+    Ready(ClipData),
+    // 懒加载的内容，需要获取
+    Fetch(u64, Fetch),
+    Missing,
+}
+
+/// X11 剪贴板
+///
+/// 作为 CLIPBOARD 或 PRIMARY 选区的所有者在后台线程中响应其他应用的请求，
+/// 懒加载的内容在应用请求时才在单独的线程中获取，获取完成后再回复请求方；
+/// 读取其他应用的内容使用单独的连接。通过 XFixes 感知所有者变化
+pub struct X11Clipboard {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    conn: RustConnection,
+    window: Window,
+    atoms: Atoms,
+    selection: Atom,
+    chunk_size: usize,
+    content: Mutex<Option<Content>>,
+    next_id: AtomicU64,
+    sequence: AtomicU64,
+    // 获取到服务器时间后成为所有者还是放弃所有权
+    claim: AtomicBool,
+    // 尚未收到服务器时间的修改次数
+    claiming: AtomicU64,
+    // 成为所有者时的服务器时间，用于响应 TIMESTAMP 请求
+    owned_at: AtomicU32,
+    transfers: Mutex<HashMap<(Window, Atom), Transfer>>,
+    reader: Mutex<Reader>,
+    // 上次读取的内容附带的提示
+    hints: Mutex<ClipHints>,
+}
+
+impl X11Clipboard {
+    /// 连接 `DISPLAY` 指定的 X 服务器，同步 `selection` 选区
+    pub fn connect(selection: Selection) -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let window = create_window(&conn, screen_num)?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let selection = match selection {
+            Selection::Clipboard => atoms.CLIPBOARD,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+        };
+        conn.xfixes_query_version(5, 0)?.reply()?;
+        conn.xfixes_select_selection_input(
+            window,
+            selection,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )?;
+        conn.flush()?;
+        let chunk_size = (conn.maximum_request_bytes() / 2).min(MAX_CHUNK_SIZE);
+
+        let (reader_conn, screen_num) = x11rb::connect(None)?;
+        let reader_window = create_window(&reader_conn, screen_num)?;
+        reader_conn.flush()?;
+
+        let inner = Arc::new(Inner {
+            conn,
+            window,
+            atoms,
+            selection,
+            chunk_size,
+            content: Mutex::new(None),
+            next_id: AtomicU64::new(0),
+            sequence: AtomicU64::new(0),
+            claim: AtomicBool::new(false),
+            claiming: AtomicU64::new(0),
+            owned_at: AtomicU32::new(CURRENT_TIME),
+            transfers: Mutex::new(HashMap::new()),
+            reader: Mutex::new(Reader {
+                conn: reader_conn,
+                window: reader_window,
+                selection,
+            }),
+            hints: Mutex::new(ClipHints::default()),
+        });
+        let worker = inner.clone();
+        std::thread::Builder::new()
+            .name("x11-clipboard".to_string())
+            .spawn(move || worker.run())?;
+        Ok(Self { inner })
+    }
+}
+
+/// 创建不可见窗口，用于持有剪贴板和接收数据
+fn create_window(conn: &RustConnection, screen_num: usize) -> Result<Window> {
+    let window = conn.generate_id()?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        conn.setup().roots[screen_num].root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    Ok(window)
+}
+
+impl Inner {
+    fn run(self: &Arc<Self>) {
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    error!("X11 clipboard stopped: {}", e);
+                    break;
+                }
+            };
+            match event {
+                Event::SelectionRequest(request) => self.on_request(request),
+                // 重新设置所有者前可能收到旧的清除事件
+                Event::SelectionClear(event)
+                    if event.selection == self.selection
+                        && self.claiming.load(Ordering::SeqCst) == 0
+                        && !self.owns().unwrap_or(false) =>
+                {
+                    *self.content.lock() = None;
+                }
+                Event::PropertyNotify(event)
+                    if event.window == self.window
+                        && event.atom == self.atoms.SYNC_POINTER_TIMESTAMP =>
+                {
+                    self.on_timestamp(event.time)
+                }
+                Event::PropertyNotify(event)
+                    if event.state == Property::DELETE =>
+                {
+                    self.on_property_deleted(&event)
+                }
+                Event::XfixesSelectionNotify(_) => {
+                    self.sequence.fetch_add(1, Ordering::SeqCst);
+                }
+                _ => {}
+            }
+            if let Err(e) = self.conn.flush() {
+                error!("X11 clipboard stopped: {}", e);
+                break;
+            }
+        }
+    }
+
+    fn owns(&self) -> Result<bool> {
+        let owner =
+            self.conn.get_selection_owner(self.selection)?.reply()?.owner;
+        Ok(owner == self.window)
+    }
+
+    /// 成为剪贴板所有者并提供内容
+    fn own(
+        &self,
+        types: Vec<ClipType>,
+        items: Vec<ClipData>,
+        fetch: Option<Fetch>,
+    ) -> Result<()> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        *self.content.lock() = Some(Content { id, types, items, fetch });
+        self.claim(true)
+    }
+
+    /// 修改本窗口的属性，收到属性变化事件时按其中的服务器时间成为或放弃所有者
+    ///
+    /// ICCCM 要求设置所有者时使用真实的时间而不是 `CurrentTime`
+    fn claim(&self, own: bool) -> Result<()> {
+        self.claim.store(own, Ordering::SeqCst);
+        self.claiming.fetch_add(1, Ordering::SeqCst);
+        let result = self
+            .conn
+            .change_property8(
+                PropMode::APPEND,
+                self.window,
+                self.atoms.SYNC_POINTER_TIMESTAMP,
+                AtomEnum::STRING,
+                &[],
+            )
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(self.conn.flush()?));
+        if result.is_err() {
+            self.claiming.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn on_timestamp(&self, time: u32) {
+        self.claiming.fetch_sub(1, Ordering::SeqCst);
+        let result = if self.claim.load(Ordering::SeqCst) {
+            self.owned_at.store(time, Ordering::SeqCst);
+            self.conn.set_selection_owner(self.window, self.selection, time)
+        } else if self.owns().unwrap_or(false) {
+            self.conn.set_selection_owner(NONE, self.selection, time)
+        } else {
+            return;
+        };
+        if let Err(e) = result {
+            error!("Failed to set clipboard owner: {}", e);
+        }
+    }
+
+    /// 响应其他应用的请求，懒加载的内容在单独的线程中获取后再回复
+    fn on_request(self: &Arc<Self>, request: SelectionRequestEvent) {
+        // 旧协议的客户端不指定属性，使用目标格式作为属性
+        let property = if request.property == NONE {
+            request.target
+        } else {
+            request.property
+        };
+        let data = match self.lookup(&request) {
+            Ok(Lookup::Fetch(id, fetch)) => {
+                let this = self.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("x11-clipboard-fetch".to_string())
+                    .spawn(move || {
+                        let lookup =
+                            this.fetch(&request, id, &fetch).map(|data| {
+                                data.map_or(Lookup::Missing, Lookup::Ready)
+                            });
+                        this.reply(&request, property, lookup);
+                        if let Err(e) = this.conn.flush() {
+                            debug!("Failed to reply clipboard request: {}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    error!("Failed to fetch clipboard: {}", e);
+                    self.notify(&request, NONE);
+                }
+                return;
+            }
+            lookup => lookup,
+        };
+        self.reply(&request, property, data);
+    }
+
+    /// 获取懒加载的内容，内容未被替换时缓存
+    fn fetch(
+        &self,
+        request: &SelectionRequestEvent,
+        id: u64,
+        fetch: &Fetch,
+    ) -> Result<Option<ClipData>> {
+        let Some(ty) = self.atoms.clip_type(request.target) else {
+            return Ok(None);
+        };
+        let data = fetch(ty)?;
+        if let Some(content) = self.content.lock().as_mut()
+            && content.id == id
+        {
+            content.items.push(data.clone());
+        }
+        Ok(Some(data))
+    }
+
+    /// 将内容写入请求方窗口的属性并通知请求方，没有内容时拒绝请求
+    fn reply(
+        &self,
+        request: &SelectionRequestEvent,
+        property: Atom,
+        lookup: Result<Lookup>,
+    ) {
+        let property = match lookup
+            .and_then(|lookup| self.convert(request, property, lookup))
+        {
+            Ok(true) => property,
+            Ok(false) => NONE,
+            Err(e) => {
+                debug!("Failed to convert clipboard: {}", e);
+                NONE
+            }
+        };
+        self.notify(request, property);
+    }
+
+    fn notify(&self, request: &SelectionRequestEvent, property: Atom) {
+        let event = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property,
+        };
+        if let Err(e) = self.conn.send_event(
+            false,
+            request.requestor,
+            EventMask::NO_EVENT,
+            event,
+        ) {
+            debug!("Failed to notify clipboard requestor: {}", e);
+        }
+    }
+
+    /// 查找请求的格式对应的内容，`TARGETS` 与 `TIMESTAMP` 直接生成
+    fn lookup(&self, request: &SelectionRequestEvent) -> Result<Lookup> {
+        if request.selection != self.selection {
+            return Ok(Lookup::Missing);
+        }
+        let content = self.content.lock();
+        let Some(content) = content.as_ref() else {
+            return Ok(Lookup::Missing);
+        };
+        if request.target == self.atoms.TARGETS {
+            let mut targets = vec![self.atoms.TARGETS, self.atoms.TIMESTAMP];
+            for ty in &content.types {
+                targets.extend(self.atoms.targets(ty));
+            }
+            return Ok(Lookup::Targets(targets));
+        }
+        if request.target == self.atoms.TIMESTAMP {
+            let time = self.owned_at.load(Ordering::SeqCst);
+            return Ok(Lookup::Timestamp(time));
+        }
+        let Some(ty) = self.atoms.clip_type(request.target) else {
+            return Ok(Lookup::Missing);
+        };
+        if let Some(item) = content.items.iter().find(|item| item.ty == ty) {
+            return Ok(Lookup::Ready(item.clone()));
+        }
+        Ok(match &content.fetch {
+            Some(fetch) if content.types.contains(&ty) => {
+                Lookup::Fetch(content.id, fetch.clone())
+            }
+            _ => Lookup::Missing,
+        })
+    }
+
+    /// 将内容写入请求方窗口的属性，返回是否成功
+    fn convert(
+        &self,
+        request: &SelectionRequestEvent,
+        property: Atom,
+        lookup: Lookup,
+    ) -> Result<bool> {
+        let data = match lookup {
+            Lookup::Targets(targets) => {
+                self.conn.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &targets,
+                )?;
+                return Ok(true);
+            }
+            Lookup::Timestamp(time) => {
+                self.conn.change_property32(
+                    PropMode::REPLACE,
+                    request.requestor,
+                    property,
+                    AtomEnum::INTEGER,
+                    &[time],
+                )?;
+                return Ok(true);
+            }
+            Lookup::Ready(data) => data,
+            Lookup::Fetch(..) | Lookup::Missing => return Ok(false),
+        };
+        let bytes = encode(&data);
+        if bytes.len() > self.chunk_size {
+            // 请求方删除属性后逐块写入，最后写入空块表示结束
+            self.conn.change_window_attributes(
+                request.requestor,
+                &ChangeWindowAttributesAux::new()
+                    .event_mask(EventMask::PROPERTY_CHANGE),
+            )?;
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                self.atoms.INCR,
+                &[bytes.len() as u32],
+            )?;
+            self.transfers.lock().insert(
+                (request.requestor, property),
+                Transfer { data: bytes, offset: 0, target: request.target },
+            );
+        } else {
+            self.conn.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                request.target,
+                &bytes,
+            )?;
+        }
+        Ok(true)
+    }
+
+    fn on_property_deleted(&self, event: &PropertyNotifyEvent) {
+        let key = (event.window, event.atom);
+        let mut transfers = self.transfers.lock();
+        let Some(transfer) = transfers.get_mut(&key) else {
+            return;
+        };
+        let end = (transfer.offset + self.chunk_size).min(transfer.data.len());
+        let chunk = &transfer.data[transfer.offset..end];
+        let done = chunk.is_empty();
+        if let Err(e) = self.conn.change_property8(
+            PropMode::REPLACE,
+            event.window,
+            event.atom,
+            transfer.target,
+            chunk,
+        ) {
+            debug!("Failed to send clipboard chunk: {}", e);
+            transfers.remove(&key);
+            return;
+        }
+        transfer.offset = end;
+        if done {
+            transfers.remove(&key);
+        }
+    }
+}
+
+impl ClipboardBackend for X11Clipboard {
+    fn read(&self) -> Result<Vec<ClipData>> {
+        if self.inner.owns()? {
+            *self.inner.hints.lock() = ClipHints::default();
+            return Ok(Vec::new());
+        }
+        let (items, hints) =
+            self.inner.reader.lock().read(&self.inner.atoms)?;
+        *self.inner.hints.lock() = hints;
+        Ok(items)
+    }
+
+    fn write(&self, items: &[ClipData]) -> Result<()> {
+        let types = items.iter().map(|item| item.ty.clone()).collect();
+        self.inner.own(types, items.to_vec(), None)
+    }
+
+    fn offer(&self, content: LazyContent) -> Result<bool> {
+        self.inner.own(content.types, Vec::new(), Some(content.fetch))?;
+        Ok(true)
+    }
+
+    fn clear(&self) -> Result<()> {
+        *self.inner.content.lock() = None;
+        self.inner.claim(false)
+    }
+
+    fn sequence(&self) -> Option<u64> {
+        Some(self.inner.sequence.load(Ordering::SeqCst))
+    }
+
+    fn hints(&self) -> ClipHints {
+        *self.inner.hints.lock()
+    }
+}
+
+/// 读取其他应用的剪贴板
+struct Reader {
+    conn: RustConnection,
+    window: Window,
+    selection: Atom,
+}
+
+impl Reader {
+    /// 读取所有支持的格式，每种类型取优先级最高的目标格式
+    ///
+    /// 先读取所有者提供的 `TARGETS`，只请求其中包含的目标格式，不逐个试探
+    fn read(&self, atoms: &Atoms) -> Result<(Vec<ClipData>, ClipHints)> {
+        let Some(targets) = self.convert(atoms, atoms.TARGETS)? else {
+            return Ok((Vec::new(), ClipHints::default()));
+        };
+        let targets: Vec<Atom> = targets
+            .chunks_exact(4)
+            .map(|atom| {
+                u32::from_ne_bytes([atom[0], atom[1], atom[2], atom[3]])
+            })
+            .collect();
+        let mut items = Vec::new();
+        for ty in ClipType::ALL {
+            for target in atoms.read_targets(&ty) {
+                if targets.contains(&target)
+                    && let Some(bytes) = self.convert(atoms, target)?
+                {
+                    items.push(decode(ty, bytes));
+                    break;
+                }
+            }
+        }
+        Ok((items, self.hints(atoms, &targets)?))
+    }
+
+    /// KeePassXC 等应用通过 KDE 的密码提示或 nspasteboard 的类型标记敏感内容
+    fn hints(&self, atoms: &Atoms, targets: &[Atom]) -> Result<ClipHints> {
+        let mut hints = ClipHints {
+            concealed: targets.contains(&atoms.CONCEALED_TYPE),
+            transient: targets.contains(&atoms.TRANSIENT_TYPE),
+        };
+        if targets.contains(&atoms.KDE_PASSWORD_HINT)
+            && let Some(value) = self.convert(atoms, atoms.KDE_PASSWORD_HINT)?
+        {
+            hints.concealed |= value == b"secret";
+        }
+        Ok(hints)
+    }
+
+    /// 请求所有者将内容转换为 `target` 格式，所有者拒绝时返回 `None`
+    fn convert(&self, atoms: &Atoms, target: Atom) -> Result<Option<Vec<u8>>> {
+        let property = atoms.SYNC_POINTER_CLIPBOARD;
+        self.conn.convert_selection(
+            self.window,
+            self.selection,
+            target,
+            property,
+            CURRENT_TIME,
+        )?;
+        self.conn.flush()?;
+        let notify = self.wait(|event| match event {
+            Event::SelectionNotify(event)
+                if event.requestor == self.window && event.target == target =>
+            {
+                Some(event.property)
+            }
+            _ => None,
+        })?;
+        if notify == NONE {
+            return Ok(None);
+        }
+
+        let reply = self
+            .conn
+            .get_property(
+                true,
+                self.window,
+                property,
+                AtomEnum::ANY,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+        if reply.type_ != atoms.INCR {
+            return Ok(Some(reply.value));
+        }
+        // INCR 分块传输，删除属性后所有者写入下一块，空块表示结束
+        let mut data = Vec::new();
+        loop {
+            self.wait(|event| match event {
+                Event::PropertyNotify(event)
+                    if event.window == self.window
+                        && event.atom == property
+                        && event.state == Property::NEW_VALUE =>
+                {
+                    Some(())
+                }
+                _ => None,
+            })?;
+            let chunk = self
+                .conn
+                .get_property(
+                    true,
+                    self.window,
+                    property,
+                    AtomEnum::ANY,
+                    0,
+                    u32::MAX / 4,
+                )?
+                .reply()?;
+            if chunk.value.is_empty() {
+                return Ok(Some(data));
+            }
+            data.extend_from_slice(&chunk.value);
+            if data.len() > MAX_DECOMPRESSED_SIZE {
+                bail!("Clipboard content exceeds {}", MAX_DECOMPRESSED_SIZE);
+            }
+        }
+    }
+
+    /// 等待满足条件的事件
+    fn wait<T>(
+        &self,
+        mut matches: impl FnMut(Event) -> Option<T>,
+    ) -> Result<T> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            match self.conn.poll_for_event()? {
+                Some(event) => {
+                    if let Some(value) = matches(event) {
+                        return Ok(value);
+                    }
+                }
+                None if Instant::now() > deadline => {
+                    bail!("Timed out waiting for clipboard owner")
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }
+}
+
+/// 转换为 X11 的格式，文件列表转为 `text/uri-list`
+fn encode(data: &ClipData) -> Vec<u8> {
+    match data.ty {
+        ClipType::Files => String::from_utf8_lossy(&data.data)
+            .lines()
+            .map(|path| format!("file://{}\r\n", percent_encode(path)))
+            .collect::<String>()
+            .into_bytes(),
+        _ => data.data.clone(),
+    }
+}
+
+fn decode(ty: ClipType, bytes: Vec<u8>) -> ClipData {
+    match ty {
+        ClipType::Files => ClipData::files(
+            String::from_utf8_lossy(&bytes)
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .filter_map(|uri| uri.strip_prefix("file://"))
+                .map(percent_decode)
+                .collect(),
+        ),
+        ClipType::Img => ClipData::image(bytes),
+        ClipType::Rich => ClipData::html(utf8_html(bytes)),
+        ClipType::Rtf => ClipData::rtf(bytes),
+        ClipType::Text => ClipData::new(ty, bytes),
+    }
+}
+
+/// 部分浏览器提供的 `text/html` 为带 BOM 的 UTF-16，统一转为 UTF-8
+fn utf8_html(bytes: Vec<u8>) -> String {
+    let utf16 = |bytes: &[u8], decode: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| decode([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes.as_slice() {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ => String::from_utf8(bytes).unwrap_or_else(|e| {
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }),
+    }
+}
+
+fn percent_encode(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'/'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = uri
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// 需要 X 服务器，如 `Xvfb :99 & DISPLAY=:99 cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn primary_selection_is_read_by_another_client() -> Result<()> {
+        let owner = X11Clipboard::connect(Selection::Primary)?;
+        let reader = X11Clipboard::connect(Selection::Primary)?;
+        owner.write(&[ClipData::text("primary")])?;
+
+        // 由事件线程取得服务器时间后才成为所有者
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let items = loop {
+            let items = reader.read()?;
+            if !items.is_empty() || Instant::now() > deadline {
+                break items;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        };
+        let text: Vec<&[u8]> = items
+            .iter()
+            .filter(|item| item.ty == ClipType::Text)
+            .map(|item| item.data.as_slice())
+            .collect();
+        assert_eq!(text, [b"primary".as_slice()]);
+        // 本机写入的内容不再读回
+        assert!(owner.read()?.is_empty());
+        Ok(())
+    }
+}
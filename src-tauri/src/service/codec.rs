@@ -6,6 +6,7 @@ use rkyv::{
     Archive, Archived,
     rancor::{BoxedError, Error as RancorError},
     ser::allocator::Arena,
+    util::AlignedVec,
     validation::{
         Validator, archive::ArchiveValidator, shared::SharedValidator,
     },
@@ -18,7 +19,11 @@ use tokio_util::{
     codec::LengthDelimitedCodec,
 };
 
-use super::protocols::base::DataPacket;
+use super::{clipboard::compress, protocols::base::DataPacket};
+
+/// 单个数据包的最大长度，剪贴板内容解压后的上限加上消息其余部分的余量
+pub const MAX_FRAME_LENGTH: usize =
+    compress::MAX_DECOMPRESSED_SIZE + 1024 * 1024;
 
 pub struct DataPacketCodec {
     inner: LengthDelimitedCodec,
@@ -31,7 +36,7 @@ impl Default for DataPacketCodec {
         Self {
             inner: LengthDelimitedCodec::builder()
                 .big_endian()
-                .max_frame_length(u32::MAX as usize)
+                .max_frame_length(MAX_FRAME_LENGTH)
                 .length_field_type::<u32>()
                 .new_codec(),
             arena: Arena::new(),
@@ -64,6 +69,14 @@ where
     }
 }
 
+impl CheckedArchive<DataPacket> {
+    /// 反序列化为拥有所有权的数据包
+    pub fn deserialize(&self) -> anyhow::Result<DataPacket> {
+        rkyv::deserialize::<DataPacket, RancorError>(&**self)
+            .map_err(|e| anyhow!("DataPacketDeserializeError: {}", e))
+    }
+}
+
 impl codec::Decoder for DataPacketCodec {
     // 修改返回类型为 CheckedArchive<DataPacket>
     type Item = CheckedArchive<DataPacket>;
@@ -75,7 +88,7 @@ impl codec::Decoder for DataPacketCodec {
     ) -> Result<Option<Self::Item>, Self::Error> {
         // 将字节缓冲区转换为引用计数形式，并创建 CheckedArchive
         let bytes = match self.inner.decode(src)? {
-            Some(bytes) => bytes.freeze(),
+            Some(bytes) => aligned(bytes),
             None => return Ok(None),
         };
 
@@ -98,6 +111,16 @@ impl codec::Decoder for DataPacketCodec {
     }
 }
 
+/// 长度字段之后的帧数据不一定满足归档数据的对齐要求，不满足时复制到对齐的缓冲区
+fn aligned(bytes: BytesMut) -> Bytes {
+    if bytes.as_ptr().align_offset(AlignedVec::<16>::ALIGNMENT) == 0 {
+        return bytes.freeze();
+    }
+    let mut vec = AlignedVec::<16>::with_capacity(bytes.len());
+    vec.extend_from_slice(&bytes);
+    Bytes::from_owner(vec)
+}
+
 impl codec::Encoder<DataPacket> for DataPacketCodec {
     type Error = anyhow::Error;
 
@@ -125,3 +148,32 @@ impl codec::Encoder<DataPacket> for DataPacketCodec {
 pub type DataPacketWriter =
     SplitSink<Framed<TcpStream, DataPacketCodec>, DataPacket>;
 pub type DataPacketReader = SplitStream<Framed<TcpStream, DataPacketCodec>>;
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use super::*;
+    use crate::service::protocols::base::PacketData;
+
+    #[test]
+    fn round_trip() {
+        let mut codec = DataPacketCodec::default();
+        let packet = DataPacket::new("device", PacketData::Fail("test".into()));
+        let mut buffer = BytesMut::new();
+
+        codec.encode(packet.clone(), &mut buffer).unwrap();
+        let decoded = codec.decode(&mut buffer).unwrap().unwrap();
+        assert_eq!(decoded.deserialize().unwrap(), packet);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn oversized_frame_is_rejected() {
+        let mut codec = DataPacketCodec::default();
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&(MAX_FRAME_LENGTH as u32 + 1).to_be_bytes());
+
+        assert!(codec.decode(&mut buffer).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Result;
+use parking_lot::RwLock;
+use spdlog::trace;
+use tokio::sync::mpsc;
+
+use crate::config::{self, network::ServiceType};
+use crate::service::protocols::{base::PacketData, input::LockState};
+use crate::service::server::router::InputRouter;
+
+static CAPTURE: LazyLock<RwLock<Arc<dyn CaptureBackend>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(NoopCapture)));
+
+// 捕获到的输入按顺序交给输入路由
+static EVENTS: LazyLock<mpsc::UnboundedSender<PacketData>> =
+    LazyLock::new(|| {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tauri::async_runtime::spawn(async move {
+            while let Some(data) = rx.recv().await {
+                // 作为客户端时本机输入由本机处理
+                if matches!(
+                    config::network::get_config().service_type(),
+                    ServiceType::Server
+                ) {
+                    InputRouter::instance().route(data).await;
+                }
+            }
+        });
+        tx
+    });
+
+/// 输入捕获后端，负责读取本机键鼠并控制物理键盘状态
+pub trait CaptureBackend: Send + Sync {
+    /// 本机锁定键状态，后端不支持时返回 `None`
+    fn lock_state(&self) -> Option<LockState>;
+
+    /// 设置物理键盘的锁定键指示灯
+    fn set_leds(&self, state: LockState) -> Result<()>;
+
+    /// 独占本机键鼠，控制远端设备时本机应用不再收到输入
+    fn grab(&self, grabbed: bool) -> Result<()> {
+        trace!("grab input: {}", grabbed);
+        Ok(())
+    }
+}
+
+/// 未注册平台后端时使用，仅记录日志
+struct NoopCapture;
+
+impl CaptureBackend for NoopCapture {
+    fn lock_state(&self) -> Option<LockState> {
+        None
+    }
+
+    fn set_leds(&self, state: LockState) -> Result<()> {
+        trace!("set leds: {:?}", state);
+        Ok(())
+    }
+}
+
+/// 注册平台捕获后端
+pub fn set_capture(capture: Arc<dyn CaptureBackend>) {
+    *CAPTURE.write() = capture;
+}
+
+pub fn capture() -> Arc<dyn CaptureBackend> {
+    CAPTURE.read().clone()
+}
+
+/// 提交捕获到的输入事件，由平台捕获后端按捕获顺序调用
+pub fn submit(data: PacketData) {
+    let _ = EVENTS.send(data);
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, LazyLock};
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use super::tracker::InputTracker;
+
+static FOCUS_PROBE: LazyLock<RwLock<Arc<dyn FocusProbe>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(NoopFocusProbe)));
+
+/// 自动锁定光标的规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CursorLockRules {
+    // 前台应用为全屏窗口时锁定
+    pub fullscreen: bool,
+    // 前台应用在列表中时锁定，按窗口类名匹配，不区分大小写
+    pub apps: Vec<String>,
+    // 按住鼠标按钮时锁定
+    pub button_held: bool,
+    // 按住其中任一按键时锁定
+    pub keys: Vec<u32>,
+}
+
+impl Default for CursorLockRules {
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            apps: Vec::new(),
+            button_held: true,
+            keys: Vec::new(),
+        }
+    }
+}
+
+/// 光标被锁定的原因
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LockReason {
+    /// 手动锁定
+    Manual,
+    /// 前台窗口全屏
+    Fullscreen,
+    /// 前台应用在列表中，值为窗口类名
+    App(String),
+    /// 按住鼠标按钮
+    Button,
+    /// 按住配置的按键，值为键码
+    Key(u32),
+}
+
+/// 前台窗口信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FocusedWindow {
+    // 窗口类名，X11 下为 WM_CLASS 的类名部分
+    pub app: String,
+    pub fullscreen: bool,
+}
+
+/// 前台窗口查询后端
+pub trait FocusProbe: Send + Sync {
+    fn focused_window(&self) -> Option<FocusedWindow>;
+}
+
+/// 未注册平台后端时使用，不支持按前台窗口锁定
+struct NoopFocusProbe;
+
+impl FocusProbe for NoopFocusProbe {
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        None
+    }
+}
+
+/// 注册平台前台窗口查询后端
+pub fn set_focus_probe(probe: Arc<dyn FocusProbe>) {
+    *FOCUS_PROBE.write() = probe;
+}
+
+pub fn focus_probe() -> Arc<dyn FocusProbe> {
+    FOCUS_PROBE.read().clone()
+}
+
+impl CursorLockRules {
+    /// 按规则判断是否需要自动锁定，手动锁定由调用方处理
+    pub fn evaluate(
+        &self,
+        tracker: &InputTracker,
+        window: Option<&FocusedWindow>,
+    ) -> Option<LockReason> {
+        if self.button_held && tracker.has_button_pressed() {
+            return Some(LockReason::Button);
+        }
+        if let Some(&key_code) =
+            self.keys.iter().find(|&&key_code| tracker.is_key_pressed(key_code))
+        {
+            return Some(LockReason::Key(key_code));
+        }
+        let window = window?;
+        if self.fullscreen && window.fullscreen {
+            return Some(LockReason::Fullscreen);
+        }
+        self.apps
+            .iter()
+            .any(|app| app.eq_ignore_ascii_case(&window.app))
+            .then(|| LockReason::App(window.app.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::protocols::input::{
+        KeyModifiers, Keyboard, Mouse, MouseButton,
+    };
+
+    fn window(app: &str, fullscreen: bool) -> FocusedWindow {
+        FocusedWindow { app: app.into(), fullscreen }
+    }
+
+    #[test]
+    fn idle_input_without_window_is_unlocked() {
+        let rules =
+            CursorLockRules { apps: vec!["Game".into()], ..Default::default() };
+        assert_eq!(rules.evaluate(&InputTracker::new(), None), None);
+        assert_eq!(
+            rules.evaluate(&InputTracker::new(), Some(&window("term", false))),
+            None
+        );
+    }
+
+    #[test]
+    fn held_button_and_keys_lock() {
+        let rules = CursorLockRules { keys: vec![50], ..Default::default() };
+        let mut tracker = InputTracker::new();
+        tracker.track_key(&Keyboard::press(50, KeyModifiers::none(), None));
+        assert_eq!(rules.evaluate(&tracker, None), Some(LockReason::Key(50)));
+
+        // 按钮优先于按键
+        tracker.track_mouse(&Mouse::button(MouseButton::Left, true));
+        assert_eq!(rules.evaluate(&tracker, None), Some(LockReason::Button));
+
+        let rules = CursorLockRules { button_held: false, ..rules };
+        assert_eq!(rules.evaluate(&tracker, None), Some(LockReason::Key(50)));
+        tracker.track_key(&Keyboard::release(50, KeyModifiers::none()));
+        assert_eq!(rules.evaluate(&tracker, None), None);
+    }
+
+    #[test]
+    fn fullscreen_window_locks_when_enabled() {
+        let tracker = InputTracker::new();
+        let fullscreen = window("player", true);
+        assert_eq!(
+            CursorLockRules::default().evaluate(&tracker, Some(&fullscreen)),
+            Some(LockReason::Fullscreen)
+        );
+        let rules = CursorLockRules { fullscreen: false, ..Default::default() };
+        assert_eq!(rules.evaluate(&tracker, Some(&fullscreen)), None);
+    }
+
+    #[test]
+    fn listed_app_matches_ignoring_case() {
+        let rules = CursorLockRules {
+            fullscreen: false,
+            apps: vec!["Blender".into()],
+            ..Default::default()
+        };
+        let tracker = InputTracker::new();
+        assert_eq!(
+            rules.evaluate(&tracker, Some(&window("blender", false))),
+            Some(LockReason::App("blender".into()))
+        );
+        assert_eq!(
+            rules.evaluate(&tracker, Some(&window("krita", true))),
+            None
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+use crate::service::protocols::input::{KeyModifiers, Keyboard, key};
+
+use super::remap::Chord;
+
+/// 紧急热键 Ctrl+Alt+Shift+Esc，始终生效且不可覆盖
+pub const EMERGENCY: Chord = Chord {
+    key_code: key::ESC,
+    modifiers: KeyModifiers { shift: true, ctrl: true, alt: true, logo: false },
+};
+
+/// 热键动作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum HotkeyAction {
+    /// 切换到指定设备，值为设备 id，本机 id 表示回到本机
+    SwitchTo(String),
+    /// 切换到下一台设备
+    Next,
+    /// 切换到上一台设备
+    Previous,
+    /// 将光标锁定在当前屏幕，禁止边缘切换
+    ToggleCursorLock,
+    /// 切换当前目标的指针模式（绝对坐标与相对位移）
+    TogglePointerMode,
+    /// 将剪贴板文本作为按键输入到当前目标
+    TypeClipboard,
+    /// 回到本机并释放所有设备上按下的输入
+    Emergency,
+}
+
+/// 热键绑定
+///
+/// `sequence` 只有一个组合键时为普通组合键，多个时需在超时内依次按下
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hotkey {
+    pub sequence: Vec<Chord>,
+    pub action: HotkeyAction,
+}
+
+impl Hotkey {
+    pub fn chord(chord: Chord, action: HotkeyAction) -> Self {
+        Self { sequence: vec![chord], action }
+    }
+}
+
+/// 热键设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeySettings {
+    pub bindings: Vec<Hotkey>,
+    // 序列中相邻两个组合键的最大间隔
+    pub sequence_timeout_ms: u32,
+}
+
+impl Default for HotkeySettings {
+    fn default() -> Self {
+        let ctrl_alt = KeyModifiers::new(false, true, true, false);
+        Self {
+            bindings: vec![
+                Hotkey::chord(
+                    Chord { key_code: key::RIGHT, modifiers: ctrl_alt },
+                    HotkeyAction::Next,
+                ),
+                Hotkey::chord(
+                    Chord { key_code: key::LEFT, modifiers: ctrl_alt },
+                    HotkeyAction::Previous,
+                ),
+                Hotkey::chord(
+                    Chord {
+                        key_code: key::SCROLL_LOCK,
+                        modifiers: KeyModifiers::none(),
+                    },
+                    HotkeyAction::ToggleCursorLock,
+                ),
+                Hotkey::chord(
+                    Chord { key_code: key::PAUSE, modifiers: ctrl_alt },
+                    HotkeyAction::TogglePointerMode,
+                ),
+            ],
+            sequence_timeout_ms: 1000,
+        }
+    }
+}
+
+/// 热键匹配结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotkeyOutcome {
+    /// 不是热键，照常转发
+    Pass,
+    /// 热键的一部分，不转发
+    Swallow,
+    /// 触发热键
+    Fire(HotkeyAction),
+}
+
+/// 在捕获的键盘事件转发前匹配热键
+///
+/// 参与热键的按键从按下到释放都不会转发到目标设备。修饰键按下时无法确定是否属于热键，
+/// 照常转发，热键匹配后由调用方通过 [`HotkeyEngine::take_released`] 在目标设备上释放，
+/// 之后的实际释放不再转发；未完成的序列被其他按键打断时，已按下的部分被丢弃
+#[derive(Debug, Default)]
+pub struct HotkeyEngine {
+    // 已按下的序列前缀
+    pending: Vec<Chord>,
+    last: Option<Instant>,
+    // 被热键吞掉的按键，其重复与释放同样吞掉
+    swallowed: HashSet<u32>,
+    // 当前按下的修饰键
+    modifiers: HashSet<u32>,
+    // 已被热键吞掉、需要在目标设备上释放的修饰键
+    released: Vec<u32>,
+}
+
+impl HotkeyEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_key(
+        &mut self,
+        settings: &HotkeySettings,
+        event: &Keyboard,
+        now: Instant,
+    ) -> HotkeyOutcome {
+        let (key_code, modifiers) = match *event {
+            Keyboard::KeyRelease { key_code, .. } => {
+                self.modifiers.remove(&key_code);
+                return if self.swallowed.remove(&key_code) {
+                    HotkeyOutcome::Swallow
+                } else {
+                    HotkeyOutcome::Pass
+                };
+            }
+            Keyboard::KeyPress { key_code, modifiers, .. } => {
+                (key_code, modifiers)
+            }
+        };
+        if key::is_modifier(key_code) {
+            if self.swallowed.contains(&key_code) {
+                return HotkeyOutcome::Swallow;
+            }
+            self.modifiers.insert(key_code);
+            return HotkeyOutcome::Pass;
+        }
+        if self.swallowed.contains(&key_code) {
+            return HotkeyOutcome::Swallow;
+        }
+
+        let chord = Chord { key_code, modifiers };
+        if chord == EMERGENCY {
+            self.pending.clear();
+            self.swallow(key_code);
+            return HotkeyOutcome::Fire(HotkeyAction::Emergency);
+        }
+
+        let timeout =
+            Duration::from_millis(settings.sequence_timeout_ms as u64);
+        if self.last.is_some_and(|last| now.duration_since(last) > timeout) {
+            self.pending.clear();
+        }
+        self.pending.push(chord);
+        let mut outcome = self.match_pending(settings);
+        if outcome == HotkeyOutcome::Pass && self.pending.len() > 1 {
+            // 序列被打断，当前按键可能是新序列的开始
+            self.pending = vec![chord];
+            outcome = self.match_pending(settings);
+        }
+
+        match outcome {
+            HotkeyOutcome::Pass => {
+                self.pending.clear();
+                self.last = None;
+            }
+            HotkeyOutcome::Swallow => {
+                self.last = Some(now);
+                self.swallow(key_code);
+            }
+            HotkeyOutcome::Fire(_) => {
+                self.pending.clear();
+                self.last = None;
+                self.swallow(key_code);
+            }
+        }
+        outcome
+    }
+
+    /// 取出已转发到目标设备、因属于热键需要释放的修饰键
+    pub fn take_released(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.released)
+    }
+
+    /// 吞掉按键，同时吞掉当前按下的修饰键
+    fn swallow(&mut self, key_code: u32) {
+        self.swallowed.insert(key_code);
+        for &modifier in &self.modifiers {
+            if self.swallowed.insert(modifier) {
+                self.released.push(modifier);
+            }
+        }
+    }
+
+    fn match_pending(&self, settings: &HotkeySettings) -> HotkeyOutcome {
+        let mut prefix = false;
+        for hotkey in &settings.bindings {
+            if hotkey.sequence == self.pending {
+                return HotkeyOutcome::Fire(hotkey.action.clone());
+            }
+            prefix |= hotkey.sequence.starts_with(&self.pending);
+        }
+        if prefix { HotkeyOutcome::Swallow } else { HotkeyOutcome::Pass }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key_code: u32, modifiers: KeyModifiers) -> Keyboard {
+        Keyboard::press(key_code, modifiers, None)
+    }
+
+    #[test]
+    fn hotkey_modifiers_are_released_and_swallowed() {
+        let settings = HotkeySettings::default();
+        let mut engine = HotkeyEngine::new();
+        let now = Instant::now();
+        let ctrl = KeyModifiers::new(false, true, false, false);
+        let ctrl_alt = KeyModifiers::new(false, true, true, false);
+
+        assert_eq!(
+            engine.on_key(&settings, &press(key::LEFT_CTRL, ctrl), now),
+            HotkeyOutcome::Pass
+        );
+        assert_eq!(
+            engine.on_key(&settings, &press(key::LEFT_ALT, ctrl_alt), now),
+            HotkeyOutcome::Pass
+        );
+        assert_eq!(
+            engine.on_key(&settings, &press(key::RIGHT, ctrl_alt), now),
+            HotkeyOutcome::Fire(HotkeyAction::Next)
+        );
+        let mut released = engine.take_released();
+        released.sort();
+        assert_eq!(released, vec![key::LEFT_CTRL, key::LEFT_ALT]);
+        assert!(engine.take_released().is_empty());
+
+        let release = Keyboard::release(key::LEFT_CTRL, ctrl_alt);
+        assert_eq!(
+            engine.on_key(&settings, &release, now),
+            HotkeyOutcome::Swallow
+        );
+    }
+
+    #[test]
+    fn modifiers_of_other_keys_are_kept() {
+        let settings = HotkeySettings::default();
+        let mut engine = HotkeyEngine::new();
+        let now = Instant::now();
+        let ctrl = KeyModifiers::new(false, true, false, false);
+
+        engine.on_key(&settings, &press(key::LEFT_CTRL, ctrl), now);
+        assert_eq!(
+            engine.on_key(&settings, &press(key::RIGHT, ctrl), now),
+            HotkeyOutcome::Pass
+        );
+        assert!(engine.take_released().is_empty());
+
+        let release = Keyboard::release(key::LEFT_CTRL, ctrl);
+        assert_eq!(
+            engine.on_key(&settings, &release, now),
+            HotkeyOutcome::Pass
+        );
+    }
+}
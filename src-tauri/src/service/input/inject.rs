@@ -0,0 +1,155 @@
+use std::sync::{Arc, LazyLock};
+
+use anyhow::Result;
+use parking_lot::RwLock;
+use spdlog::{debug, error, trace};
+use tokio::time::Instant;
+
+use crate::service::protocols::{
+    base::PacketData,
+    input::{Keyboard, LockState, Mouse, key},
+};
+
+use super::{locks, repeat::RepeatEngine, tracker::InputTracker};
+
+static INJECTOR: LazyLock<RwLock<Arc<dyn Injector>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(NoopInjector)));
+
+/// 输入注入后端，将远端发送的事件作用到本机
+pub trait Injector: Send + Sync {
+    fn key(&self, event: &Keyboard) -> Result<()>;
+    fn mouse(&self, event: &Mouse) -> Result<()>;
+
+    /// 本机锁定键状态，后端不支持时返回 `None`
+    fn lock_state(&self) -> Option<LockState> {
+        None
+    }
+
+    /// 本机指针是否被前台应用锁定，后端不支持时返回 `None`
+    fn pointer_locked(&self) -> Option<bool> {
+        None
+    }
+}
+
+/// 未注册平台后端时使用，仅记录日志
+struct NoopInjector;
+
+impl Injector for NoopInjector {
+    fn key(&self, event: &Keyboard) -> Result<()> {
+        trace!("inject key: {:?}", event);
+        Ok(())
+    }
+
+    fn mouse(&self, event: &Mouse) -> Result<()> {
+        trace!("inject mouse: {:?}", event);
+        Ok(())
+    }
+}
+
+/// 注册平台注入后端
+pub fn set_injector(injector: Arc<dyn Injector>) {
+    *INJECTOR.write() = injector;
+}
+
+pub fn injector() -> Arc<dyn Injector> {
+    INJECTOR.read().clone()
+}
+
+/// 被控端的注入会话，一条控制端连接对应一个实例
+#[derive(Debug, Default)]
+pub struct InjectSession {
+    tracker: InputTracker,
+    repeat: RepeatEngine,
+    // 最近一次回报给控制端的指针锁定状态
+    pointer_locked: bool,
+}
+
+impl InjectSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 处理控制端发来的输入类数据包，非输入类数据包会被忽略
+    ///
+    /// 返回需要回复给控制端的数据
+    pub fn handle(&mut self, data: &PacketData) -> Option<PacketData> {
+        match data {
+            PacketData::Repeat(policy) => {
+                debug!("Repeat policy changed: {:?}", policy);
+                self.repeat.set_policy(*policy);
+                None
+            }
+            PacketData::Locks(desired) => self.sync_locks(*desired),
+            PacketData::Key(event) => {
+                self.repeat.on_event(event, Instant::now());
+                self.inject(data);
+                // 锁定键切换后向控制端回报新的状态
+                match event {
+                    Keyboard::KeyRelease { key_code, .. }
+                        if key::is_lock(*key_code) =>
+                    {
+                        injector().lock_state().map(PacketData::Locks)
+                    }
+                    _ => None,
+                }
+            }
+            PacketData::Mouse(_) => {
+                self.inject(data);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// 将本机锁定键状态调整为控制端的状态，并回报调整后的状态
+    fn sync_locks(&mut self, desired: LockState) -> Option<PacketData> {
+        let current = injector().lock_state()?;
+        for event in locks::reconcile(current, desired) {
+            self.inject(&PacketData::Key(event));
+        }
+        injector().lock_state().map(PacketData::Locks)
+    }
+
+    /// 检查本机指针锁定状态，变化时返回需要回报给控制端的数据
+    pub fn check_pointer_lock(&mut self) -> Option<PacketData> {
+        let locked = injector().pointer_locked()?;
+        if locked == self.pointer_locked {
+            return None;
+        }
+        self.pointer_locked = locked;
+        Some(PacketData::PointerLock(locked))
+    }
+
+    /// 下一次需要调用 [`InjectSession::poll`] 的时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.repeat.next_deadline()
+    }
+
+    /// 注入到期的本地重复事件或看门狗释放事件
+    pub fn poll(&mut self) {
+        for event in self.repeat.poll(Instant::now()) {
+            self.inject(&PacketData::Key(event));
+        }
+    }
+
+    fn inject(&mut self, data: &PacketData) {
+        let injector = injector();
+        let result = match data {
+            PacketData::Key(event) => injector.key(event),
+            PacketData::Mouse(event) => injector.mouse(event),
+            _ => return,
+        };
+        match result {
+            Ok(()) => self.tracker.track(data),
+            Err(e) => error!("Failed to inject input: {}", e),
+        }
+    }
+
+    /// 控制端消失时释放所有仍处于按下状态的键和按钮
+    pub fn release_all(&mut self) {
+        self.repeat.reset();
+        for data in self.tracker.release_all() {
+            self.inject(&data);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::service::protocols::input::{
+    KeyModifiers, Keyboard, LockState, key,
+};
+
+/// 生成将锁定键状态从 `current` 切换到 `desired` 所需的按键事件
+///
+/// 每个不一致的锁定键产生一次按下与释放
+pub fn reconcile(current: LockState, desired: LockState) -> Vec<Keyboard> {
+    [
+        (current.caps != desired.caps, key::CAPS_LOCK),
+        (current.num != desired.num, key::NUM_LOCK),
+        (current.scroll != desired.scroll, key::SCROLL_LOCK),
+    ]
+    .into_iter()
+    .filter(|(differs, _)| *differs)
+    .flat_map(|(_, key_code)| {
+        [
+            Keyboard::press(key_code, KeyModifiers::none(), None),
+            Keyboard::release(key_code, KeyModifiers::none()),
+        ]
+    })
+    .collect()
+}
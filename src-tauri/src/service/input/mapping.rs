@@ -0,0 +1,60 @@
+// 平台鼠标按钮与滚动事件到协议事件的映射，供捕获与注入后端共用
+//
+// 目前只有 X11 后端，其他平台接入后端时在此添加对应的映射
+use crate::service::protocols::input::{Mouse, MouseButton, ScrollKind};
+
+/// 连续滚动折算为滚轮时一格对应的像素数
+pub const PIXELS_PER_NOTCH: f32 = 15.0;
+
+/// 滚动量折算为格数
+pub fn notches(amount: f32, kind: ScrollKind) -> f32 {
+    match kind {
+        ScrollKind::Wheel => amount,
+        ScrollKind::Continuous => amount / PIXELS_PER_NOTCH,
+    }
+}
+
+/// X11 核心协议按钮编号，4-7 为滚轮，8/9 为侧键
+pub mod x11 {
+    use super::*;
+
+    /// 按下事件对应的协议事件，滚轮按钮只在按下时产生滚动
+    pub fn from_button(detail: u8, pressed: bool) -> Option<Mouse> {
+        let button = match detail {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            4..=7 if !pressed => return None,
+            4 => return Some(Mouse::scroll(0.0, 1.0)),
+            5 => return Some(Mouse::scroll(0.0, -1.0)),
+            6 => return Some(Mouse::scroll(-1.0, 0.0)),
+            7 => return Some(Mouse::scroll(1.0, 0.0)),
+            8 => MouseButton::Back,
+            9 => MouseButton::Forward,
+            other => MouseButton::Other(other),
+        };
+        Some(Mouse::button(button, pressed))
+    }
+
+    pub fn to_button(button: MouseButton) -> u8 {
+        match button {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::Back => 8,
+            MouseButton::Forward => 9,
+            MouseButton::Other(detail) => detail,
+        }
+    }
+
+    /// 滚动转换为按钮点击序列，每个元素为一次按下并释放
+    ///
+    /// X11 核心协议只能表示整格滚动，小数部分由调用方累计
+    pub fn to_scroll_buttons(dx: f32, dy: f32) -> Vec<u8> {
+        let repeat = |amount: f32, positive: u8, negative: u8| {
+            let button = if amount > 0.0 { positive } else { negative };
+            std::iter::repeat_n(button, amount.abs().trunc() as usize)
+        };
+        repeat(dy, 4, 5).chain(repeat(dx, 7, 6)).collect()
+    }
+}
@@ -0,0 +1,14 @@
+pub mod capture;
+pub mod cursor_lock;
+pub mod hotkey;
+pub mod inject;
+pub mod locks;
+pub mod mapping;
+pub mod pointer;
+pub mod remap;
+pub mod repeat;
+pub mod tracker;
+pub mod tuning;
+pub mod typing;
+#[cfg(target_os = "linux")]
+pub mod x11;
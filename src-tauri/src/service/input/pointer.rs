@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+use crate::service::protocols::input::Mouse;
+
+/// 指针模式
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+pub enum PointerMode {
+    /// 发送绝对坐标，允许边缘切换
+    #[default]
+    Absolute,
+    /// 发送原始相对位移，用于锁定并隐藏光标的游戏与 3D 应用，不进行边缘切换
+    Relative,
+}
+
+/// 会话的指针状态，负责在相对模式下将绝对坐标转换为相对位移
+#[derive(Debug, Default)]
+pub struct PointerState {
+    mode: PointerMode,
+    last_position: Option<(f32, f32)>,
+}
+
+impl PointerState {
+    pub fn mode(&self) -> PointerMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: PointerMode) {
+        self.mode = mode;
+        self.last_position = None;
+    }
+
+    /// 清除上一次的位置，下一次绝对坐标不产生位移
+    pub fn reset(&mut self) {
+        self.last_position = None;
+    }
+
+    /// 切换指针模式，返回切换后的模式
+    pub fn toggle(&mut self) -> PointerMode {
+        self.set_mode(match self.mode {
+            PointerMode::Absolute => PointerMode::Relative,
+            PointerMode::Relative => PointerMode::Absolute,
+        });
+        self.mode
+    }
+
+    /// 按当前模式转换鼠标事件，返回 `None` 表示无需发送
+    ///
+    /// 事件按捕获设备的原始频率逐个转换，不做合并
+    pub fn apply(&mut self, event: Mouse) -> Option<Mouse> {
+        match (self.mode, event) {
+            (PointerMode::Relative, Mouse::Move { x, y }) => {
+                let last = self.last_position.replace((x, y));
+                let (last_x, last_y) = last?;
+                let (dx, dy) = (x - last_x, y - last_y);
+                (dx != 0.0 || dy != 0.0).then(|| Mouse::motion(dx, dy))
+            }
+            (_, event) => Some(event),
+        }
+    }
+}
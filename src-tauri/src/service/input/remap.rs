@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::service::protocols::{
+    base::OsType,
+    input::{KeyModifiers, Keyboard, key},
+};
+
+/// 单键映射
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyRemap {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// 组合键
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Chord {
+    pub key_code: u32,
+    pub modifiers: KeyModifiers,
+}
+
+/// 组合键映射
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChordRemap {
+    pub from: Chord,
+    pub to: Chord,
+}
+
+/// 按键映射规则
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemapRules {
+    // 交换 Ctrl 与 Logo(Cmd/Win)
+    pub swap_ctrl_logo: bool,
+    // 单键映射
+    pub keys: Vec<KeyRemap>,
+    // 组合键映射，优先于单键映射
+    pub chords: Vec<ChordRemap>,
+}
+
+impl RemapRules {
+    /// 根据控制端与目标端系统生成内置预设
+    ///
+    /// Linux/Windows 与 macOS 之间交换 Ctrl 与 Cmd，其余组合不做映射
+    pub fn preset(from: &OsType, to: &OsType) -> Self {
+        let is_pc = |os: &OsType| matches!(os, OsType::Win | OsType::Nix);
+        let swap_ctrl_logo = (is_pc(from) && *to == OsType::Mac)
+            || (*from == OsType::Mac && is_pc(to));
+        Self { swap_ctrl_logo, ..Default::default() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.swap_ctrl_logo && self.keys.is_empty() && self.chords.is_empty()
+    }
+}
+
+/// 按键映射引擎，每个目标设备持有一个实例
+///
+/// 记录按下时生效的映射结果，保证释放事件与按下事件映射到同一个键
+#[derive(Debug, Default)]
+pub struct KeyRemapper {
+    rules: RemapRules,
+    pressed: HashMap<u32, Chord>,
+}
+
+impl KeyRemapper {
+    pub fn new(rules: RemapRules) -> Self {
+        Self { rules, pressed: HashMap::new() }
+    }
+
+    pub fn rules(&self) -> &RemapRules {
+        &self.rules
+    }
+
+    /// 更新规则，已按下的键仍按旧规则释放
+    pub fn set_rules(&mut self, rules: RemapRules) {
+        self.rules = rules;
+    }
+
+    /// 清空按下记录，在释放所有按键后调用
+    pub fn reset(&mut self) {
+        self.pressed.clear();
+    }
+
+    /// 对键盘事件应用映射
+    pub fn apply(&mut self, event: Keyboard) -> Keyboard {
+        match event {
+            Keyboard::KeyPress { key_code, modifiers, text } => {
+                let mapped = self.map(key_code, modifiers);
+                self.pressed.insert(key_code, mapped);
+                // 映射后原字符不再可信，仅在按键未改变时保留
+                let text = if mapped.key_code == key_code
+                    && mapped.modifiers == modifiers
+                {
+                    text
+                } else {
+                    None
+                };
+                Keyboard::press(mapped.key_code, mapped.modifiers, text)
+            }
+            Keyboard::KeyRelease { key_code, modifiers } => {
+                let chord = self
+                    .pressed
+                    .remove(&key_code)
+                    .unwrap_or_else(|| self.map(key_code, modifiers));
+                Keyboard::release(chord.key_code, chord.modifiers)
+            }
+        }
+    }
+
+    fn map(&self, key_code: u32, modifiers: KeyModifiers) -> Chord {
+        if let Some(rule) = self.rules.chords.iter().find(|rule| {
+            rule.from.key_code == key_code && rule.from.modifiers == modifiers
+        }) {
+            return rule.to;
+        }
+
+        let key_code = self
+            .rules
+            .keys
+            .iter()
+            .find(|rule| rule.from == key_code)
+            .map(|rule| rule.to)
+            .unwrap_or(key_code);
+
+        Chord {
+            key_code: self.map_modifier_key(key_code),
+            modifiers: self.map_modifiers(modifiers),
+        }
+    }
+
+    fn map_modifier_key(&self, key_code: u32) -> u32 {
+        if !self.rules.swap_ctrl_logo {
+            return key_code;
+        }
+        match key_code {
+            key::LEFT_CTRL => key::LEFT_META,
+            key::RIGHT_CTRL => key::RIGHT_META,
+            key::LEFT_META => key::LEFT_CTRL,
+            key::RIGHT_META => key::RIGHT_CTRL,
+            other => other,
+        }
+    }
+
+    fn map_modifiers(&self, modifiers: KeyModifiers) -> KeyModifiers {
+        if !self.rules.swap_ctrl_logo {
+            return modifiers;
+        }
+        KeyModifiers { ctrl: modifiers.logo, logo: modifiers.ctrl, ..modifiers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const C: u32 = 46;
+    const V: u32 = 47;
+    const Q: u32 = 16;
+
+    fn ctrl() -> KeyModifiers {
+        KeyModifiers::new(false, true, false, false)
+    }
+
+    fn logo() -> KeyModifiers {
+        KeyModifiers::new(false, false, false, true)
+    }
+
+    #[test]
+    fn preset_swaps_only_between_pc_and_mac() {
+        let swaps = |from, to| RemapRules::preset(&from, &to).swap_ctrl_logo;
+        assert!(swaps(OsType::Nix, OsType::Mac));
+        assert!(swaps(OsType::Win, OsType::Mac));
+        assert!(swaps(OsType::Mac, OsType::Win));
+        assert!(!swaps(OsType::Nix, OsType::Win));
+        assert!(!swaps(OsType::Mac, OsType::Mac));
+        assert!(!swaps(OsType::Unknown, OsType::Mac));
+        assert!(RemapRules::preset(&OsType::Nix, &OsType::Nix).is_empty());
+    }
+
+    #[test]
+    fn swap_maps_modifier_keys_and_flags() {
+        let mut remapper = KeyRemapper::new(RemapRules {
+            swap_ctrl_logo: true,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            remapper.apply(Keyboard::press(key::LEFT_CTRL, ctrl(), None)),
+            Keyboard::press(key::LEFT_META, logo(), None)
+        );
+        assert_eq!(
+            remapper.apply(Keyboard::press(C, ctrl(), Some('c'))),
+            Keyboard::press(C, logo(), None)
+        );
+        // 未改变的按键保留字符
+        assert_eq!(
+            remapper.apply(Keyboard::press(V, KeyModifiers::none(), Some('v'))),
+            Keyboard::press(V, KeyModifiers::none(), Some('v'))
+        );
+    }
+
+    #[test]
+    fn chords_take_precedence_over_keys() {
+        let mut remapper = KeyRemapper::new(RemapRules {
+            swap_ctrl_logo: true,
+            keys: vec![KeyRemap { from: C, to: V }],
+            chords: vec![ChordRemap {
+                from: Chord { key_code: C, modifiers: ctrl() },
+                to: Chord { key_code: Q, modifiers: ctrl() },
+            }],
+        });
+
+        // 组合键映射的结果不再交换修饰键
+        assert_eq!(
+            remapper.apply(Keyboard::press(C, ctrl(), None)),
+            Keyboard::press(Q, ctrl(), None)
+        );
+        assert_eq!(
+            remapper.apply(Keyboard::press(C, KeyModifiers::none(), None)),
+            Keyboard::press(V, KeyModifiers::none(), None)
+        );
+    }
+
+    #[test]
+    fn release_follows_the_press_after_rules_change() {
+        let mut remapper = KeyRemapper::new(RemapRules {
+            keys: vec![KeyRemap { from: C, to: V }],
+            ..Default::default()
+        });
+        remapper.apply(Keyboard::press(C, ctrl(), None));
+        remapper.set_rules(RemapRules::default());
+
+        assert_eq!(
+            remapper.apply(Keyboard::release(C, KeyModifiers::none())),
+            Keyboard::release(V, ctrl())
+        );
+        // 之后的按键使用新规则
+        assert_eq!(
+            remapper.apply(Keyboard::release(C, KeyModifiers::none())),
+            Keyboard::release(C, KeyModifiers::none())
+        );
+    }
+
+    #[test]
+    fn reset_forgets_pressed_keys() {
+        let mut remapper = KeyRemapper::new(RemapRules {
+            keys: vec![KeyRemap { from: C, to: V }],
+            ..Default::default()
+        });
+        remapper.apply(Keyboard::press(C, KeyModifiers::none(), None));
+        remapper.set_rules(RemapRules::default());
+        remapper.reset();
+
+        assert_eq!(
+            remapper.apply(Keyboard::release(C, KeyModifiers::none())),
+            Keyboard::release(C, KeyModifiers::none())
+        );
+    }
+}
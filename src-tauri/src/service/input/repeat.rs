@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+
+use tokio::time::{Duration, Instant};
+
+use crate::service::protocols::input::{
+    KeyModifiers, Keyboard, RepeatPolicy, key,
+};
+
+/// 本地生成重复的按键
+#[derive(Debug)]
+struct RepeatingKey {
+    key_code: u32,
+    modifiers: KeyModifiers,
+    text: Option<char>,
+    next: Instant,
+}
+
+/// 接收端的键盘自动重复处理
+///
+/// `Local` 策略下为最后按下的非修饰键生成重复事件；
+/// `Forward` 策略下为每个按下的非修饰键维护看门狗，超时后生成释放事件
+#[derive(Debug)]
+pub struct RepeatEngine {
+    policy: RepeatPolicy,
+    repeating: Option<RepeatingKey>,
+    watchdogs: HashMap<u32, Instant>,
+}
+
+impl Default for RepeatEngine {
+    fn default() -> Self {
+        // 未收到控制端策略前，按转发处理并启用看门狗，避免与发送端重复叠加
+        Self::new(RepeatPolicy::Forward { timeout_ms: 1000 })
+    }
+}
+
+impl RepeatEngine {
+    pub fn new(policy: RepeatPolicy) -> Self {
+        Self { policy, repeating: None, watchdogs: HashMap::new() }
+    }
+
+    pub fn policy(&self) -> RepeatPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RepeatPolicy) {
+        self.policy = policy;
+        self.reset();
+    }
+
+    /// 停止所有重复与看门狗
+    pub fn reset(&mut self) {
+        self.repeating = None;
+        self.watchdogs.clear();
+    }
+
+    /// 记录一个已注入的键盘事件
+    pub fn on_event(&mut self, event: &Keyboard, now: Instant) {
+        match *event {
+            Keyboard::KeyPress { key_code, modifiers, text } => {
+                if key::is_modifier(key_code) {
+                    return;
+                }
+                match self.policy {
+                    RepeatPolicy::Local { delay_ms, .. } => {
+                        self.repeating = Some(RepeatingKey {
+                            key_code,
+                            modifiers,
+                            text,
+                            next: now + Duration::from_millis(delay_ms as u64),
+                        });
+                    }
+                    RepeatPolicy::Forward { timeout_ms } => {
+                        self.watchdogs.insert(
+                            key_code,
+                            now + Duration::from_millis(timeout_ms as u64),
+                        );
+                    }
+                }
+            }
+            Keyboard::KeyRelease { key_code, .. } => {
+                if self
+                    .repeating
+                    .as_ref()
+                    .is_some_and(|repeating| repeating.key_code == key_code)
+                {
+                    self.repeating = None;
+                }
+                self.watchdogs.remove(&key_code);
+            }
+        }
+    }
+
+    /// 下一次需要调用 [`RepeatEngine::poll`] 的时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let repeat = self.repeating.as_ref().map(|repeating| repeating.next);
+        let watchdog = self.watchdogs.values().min().copied();
+        match (repeat, watchdog) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// 生成到期的重复或释放事件
+    pub fn poll(&mut self, now: Instant) -> Vec<Keyboard> {
+        let mut events = Vec::new();
+
+        if let (Some(repeating), RepeatPolicy::Local { interval_ms, .. }) =
+            (self.repeating.as_mut(), self.policy)
+            && repeating.next <= now
+        {
+            events.push(Keyboard::press(
+                repeating.key_code,
+                repeating.modifiers,
+                repeating.text,
+            ));
+            // 落后时不补发，避免卡顿后一次性爆发
+            repeating.next =
+                now + Duration::from_millis(interval_ms.max(1) as u64);
+        }
+
+        let expired = self
+            .watchdogs
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key_code, _)| *key_code)
+            .collect::<Vec<_>>();
+        for key_code in expired {
+            self.watchdogs.remove(&key_code);
+            events.push(Keyboard::release(key_code, KeyModifiers::none()));
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: u32 = 30;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn press(key_code: u32) -> Keyboard {
+        Keyboard::press(key_code, KeyModifiers::none(), Some('a'))
+    }
+
+    fn local() -> RepeatEngine {
+        RepeatEngine::new(RepeatPolicy::Local {
+            delay_ms: 500,
+            interval_ms: 30,
+        })
+    }
+
+    #[test]
+    fn local_repeat_follows_delay_and_interval() {
+        let mut engine = local();
+        let start = Instant::now();
+        engine.on_event(&press(A), start);
+
+        assert_eq!(engine.next_deadline(), Some(start + ms(500)));
+        assert!(engine.poll(start + ms(499)).is_empty());
+        assert_eq!(engine.poll(start + ms(500)), vec![press(A)]);
+        assert_eq!(engine.next_deadline(), Some(start + ms(530)));
+        assert!(engine.poll(start + ms(529)).is_empty());
+        assert_eq!(engine.poll(start + ms(530)), vec![press(A)]);
+    }
+
+    #[test]
+    fn local_repeat_does_not_catch_up_after_stall() {
+        let mut engine = local();
+        let start = Instant::now();
+        engine.on_event(&press(A), start);
+
+        // 停顿了 10 个间隔，只补一次
+        let late = start + ms(800);
+        assert_eq!(engine.poll(late), vec![press(A)]);
+        assert!(engine.poll(late).is_empty());
+        assert_eq!(engine.next_deadline(), Some(late + ms(30)));
+    }
+
+    #[test]
+    fn release_stops_repeat() {
+        let mut engine = local();
+        let start = Instant::now();
+        engine.on_event(&press(A), start);
+        engine.on_event(&Keyboard::release(A, KeyModifiers::none()), start);
+
+        assert_eq!(engine.next_deadline(), None);
+        assert!(engine.poll(start + ms(1000)).is_empty());
+    }
+
+    #[test]
+    fn modifiers_never_repeat() {
+        let start = Instant::now();
+        for mut engine in [
+            local(),
+            RepeatEngine::new(RepeatPolicy::Forward { timeout_ms: 100 }),
+        ] {
+            engine.on_event(&press(key::LEFT_SHIFT), start);
+            assert_eq!(engine.next_deadline(), None);
+            assert!(engine.poll(start + ms(1000)).is_empty());
+        }
+
+        // 按住修饰键时不影响正在重复的按键
+        let mut engine = local();
+        engine.on_event(&press(A), start);
+        engine.on_event(&press(key::LEFT_CTRL), start + ms(100));
+        assert_eq!(engine.poll(start + ms(500)), vec![press(A)]);
+    }
+
+    #[test]
+    fn forward_watchdog_releases_after_timeout() {
+        let mut engine =
+            RepeatEngine::new(RepeatPolicy::Forward { timeout_ms: 100 });
+        let start = Instant::now();
+        engine.on_event(&press(A), start);
+
+        assert_eq!(engine.next_deadline(), Some(start + ms(100)));
+        // 转发的重复事件续期看门狗
+        engine.on_event(&press(A), start + ms(80));
+        assert_eq!(engine.next_deadline(), Some(start + ms(180)));
+        assert!(engine.poll(start + ms(100)).is_empty());
+        assert_eq!(
+            engine.poll(start + ms(180)),
+            vec![Keyboard::release(A, KeyModifiers::none())]
+        );
+        assert_eq!(engine.next_deadline(), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use crate::service::protocols::{
+    base::PacketData,
+    input::{KeyModifiers, Keyboard, Mouse, MouseButton},
+};
+
+/// 记录目标设备上处于按下状态的键、鼠标按钮与修饰键
+///
+/// 控制权离开或会话结束时，通过 [`InputTracker::release_all`] 生成对应的释放事件，
+/// 避免目标端按键卡住
+#[derive(Debug, Clone, Default)]
+pub struct InputTracker {
+    keys: HashSet<u32>,
+    buttons: HashSet<MouseButton>,
+    modifiers: KeyModifiers,
+}
+
+impl InputTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录一个输入事件，非输入类数据包会被忽略
+    pub fn track(&mut self, data: &PacketData) {
+        match data {
+            PacketData::Key(event) => self.track_key(event),
+            PacketData::Mouse(event) => self.track_mouse(event),
+            _ => {}
+        }
+    }
+
+    pub fn track_key(&mut self, event: &Keyboard) {
+        match event {
+            Keyboard::KeyPress { key_code, modifiers, .. } => {
+                self.keys.insert(*key_code);
+                self.modifiers = *modifiers;
+            }
+            Keyboard::KeyRelease { key_code, modifiers } => {
+                self.keys.remove(key_code);
+                self.modifiers = *modifiers;
+            }
+        }
+    }
+
+    pub fn track_mouse(&mut self, event: &Mouse) {
+        if let Mouse::Button { button, pressed } = event {
+            if *pressed {
+                self.buttons.insert(*button);
+            } else {
+                self.buttons.remove(button);
+            }
+        }
+    }
+
+    /// 当前修饰键状态
+    pub fn modifiers(&self) -> KeyModifiers {
+        self.modifiers
+    }
+
+    /// 指定键是否处于按下状态
+    pub fn is_key_pressed(&self, key_code: u32) -> bool {
+        self.keys.contains(&key_code)
+    }
+
+    /// 是否有鼠标按钮按下
+    pub fn has_button_pressed(&self) -> bool {
+        !self.buttons.is_empty()
+    }
+
+    /// 是否没有任何键或按钮处于按下状态
+    pub fn is_idle(&self) -> bool {
+        self.keys.is_empty() && self.buttons.is_empty()
+    }
+
+    /// 生成所有按下键与按钮的释放事件，并清空记录
+    pub fn release_all(&mut self) -> Vec<PacketData> {
+        let mut events =
+            Vec::with_capacity(self.keys.len() + self.buttons.len());
+        events.extend(
+            self.buttons
+                .drain()
+                .map(|button| PacketData::Mouse(Mouse::button(button, false))),
+        );
+        events.extend(self.keys.drain().map(|key_code| {
+            PacketData::Key(Keyboard::release(key_code, KeyModifiers::none()))
+        }));
+        self.modifiers = KeyModifiers::none();
+        events
+    }
+}
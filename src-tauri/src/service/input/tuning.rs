@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+
+use crate::service::protocols::{base::DisplayInfo, input::Mouse};
+
+/// 指针加速曲线
+///
+/// 单次位移不超过 `threshold` 时不加速，超出部分每像素增加 `gain` 倍，最多 `max` 倍
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Acceleration {
+    pub threshold: f32,
+    pub gain: f32,
+    pub max: f32,
+}
+
+impl Acceleration {
+    /// 给定位移长度对应的倍率
+    pub fn factor(&self, speed: f32) -> f32 {
+        let excess = (speed - self.threshold).max(0.0);
+        (1.0 + excess * self.gain).min(self.max.max(1.0))
+    }
+}
+
+/// 针对目标设备的指针调节参数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PointerTuning {
+    // 线性灵敏度
+    pub sensitivity: f32,
+    // 加速曲线，为空时不加速
+    pub acceleration: Option<Acceleration>,
+    // 水平滚动倍率
+    pub scroll_x: f32,
+    // 垂直滚动倍率
+    pub scroll_y: f32,
+    // 反转滚动方向（自然滚动）
+    pub invert_scroll: bool,
+}
+
+impl Default for PointerTuning {
+    fn default() -> Self {
+        Self {
+            sensitivity: 1.0,
+            acceleration: None,
+            scroll_x: 1.0,
+            scroll_y: 1.0,
+            invert_scroll: false,
+        }
+    }
+}
+
+impl PointerTuning {
+    /// 调节一次相对位移
+    pub fn tune_delta(&self, dx: f32, dy: f32) -> (f32, f32) {
+        let accel = self
+            .acceleration
+            .map(|accel| accel.factor(dx.hypot(dy)))
+            .unwrap_or(1.0);
+        let factor = self.sensitivity * accel;
+        (dx * factor, dy * factor)
+    }
+
+    /// 调节一次滚动
+    pub fn tune_scroll(&self, dx: f32, dy: f32) -> (f32, f32) {
+        let direction = if self.invert_scroll { -1.0 } else { 1.0 };
+        (dx * self.scroll_x * direction, dy * self.scroll_y * direction)
+    }
+}
+
+/// 会话的指针调节状态
+///
+/// 绝对坐标按相邻两次的位移调节后累加到虚拟光标上，虚拟光标限制在目标设备的显示器内
+#[derive(Debug, Default)]
+pub struct TuningState {
+    tuning: PointerTuning,
+    // 目标设备各显示器的物理范围 `(left, top, right, bottom)`
+    displays: Vec<(f32, f32, f32, f32)>,
+    last_input: Option<(f32, f32)>,
+    // 虚拟光标，为空时下一次绝对坐标原样发送
+    output: Option<(f32, f32)>,
+}
+
+impl TuningState {
+    pub fn tuning(&self) -> PointerTuning {
+        self.tuning
+    }
+
+    /// 更新调节参数，无需重连即可生效
+    pub fn set_tuning(&mut self, tuning: PointerTuning) {
+        self.tuning = tuning;
+    }
+
+    /// 更新目标设备的显示器，为空时不限制虚拟光标
+    pub fn set_displays(&mut self, displays: &[DisplayInfo]) {
+        self.displays = displays
+            .iter()
+            .filter(|d| d.width > 0 && d.height > 0)
+            .map(|d| {
+                (d.x as f32, d.y as f32, d.right() as f32, d.bottom() as f32)
+            })
+            .collect();
+    }
+
+    /// 重新开始计算位移，虚拟光标保持在原位置
+    pub fn reset(&mut self) {
+        self.last_input = None;
+    }
+
+    /// 将虚拟光标移动到目标设备的物理坐标，之后的位移从该位置开始累加
+    pub fn warp(&mut self, x: f32, y: f32) {
+        self.last_input = None;
+        self.output = Some(self.clamp(x, y));
+    }
+
+    pub fn apply(&mut self, event: Mouse) -> Mouse {
+        match event {
+            Mouse::Move { x, y } => {
+                let output =
+                    match (self.last_input.replace((x, y)), self.output) {
+                        (Some((last_x, last_y)), Some((out_x, out_y))) => {
+                            let (dx, dy) =
+                                self.tuning.tune_delta(x - last_x, y - last_y);
+                            (out_x + dx, out_y + dy)
+                        }
+                        // 重置或移动后的第一次只记录输入位置
+                        (None, Some(output)) => output,
+                        (_, None) => (x, y),
+                    };
+                let output = self.clamp(output.0, output.1);
+                self.output = Some(output);
+                Mouse::move_to(output.0, output.1)
+            }
+            Mouse::Motion { dx, dy } => {
+                let (dx, dy) = self.tuning.tune_delta(dx, dy);
+                Mouse::motion(dx, dy)
+            }
+            Mouse::Scroll { dx, dy, kind } => {
+                let (dx, dy) = self.tuning.tune_scroll(dx, dy);
+                Mouse::Scroll { dx, dy, kind }
+            }
+            event => event,
+        }
+    }
+
+    /// 将坐标限制到最近的显示器内
+    fn clamp(&self, x: f32, y: f32) -> (f32, f32) {
+        self.displays
+            .iter()
+            .map(|&(left, top, right, bottom)| {
+                (
+                    x.clamp(left, (right - 1.0).max(left)),
+                    y.clamp(top, (bottom - 1.0).max(top)),
+                )
+            })
+            .min_by(|a, b| {
+                let distance = |(px, py): (f32, f32)| (px - x).hypot(py - y);
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .unwrap_or((x, y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(x: i32, y: i32, width: u32, height: u32) -> DisplayInfo {
+        DisplayInfo { x, y, width, height, scale: 1.0, primary: x == 0 }
+    }
+
+    fn position(event: Mouse) -> (f32, f32) {
+        match event {
+            Mouse::Move { x, y } => (x, y),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn first_move_after_warp_stays_at_warp_position() {
+        let mut state = TuningState::default();
+        state.set_displays(&[display(0, 0, 1920, 1080)]);
+        state.warp(1.0, 500.0);
+
+        assert_eq!(
+            position(state.apply(Mouse::move_to(1900.0, 20.0))),
+            (1.0, 500.0)
+        );
+        assert_eq!(
+            position(state.apply(Mouse::move_to(1910.0, 25.0))),
+            (11.0, 505.0)
+        );
+    }
+
+    #[test]
+    fn output_is_clamped_to_nearest_display() {
+        let mut state = TuningState::default();
+        state.set_displays(&[
+            display(0, 0, 1920, 1080),
+            display(1920, 0, 1280, 720),
+        ]);
+        state.warp(100.0, 100.0);
+        state.apply(Mouse::move_to(0.0, 0.0));
+
+        assert_eq!(
+            position(state.apply(Mouse::move_to(-500.0, 0.0))),
+            (0.0, 100.0)
+        );
+        state.warp(3000.0, 700.0);
+        state.apply(Mouse::move_to(0.0, 0.0));
+        assert_eq!(
+            position(state.apply(Mouse::move_to(0.0, 300.0))),
+            (3000.0, 719.0)
+        );
+    }
+
+    #[test]
+    fn without_displays_first_move_is_sent_as_is() {
+        let mut state = TuningState::default();
+
+        assert_eq!(
+            position(state.apply(Mouse::move_to(-5.0, 40.0))),
+            (-5.0, 40.0)
+        );
+    }
+}
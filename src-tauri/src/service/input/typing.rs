@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::service::protocols::input::{KeyModifiers, Keyboard, key};
+
+/// 将剪贴板文本作为按键输入的设置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TypingSettings {
+    // 相邻两个按键事件的间隔，虚拟机控制台等目标过快时会丢键
+    pub key_delay_ms: u32,
+    // 最多输入的字符数，超过时不输入
+    pub max_chars: usize,
+}
+
+impl Default for TypingSettings {
+    fn default() -> Self {
+        Self { key_delay_ms: 15, max_chars: 2000 }
+    }
+}
+
+// US 布局下可输入的字符：键码、不按 Shift 的字符、按 Shift 的字符
+#[rustfmt::skip]
+const KEYMAP: &[(u32, char, char)] = &[
+    (2, '1', '!'), (3, '2', '@'), (4, '3', '#'), (5, '4', '$'),
+    (6, '5', '%'), (7, '6', '^'), (8, '7', '&'), (9, '8', '*'),
+    (10, '9', '('), (11, '0', ')'), (12, '-', '_'), (13, '=', '+'),
+    (16, 'q', 'Q'), (17, 'w', 'W'), (18, 'e', 'E'), (19, 'r', 'R'),
+    (20, 't', 'T'), (21, 'y', 'Y'), (22, 'u', 'U'), (23, 'i', 'I'),
+    (24, 'o', 'O'), (25, 'p', 'P'), (26, '[', '{'), (27, ']', '}'),
+    (30, 'a', 'A'), (31, 's', 'S'), (32, 'd', 'D'), (33, 'f', 'F'),
+    (34, 'g', 'G'), (35, 'h', 'H'), (36, 'j', 'J'), (37, 'k', 'K'),
+    (38, 'l', 'L'), (39, ';', ':'), (40, '\'', '"'), (41, '`', '~'),
+    (43, '\\', '|'), (44, 'z', 'Z'), (45, 'x', 'X'), (46, 'c', 'C'),
+    (47, 'v', 'V'), (48, 'b', 'B'), (49, 'n', 'N'), (50, 'm', 'M'),
+    (51, ',', '<'), (52, '.', '>'), (53, '/', '?'),
+];
+
+const TAB: u32 = 15;
+const ENTER: u32 = 28;
+const SPACE: u32 = 57;
+
+/// 字符对应的键码以及是否需要按住 Shift
+///
+/// `caps_lock` 为目标端大写锁定状态，开启时字母的 Shift 相反
+fn lookup(ch: char, caps_lock: bool) -> Option<(u32, bool)> {
+    match ch {
+        '\t' => return Some((TAB, false)),
+        '\n' => return Some((ENTER, false)),
+        ' ' => return Some((SPACE, false)),
+        _ => {}
+    }
+    let (key_code, shift) =
+        KEYMAP.iter().find_map(|&(key_code, plain, shifted)| {
+            if ch == plain {
+                Some((key_code, false))
+            } else if ch == shifted {
+                Some((key_code, true))
+            } else {
+                None
+            }
+        })?;
+    Some((key_code, shift ^ (caps_lock && ch.is_ascii_alphabetic())))
+}
+
+/// 将文本转换为按键事件，每个字符一组，组内依次为按下与释放
+///
+/// 按键采用 US 布局的键码，按下事件同时携带字符，支持直接输入字符的注入后端可以不受目标布局影响。
+/// 换行统一为回车，无法输入的字符跳过，与按键事件一起返回
+pub fn keystrokes(
+    text: &str,
+    caps_lock: bool,
+) -> (Vec<Vec<Keyboard>>, Vec<char>) {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let shift = KeyModifiers::new(true, false, false, false);
+    let mut skipped = Vec::new();
+    let strokes = text
+        .chars()
+        .filter_map(|ch| {
+            let Some((key_code, shifted)) = lookup(ch, caps_lock) else {
+                skipped.push(ch);
+                return None;
+            };
+            let text = (!ch.is_control()).then_some(ch);
+            Some(if shifted {
+                vec![
+                    Keyboard::press(key::LEFT_SHIFT, shift, None),
+                    Keyboard::press(key_code, shift, text),
+                    Keyboard::release(key_code, shift),
+                    Keyboard::release(key::LEFT_SHIFT, KeyModifiers::none()),
+                ]
+            } else {
+                vec![
+                    Keyboard::press(key_code, KeyModifiers::none(), text),
+                    Keyboard::release(key_code, KeyModifiers::none()),
+                ]
+            })
+        })
+        .collect();
+    (strokes, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifted_and_caps_lock_letters() {
+        assert_eq!(lookup('a', false), Some((30, false)));
+        assert_eq!(lookup('A', false), Some((30, true)));
+        assert_eq!(lookup('A', true), Some((30, false)));
+        // 大写锁定不影响符号
+        assert_eq!(lookup('!', true), Some((2, true)));
+    }
+
+    #[test]
+    fn untypable_characters_are_skipped() {
+        let (strokes, skipped) = keystrokes("a\r\nb中é", false);
+        assert_eq!(strokes.len(), 3);
+        assert_eq!(
+            strokes[1][0],
+            Keyboard::press(ENTER, KeyModifiers::none(), None)
+        );
+        assert_eq!(skipped, ['中', 'é']);
+    }
+}
@@ -0,0 +1,593 @@
+use std::collections::HashSet;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use parking_lot::Mutex;
+use spdlog::{debug, error};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        Event,
+        randr::{ConnectionExt as _, NotifyMask},
+        xinput::{self, ConnectionExt as _, Device, Fp3232, XIEventMask},
+        xproto::{
+            self, AtomEnum, ChangeKeyboardControlAux, ConnectionExt as _,
+            GrabMode, InputFocus, KeyButMask, LedMode, Window,
+        },
+        xtest::ConnectionExt as _,
+    },
+    rust_connection::RustConnection,
+};
+
+use super::{
+    capture::{self, CaptureBackend},
+    cursor_lock::{FocusProbe, FocusedWindow},
+    inject::Injector,
+    mapping,
+};
+use crate::service::protocols::{
+    base::PacketData,
+    input::{KeyModifiers, Keyboard, LockState, Mouse, key},
+};
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        _NET_ACTIVE_WINDOW,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FULLSCREEN,
+    }
+}
+
+/// X11 前台窗口查询
+///
+/// 优先读取窗口管理器设置的 `_NET_ACTIVE_WINDOW`，没有窗口管理器时（如 Xvfb）回退到输入焦点窗口
+pub struct X11FocusProbe {
+    conn: RustConnection,
+    root: Window,
+    atoms: Atoms,
+}
+
+impl X11FocusProbe {
+    /// 连接 `DISPLAY` 指定的 X 服务器
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        Ok(Self { conn, root, atoms })
+    }
+
+    fn active_window(&self) -> Result<Option<Window>> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                self.atoms._NET_ACTIVE_WINDOW,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?;
+        if let Some(window) =
+            reply.value32().and_then(|mut values| values.next())
+            && window != x11rb::NONE
+        {
+            return Ok(Some(window));
+        }
+
+        let focus = self.conn.get_input_focus()?.reply()?.focus;
+        let valid = focus != x11rb::NONE
+            && focus != u32::from(InputFocus::POINTER_ROOT)
+            && focus != self.root;
+        Ok(valid.then_some(focus))
+    }
+
+    /// 窗口类名，`WM_CLASS` 的格式为 "实例名\0类名\0"
+    fn window_class(&self, window: Window) -> Result<String> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                0,
+                1024,
+            )?
+            .reply()?;
+        Ok(reply
+            .value
+            .split(|byte| *byte == 0)
+            .nth(1)
+            .map(|class| String::from_utf8_lossy(class).into_owned())
+            .unwrap_or_default())
+    }
+
+    fn is_fullscreen(&self, window: Window) -> Result<bool> {
+        let reply = self
+            .conn
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_WM_STATE,
+                AtomEnum::ATOM,
+                0,
+                64,
+            )?
+            .reply()?;
+        Ok(reply.value32().is_some_and(|mut states| {
+            states.any(|state| state == self.atoms._NET_WM_STATE_FULLSCREEN)
+        }))
+    }
+
+    fn query(&self) -> Result<Option<FocusedWindow>> {
+        let Some(window) = self.active_window()? else {
+            return Ok(None);
+        };
+        Ok(Some(FocusedWindow {
+            app: self.window_class(window)?,
+            fullscreen: self.is_fullscreen(window)?,
+        }))
+    }
+}
+
+impl FocusProbe for X11FocusProbe {
+    fn focused_window(&self) -> Option<FocusedWindow> {
+        match self.query() {
+            Ok(window) => window,
+            Err(e) => {
+                debug!("Failed to query focused window: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// 显示器变化事件的合并时间
+const DISPLAY_CHANGE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 在后台线程中监听 XRandR 显示器配置变化
+///
+/// 插拔显示器时会连续产生多个事件，合并后只调用一次 `on_change`
+pub fn watch_displays(on_change: impl Fn() + Send + 'static) -> Result<()> {
+    let (conn, screen_num) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen_num].root;
+    conn.randr_query_version(1, 2)?.reply()?;
+    conn.randr_select_input(
+        root,
+        NotifyMask::SCREEN_CHANGE
+            | NotifyMask::OUTPUT_CHANGE
+            | NotifyMask::CRTC_CHANGE,
+    )?;
+    conn.flush()?;
+
+    std::thread::Builder::new().name("xrandr-watcher".to_string()).spawn(
+        move || loop {
+            match conn.wait_for_event() {
+                Ok(
+                    Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_),
+                ) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    error!("XRandR watcher stopped: {}", e);
+                    break;
+                }
+            }
+            std::thread::sleep(DISPLAY_CHANGE_DEBOUNCE);
+            while let Ok(Some(_)) = conn.poll_for_event() {}
+            on_change();
+        },
+    )?;
+    Ok(())
+}
+
+// X 键码比 evdev 键码大 8
+const KEYCODE_OFFSET: u32 = 8;
+// 核心协议的指示灯编号，Xorg 下依次为 Caps Lock、Num Lock 与 Scroll Lock
+const LED_CAPS: u32 = 1;
+const LED_NUM: u32 = 2;
+const LED_SCROLL: u32 = 3;
+
+/// 读取锁定键状态
+///
+/// Caps Lock 与 Num Lock 取自锁定的修饰键（Lock 与 Mod2），
+/// Scroll Lock 没有对应的修饰键，取自指示灯
+fn read_lock_state(conn: &RustConnection, root: Window) -> Result<LockState> {
+    let mask = conn.query_pointer(root)?.reply()?.mask;
+    let leds = conn.get_keyboard_control()?.reply()?.led_mask;
+    Ok(LockState {
+        caps: mask.contains(KeyButMask::LOCK),
+        num: mask.contains(KeyButMask::MOD2),
+        scroll: leds & (1 << (LED_SCROLL - 1)) != 0,
+    })
+}
+
+/// X11 输入注入，通过 XTEST 扩展模拟键盘与鼠标
+///
+/// 核心协议只能表示整数位移与整格滚动，小数部分累计到下一次事件
+pub struct X11Injector {
+    conn: RustConnection,
+    root: Window,
+    motion: Mutex<(f32, f32)>,
+    scroll: Mutex<(f32, f32)>,
+}
+
+impl X11Injector {
+    /// 连接 `DISPLAY` 指定的 X 服务器
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        conn.xtest_get_version(2, 2)?.reply()?;
+        Ok(Self {
+            conn,
+            root,
+            motion: Mutex::new((0.0, 0.0)),
+            scroll: Mutex::new((0.0, 0.0)),
+        })
+    }
+
+    fn fake(&self, type_: u8, detail: u8, x: i16, y: i16) -> Result<()> {
+        self.conn.xtest_fake_input(
+            type_,
+            detail,
+            x11rb::CURRENT_TIME,
+            self.root,
+            x,
+            y,
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn click(&self, button: u8, pressed: bool) -> Result<()> {
+        let type_ = if pressed {
+            xproto::BUTTON_PRESS_EVENT
+        } else {
+            xproto::BUTTON_RELEASE_EVENT
+        };
+        self.fake(type_, button, 0, 0)
+    }
+}
+
+impl Injector for X11Injector {
+    fn key(&self, event: &Keyboard) -> Result<()> {
+        let (type_, key_code) = match *event {
+            Keyboard::KeyPress { key_code, .. } => {
+                (xproto::KEY_PRESS_EVENT, key_code)
+            }
+            Keyboard::KeyRelease { key_code, .. } => {
+                (xproto::KEY_RELEASE_EVENT, key_code)
+            }
+        };
+        let detail = u8::try_from(key_code + KEYCODE_OFFSET)
+            .map_err(|_| anyhow!("Key code {} out of range", key_code))?;
+        self.fake(type_, detail, 0, 0)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn mouse(&self, event: &Mouse) -> Result<()> {
+        match *event {
+            Mouse::Move { x, y } => self.fake(
+                xproto::MOTION_NOTIFY_EVENT,
+                0,
+                x.round() as i16,
+                y.round() as i16,
+            )?,
+            // detail 为 1 表示相对位移
+            Mouse::Motion { dx, dy } => {
+                let (dx, dy) = accumulate(&self.motion, dx, dy);
+                self.fake(xproto::MOTION_NOTIFY_EVENT, 1, dx as i16, dy as i16)?
+            }
+            Mouse::Button { button, pressed } => {
+                self.click(mapping::x11::to_button(button), pressed)?
+            }
+            Mouse::Scroll { dx, dy, kind } => {
+                let (dx, dy) = accumulate(
+                    &self.scroll,
+                    mapping::notches(dx, kind),
+                    mapping::notches(dy, kind),
+                );
+                for button in mapping::x11::to_scroll_buttons(dx, dy) {
+                    self.click(button, true)?;
+                    self.click(button, false)?;
+                }
+            }
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn lock_state(&self) -> Option<LockState> {
+        match read_lock_state(&self.conn, self.root) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                debug!("Failed to read lock state: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// 累加上次剩余的小数部分，返回整数部分并保留新的小数部分
+fn accumulate(remainder: &Mutex<(f32, f32)>, dx: f32, dy: f32) -> (f32, f32) {
+    let mut remainder = remainder.lock();
+    let (dx, dy) = (remainder.0 + dx, remainder.1 + dy);
+    *remainder = (dx.fract(), dy.fract());
+    (dx.trunc(), dy.trunc())
+}
+
+/// X11 输入捕获，通过 XInput2 原始事件读取本机键鼠
+///
+/// 原始事件不影响本机应用接收输入。独占时抓取键盘与指针，
+/// 本机光标可能停在屏幕边缘，光标位置改由原始位移累加
+pub struct X11Capture {
+    conn: Arc<RustConnection>,
+    root: Window,
+    grabbed: Arc<AtomicBool>,
+    // 锁定键状态，由捕获线程在锁定键按下和释放后刷新
+    locks: Arc<Mutex<Option<LockState>>>,
+}
+
+impl X11Capture {
+    /// 连接 `DISPLAY` 指定的 X 服务器，在后台线程中读取输入并交给输入路由
+    pub fn start() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        conn.xinput_xi_query_version(2, 2)?.reply()?;
+        conn.xinput_xi_select_events(
+            root,
+            &[xinput::EventMask {
+                deviceid: Device::ALL_MASTER.into(),
+                mask: vec![
+                    XIEventMask::RAW_KEY_PRESS
+                        | XIEventMask::RAW_KEY_RELEASE
+                        | XIEventMask::RAW_BUTTON_PRESS
+                        | XIEventMask::RAW_BUTTON_RELEASE
+                        | XIEventMask::RAW_MOTION,
+                ],
+            }],
+        )?
+        .check()?;
+
+        let conn = Arc::new(conn);
+        let grabbed = Arc::new(AtomicBool::new(false));
+        let locks = Arc::new(Mutex::new(read_lock_state(&conn, root).ok()));
+        let reader = CaptureReader {
+            conn: conn.clone(),
+            root,
+            grabbed: grabbed.clone(),
+            locks: locks.clone(),
+            modifiers: HashSet::new(),
+            position: None,
+        };
+        std::thread::Builder::new()
+            .name("x11-capture".to_string())
+            .spawn(move || reader.run())?;
+        Ok(Self { conn, root, grabbed, locks })
+    }
+}
+
+impl CaptureBackend for X11Capture {
+    /// 返回捕获线程缓存的状态，不与 X 服务器往返
+    fn lock_state(&self) -> Option<LockState> {
+        *self.locks.lock()
+    }
+
+    fn set_leds(&self, state: LockState) -> Result<()> {
+        for (led, lit) in [
+            (LED_CAPS, state.caps),
+            (LED_NUM, state.num),
+            (LED_SCROLL, state.scroll),
+        ] {
+            let mode = if lit { LedMode::ON } else { LedMode::OFF };
+            self.conn.change_keyboard_control(
+                &ChangeKeyboardControlAux::new().led(led).led_mode(mode),
+            )?;
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn grab(&self, grabbed: bool) -> Result<()> {
+        // 不等待抓取结果，避免在输入路由中与 X 服务器往返
+        if grabbed {
+            drop(self.conn.grab_pointer(
+                false,
+                self.root,
+                xproto::EventMask::NO_EVENT,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )?);
+            drop(self.conn.grab_keyboard(
+                false,
+                self.root,
+                x11rb::CURRENT_TIME,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+            )?);
+        } else {
+            self.conn.ungrab_pointer(x11rb::CURRENT_TIME)?;
+            self.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+        }
+        self.grabbed.store(grabbed, Ordering::SeqCst);
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+/// 捕获线程的状态
+struct CaptureReader {
+    conn: Arc<RustConnection>,
+    root: Window,
+    grabbed: Arc<AtomicBool>,
+    locks: Arc<Mutex<Option<LockState>>>,
+    // 按下的修饰键
+    modifiers: HashSet<u32>,
+    // 独占期间累加的光标位置
+    position: Option<(f32, f32)>,
+}
+
+impl CaptureReader {
+    fn run(mut self) {
+        loop {
+            let event = match self.conn.wait_for_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    error!("X11 capture stopped: {}", e);
+                    break;
+                }
+            };
+            if let Some(data) = self.convert(event) {
+                capture::submit(data);
+            }
+        }
+    }
+
+    fn convert(&mut self, event: Event) -> Option<PacketData> {
+        match event {
+            Event::XinputRawKeyPress(event) => self.key(event.detail, true),
+            Event::XinputRawKeyRelease(event) => self.key(event.detail, false),
+            Event::XinputRawButtonPress(event) => {
+                mapping::x11::from_button(event.detail as u8, true)
+                    .map(PacketData::Mouse)
+            }
+            Event::XinputRawButtonRelease(event) => {
+                mapping::x11::from_button(event.detail as u8, false)
+                    .map(PacketData::Mouse)
+            }
+            Event::XinputRawMotion(event) => {
+                let delta = raw_delta(&event.valuator_mask, &event.axisvalues);
+                self.motion(delta)
+            }
+            _ => None,
+        }
+    }
+
+    fn key(&mut self, detail: u32, pressed: bool) -> Option<PacketData> {
+        let key_code = detail.checked_sub(KEYCODE_OFFSET)?;
+        if key::is_lock(key_code) {
+            *self.locks.lock() = read_lock_state(&self.conn, self.root).ok();
+        }
+        if key::is_modifier(key_code) {
+            if pressed {
+                self.modifiers.insert(key_code);
+            } else {
+                self.modifiers.remove(&key_code);
+            }
+        }
+        let held = |codes: [u32; 2]| {
+            codes.iter().any(|code| self.modifiers.contains(code))
+        };
+        let modifiers = KeyModifiers::new(
+            held([key::LEFT_SHIFT, key::RIGHT_SHIFT]),
+            held([key::LEFT_CTRL, key::RIGHT_CTRL]),
+            held([key::LEFT_ALT, key::RIGHT_ALT]),
+            held([key::LEFT_META, key::RIGHT_META]),
+        );
+        let event = if pressed {
+            Keyboard::press(key_code, modifiers, None)
+        } else {
+            Keyboard::release(key_code, modifiers)
+        };
+        Some(PacketData::Key(event))
+    }
+
+    fn motion(&mut self, (dx, dy): (f32, f32)) -> Option<PacketData> {
+        let position = match self.position {
+            Some((x, y)) if self.grabbed.load(Ordering::SeqCst) => {
+                (x + dx, y + dy)
+            }
+            _ => {
+                let reply = self.conn.query_pointer(self.root).ok()?.reply();
+                let reply = reply.ok()?;
+                (reply.root_x as f32, reply.root_y as f32)
+            }
+        };
+        self.position = Some(position);
+        Some(PacketData::Mouse(Mouse::move_to(position.0, position.1)))
+    }
+}
+
+/// 原始移动事件的 x、y 位移，只包含掩码中置位的轴
+fn raw_delta(valuator_mask: &[u32], values: &[Fp3232]) -> (f32, f32) {
+    let mask = valuator_mask.first().copied().unwrap_or(0);
+    let mut values = values.iter();
+    let mut delta = [0.0; 2];
+    for (axis, slot) in delta.iter_mut().enumerate() {
+        if mask & (1 << axis) != 0
+            && let Some(value) = values.next()
+        {
+            *slot = value.integral as f32 + value.frac as f32 / 4_294_967_296.0;
+        }
+    }
+    (delta[0], delta[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::{
+        protocol::xproto::{CreateWindowAux, PropMode, WindowClass},
+        wrapper::ConnectionExt as _,
+    };
+
+    use super::*;
+
+    /// 需要没有窗口管理器的 X 服务器，如 `Xvfb :99 & DISPLAY=:99 cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn focus_probe_reads_focused_window() -> Result<()> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let screen = &conn.setup().roots[screen_num];
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let window = conn.generate_id()?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            0,
+            0,
+            100,
+            100,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            0,
+            &CreateWindowAux::new(),
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            window,
+            AtomEnum::WM_CLASS,
+            AtomEnum::STRING,
+            b"sync-pointer-test\0SyncPointerTest\0",
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            window,
+            atoms._NET_WM_STATE,
+            AtomEnum::ATOM,
+            &[atoms._NET_WM_STATE_FULLSCREEN],
+        )?;
+        conn.map_window(window)?;
+        conn.set_input_focus(InputFocus::PARENT, window, x11rb::CURRENT_TIME)?;
+        conn.get_input_focus()?.reply()?;
+
+        let focused = X11FocusProbe::connect()?.focused_window();
+
+        assert_eq!(
+            focused,
+            Some(FocusedWindow {
+                app: "SyncPointerTest".to_string(),
+                fullscreen: true,
+            })
+        );
+        Ok(())
+    }
+}
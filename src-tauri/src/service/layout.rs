@@ -0,0 +1,529 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::service::{
+    input::cursor_lock::CursorLockRules, protocols::base::DisplayInfo,
+    switch::SwitchRules,
+};
+
+/// 进入设备时与边缘保持的最小距离，落在边缘上会立即切换回去
+pub const ENTRY_INSET: f64 = 1.0;
+
+/// 屏幕边缘
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Edge {
+    /// 相对的边缘
+    pub fn opposite(&self) -> Self {
+        match self {
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+            Edge::Top => Edge::Bottom,
+            Edge::Bottom => Edge::Top,
+        }
+    }
+}
+
+/// 设备在布局平面上的位置与尺寸
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Screen {
+    pub device_id: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Screen {
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    /// 是否与另一屏幕重叠，仅共享边缘不算重叠
+    pub fn intersects(&self, other: &Screen) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+
+    /// 设备的物理坐标转换为设备内布局坐标，显示器未知时原样返回
+    pub fn from_physical(
+        &self,
+        displays: &[DisplayInfo],
+        x: f64,
+        y: f64,
+    ) -> (f64, f64) {
+        match bounds(displays) {
+            Some((bx, by, bw, bh)) => {
+                ((x - bx) / bw * self.width, (y - by) / bh * self.height)
+            }
+            None => (x, y),
+        }
+    }
+
+    /// 设备内布局坐标转换为设备的物理坐标，显示器未知时原样返回
+    pub fn to_physical(
+        &self,
+        displays: &[DisplayInfo],
+        x: f64,
+        y: f64,
+    ) -> (f64, f64) {
+        match bounds(displays) {
+            Some((bx, by, bw, bh)) => {
+                (bx + x / self.width * bw, by + y / self.height * bh)
+            }
+            None => (x, y),
+        }
+    }
+
+    /// 将设备内坐标移到距各边缘至少 [`ENTRY_INSET`] 的位置
+    pub fn inset(&self, x: f64, y: f64) -> (f64, f64) {
+        let inset = |value: f64, length: f64| {
+            value.clamp(
+                ENTRY_INSET,
+                (length - 1.0 - ENTRY_INSET).max(ENTRY_INSET),
+            )
+        };
+        (inset(x, self.width), inset(y, self.height))
+    }
+
+    /// 设备内坐标所在的边缘，不在边缘时返回 `None`
+    pub fn edge_at(&self, x: f64, y: f64) -> Option<Edge> {
+        if x <= 0.0 {
+            Some(Edge::Left)
+        } else if x >= self.width - 1.0 {
+            Some(Edge::Right)
+        } else if y <= 0.0 {
+            Some(Edge::Top)
+        } else if y >= self.height - 1.0 {
+            Some(Edge::Bottom)
+        } else {
+            None
+        }
+    }
+}
+
+/// 跨越边缘的结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Crossing {
+    // 进入的设备
+    pub device_id: String,
+    // 进入的边缘
+    pub edge: Edge,
+    // 进入设备内的坐标
+    pub x: f64,
+    pub y: f64,
+}
+
+/// 自由布局，设备可放置在平面任意位置，允许错位与部分边缘重合
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Layout {
+    pub screens: Vec<Screen>,
+    // 边缘切换规则
+    pub switching: SwitchRules,
+    // 自动锁定光标规则
+    pub cursor_lock: CursorLockRules,
+}
+
+impl Layout {
+    pub fn screen(&self, device_id: &str) -> Option<&Screen> {
+        self.screens.iter().find(|screen| screen.device_id == device_id)
+    }
+
+    /// 放置设备，已存在时更新位置，与其他设备重叠时返回错误
+    pub fn place(&mut self, screen: Screen) -> Result<()> {
+        self.check(&screen)?;
+        match self
+            .screens
+            .iter_mut()
+            .find(|other| other.device_id == screen.device_id)
+        {
+            Some(existing) => *existing = screen,
+            None => self.screens.push(screen),
+        }
+        Ok(())
+    }
+
+    /// 检查整个布局，规则与 [`Layout::place`] 相同，且同一设备只能出现一次
+    pub fn validate(&self) -> Result<()> {
+        for (index, screen) in self.screens.iter().enumerate() {
+            if self.screens[..index]
+                .iter()
+                .any(|other| other.device_id == screen.device_id)
+            {
+                return Err(anyhow!("Duplicate screen {}", screen.device_id));
+            }
+            self.check(screen)?;
+        }
+        Ok(())
+    }
+
+    /// 检查屏幕尺寸有效且不与其他设备重叠
+    fn check(&self, screen: &Screen) -> Result<()> {
+        if screen.width <= 0.0 || screen.height <= 0.0 {
+            return Err(anyhow!(
+                "Invalid screen size for {}",
+                screen.device_id
+            ));
+        }
+        if let Some(other) = self.screens.iter().find(|other| {
+            other.device_id != screen.device_id && other.intersects(screen)
+        }) {
+            return Err(anyhow!(
+                "Screen {} overlaps {}",
+                screen.device_id,
+                other.device_id
+            ));
+        }
+        Ok(())
+    }
+
+    /// 设备显示器变化后按原有比例调整屏幕尺寸，位置不变，返回尺寸是否变化
+    ///
+    /// 原显示器未知时无法换算，不调整
+    pub fn resize(
+        &mut self,
+        device_id: &str,
+        from: &[DisplayInfo],
+        to: &[DisplayInfo],
+    ) -> Result<bool> {
+        let Some(screen) = self.screen(device_id) else {
+            return Ok(false);
+        };
+        let (
+            Some((_, _, from_width, from_height)),
+            Some((_, _, width, height)),
+        ) = (bounds(from), bounds(to))
+        else {
+            return Ok(false);
+        };
+        let resized = Screen {
+            width: width * screen.width / from_width,
+            height: height * screen.height / from_height,
+            ..screen.clone()
+        };
+        if resized == *screen {
+            return Ok(false);
+        }
+        self.place(resized)?;
+        Ok(true)
+    }
+
+    /// 移除设备
+    pub fn remove(&mut self, device_id: &str) -> Option<Screen> {
+        let index = self
+            .screens
+            .iter()
+            .position(|screen| screen.device_id == device_id)?;
+        Some(self.screens.remove(index))
+    }
+
+    /// 从设备 `device_id` 的 `edge` 边缘上的点 `(x, y)` 离开时进入的设备与位置
+    ///
+    /// 坐标为设备内坐标。取该方向上与离开点所在行/列重合的最近设备
+    pub fn cross(
+        &self,
+        device_id: &str,
+        edge: Edge,
+        x: f64,
+        y: f64,
+    ) -> Option<Crossing> {
+        let from = self.screen(device_id)?;
+        let (gx, gy) = (from.x + x, from.y + y);
+
+        self.screens
+            .iter()
+            .filter(|to| to.device_id != device_id)
+            .filter_map(|to| {
+                let distance = match edge {
+                    Edge::Right if to.y <= gy && gy < to.bottom() => {
+                        to.x - from.right()
+                    }
+                    Edge::Left if to.y <= gy && gy < to.bottom() => {
+                        from.x - to.right()
+                    }
+                    Edge::Bottom if to.x <= gx && gx < to.right() => {
+                        to.y - from.bottom()
+                    }
+                    Edge::Top if to.x <= gx && gx < to.right() => {
+                        from.y - to.bottom()
+                    }
+                    _ => return None,
+                };
+                (distance >= 0.0).then_some((distance, to))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, to)| Self::enter(to, edge, gx, gy))
+    }
+
+    /// 从最外侧边缘离开时环绕到反方向最远的设备
+    ///
+    /// 参数与 [`Layout::cross`] 相同，通常在其返回 `None` 时调用
+    pub fn wrap(
+        &self,
+        device_id: &str,
+        edge: Edge,
+        x: f64,
+        y: f64,
+    ) -> Option<Crossing> {
+        let from = self.screen(device_id)?;
+        let (gx, gy) = (from.x + x, from.y + y);
+
+        self.screens
+            .iter()
+            .filter(|to| to.device_id != device_id)
+            .filter_map(|to| {
+                let position = match edge {
+                    Edge::Right if to.y <= gy && gy < to.bottom() => to.x,
+                    Edge::Left if to.y <= gy && gy < to.bottom() => -to.right(),
+                    Edge::Bottom if to.x <= gx && gx < to.right() => to.y,
+                    Edge::Top if to.x <= gx && gx < to.right() => -to.bottom(),
+                    _ => return None,
+                };
+                Some((position, to))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, to)| Self::enter(to, edge, gx, gy))
+    }
+
+    /// 沿 `edge` 方向移动时从相对边缘进入 `to` 的位置，`(gx, gy)` 为布局平面坐标
+    fn enter(to: &Screen, edge: Edge, gx: f64, gy: f64) -> Crossing {
+        let (x, y) = match edge {
+            Edge::Right => (0.0, gy - to.y),
+            Edge::Left => (to.width - 1.0, gy - to.y),
+            Edge::Bottom => (gx - to.x, 0.0),
+            Edge::Top => (gx - to.x, to.height - 1.0),
+        };
+        let (x, y) = to.inset(x, y);
+        Crossing {
+            device_id: to.device_id.clone(),
+            edge: edge.opposite(),
+            x,
+            y,
+        }
+    }
+}
+
+impl DisplayInfo {
+    pub fn right(&self) -> f64 {
+        self.x as f64 + self.width as f64
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y as f64 + self.height as f64
+    }
+
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.x as f64 <= x
+            && x < self.right()
+            && self.y as f64 <= y
+            && y < self.bottom()
+    }
+
+    /// 沿边缘方向的起点与长度
+    fn span(&self, edge: Edge) -> (f64, f64) {
+        match edge {
+            Edge::Left | Edge::Right => (self.y as f64, self.height as f64),
+            Edge::Top | Edge::Bottom => (self.x as f64, self.width as f64),
+        }
+    }
+
+    /// 点沿边缘方向在显示器内的相对位置，范围 0..1
+    fn fraction(&self, edge: Edge, x: f64, y: f64) -> f64 {
+        let (start, length) = self.span(edge);
+        let position = match edge {
+            Edge::Left | Edge::Right => y,
+            Edge::Top | Edge::Bottom => x,
+        };
+        ((position - start) / length).clamp(0.0, 1.0)
+    }
+}
+
+/// 所有显示器的外接矩形 `(x, y, width, height)`
+pub fn bounds(displays: &[DisplayInfo]) -> Option<(f64, f64, f64, f64)> {
+    let left = displays.iter().map(|d| d.x as f64).min_by(f64::total_cmp)?;
+    let top = displays.iter().map(|d| d.y as f64).min_by(f64::total_cmp)?;
+    let right = displays.iter().map(|d| d.right()).max_by(f64::total_cmp)?;
+    let bottom = displays.iter().map(|d| d.bottom()).max_by(f64::total_cmp)?;
+    (right > left && bottom > top).then_some((
+        left,
+        top,
+        right - left,
+        bottom - top,
+    ))
+}
+
+/// 物理坐标所在的显示器，不在任何显示器内时取最近的显示器
+pub fn display_at(
+    displays: &[DisplayInfo],
+    x: f64,
+    y: f64,
+) -> Option<&DisplayInfo> {
+    displays.iter().find(|display| display.contains(x, y)).or_else(|| {
+        displays
+            .iter()
+            .min_by(|a, b| distance(a, x, y).total_cmp(&distance(b, x, y)))
+    })
+}
+
+fn distance(display: &DisplayInfo, x: f64, y: f64) -> f64 {
+    let dx = (display.x as f64 - x).max(x - display.right()).max(0.0);
+    let dy = (display.y as f64 - y).max(y - display.bottom()).max(0.0);
+    dx.hypot(dy)
+}
+
+/// 不在任何显示器内的物理坐标移动到最近显示器内的位置，已在显示器内时返回 `None`
+pub fn clamp_to_displays(
+    displays: &[DisplayInfo],
+    x: f64,
+    y: f64,
+) -> Option<(f64, f64)> {
+    let display = display_at(displays, x, y)?;
+    if display.contains(x, y) {
+        return None;
+    }
+    Some((
+        x.clamp(display.x as f64, display.right() - 1.0),
+        y.clamp(display.y as f64, display.bottom() - 1.0),
+    ))
+}
+
+/// 在 `edge` 边缘上的显示器，按沿边缘方向排序
+fn edge_displays(displays: &[DisplayInfo], edge: Edge) -> Vec<&DisplayInfo> {
+    let outer = |display: &DisplayInfo| match edge {
+        Edge::Left => -(display.x as f64),
+        Edge::Right => display.right(),
+        Edge::Top => -(display.y as f64),
+        Edge::Bottom => display.bottom(),
+    };
+    let Some(limit) = displays.iter().map(outer).max_by(f64::total_cmp) else {
+        return Vec::new();
+    };
+    let mut result: Vec<_> =
+        displays.iter().filter(|display| outer(display) == limit).collect();
+    result.sort_by(|a, b| a.span(edge).0.total_cmp(&b.span(edge).0));
+    result
+}
+
+/// 将从源设备 `edge` 边缘离开的物理坐标映射为目标设备上的物理坐标
+///
+/// 按各显示器自身的物理尺寸计算相对位置，从 200% 缩放的笔记本移到 100% 的显示器时
+/// 光标保持在相同的相对高度
+pub fn map_crossing(
+    from: &[DisplayInfo],
+    to: &[DisplayInfo],
+    edge: Edge,
+    x: f64,
+    y: f64,
+) -> Option<(f64, f64)> {
+    let source = display_at(from, x, y)?;
+    let fraction = source.fraction(edge, x, y);
+
+    // 源设备边缘上的全部显示器视为一条连续的边，按所在序号选择目标显示器
+    let sources = edge_displays(from, edge);
+    let index =
+        sources.iter().position(|display| *display == source).unwrap_or(0);
+    let targets = edge_displays(to, edge.opposite());
+    let target = if targets.len() == sources.len() {
+        targets.get(index)
+    } else {
+        let overall = (index as f64 + fraction) / sources.len().max(1) as f64;
+        targets.get(
+            ((overall * targets.len() as f64) as usize)
+                .min(targets.len().saturating_sub(1)),
+        )
+    }?;
+
+    let (start, length) = target.span(edge);
+    let along = start + fraction * (length - 1.0).max(0.0);
+    Some(match edge {
+        Edge::Right => (target.x as f64 + ENTRY_INSET, along),
+        Edge::Left => (target.right() - 1.0 - ENTRY_INSET, along),
+        Edge::Bottom => (along, target.y as f64 + ENTRY_INSET),
+        Edge::Top => (along, target.bottom() - 1.0 - ENTRY_INSET),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen(device_id: &str, x: f64, width: f64) -> Screen {
+        Screen { device_id: device_id.into(), x, y: 0.0, width, height: 1080.0 }
+    }
+
+    fn layout(screens: Vec<Screen>) -> Layout {
+        Layout { screens, ..Default::default() }
+    }
+
+    #[test]
+    fn validate_accepts_adjacent_screens() {
+        let layout =
+            layout(vec![screen("a", 0.0, 1920.0), screen("b", 1920.0, 1280.0)]);
+
+        assert!(layout.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_overlap_size_and_duplicates() {
+        let overlap =
+            layout(vec![screen("a", 0.0, 1920.0), screen("b", 1900.0, 1280.0)]);
+        let empty = layout(vec![screen("a", 0.0, 0.0)]);
+        let duplicate =
+            layout(vec![screen("a", 0.0, 1920.0), screen("a", 1920.0, 1280.0)]);
+
+        assert!(overlap.validate().is_err());
+        assert!(empty.validate().is_err());
+        assert!(duplicate.validate().is_err());
+    }
+
+    #[test]
+    fn resize_keeps_scale_and_rejects_overlap() {
+        let display = |x: i32, width: u32| DisplayInfo {
+            x,
+            y: 0,
+            width,
+            height: 1080,
+            scale: 1.0,
+            primary: x == 0,
+        };
+        let mut layout =
+            layout(vec![screen("a", 0.0, 960.0), screen("b", 1920.0, 1280.0)]);
+        let before = [display(0, 1920)];
+        let after = [display(0, 1920), display(1920, 1920)];
+
+        assert!(layout.resize("a", &before, &after).unwrap());
+        assert_eq!(layout.screen("a").unwrap().width, 1920.0);
+        assert!(!layout.resize("a", &after, &after).unwrap());
+        assert!(!layout.resize("a", &[], &after).unwrap());
+
+        let wider = [display(0, 1920), display(1920, 2560)];
+        assert!(layout.resize("a", &after, &wider).is_err());
+        assert_eq!(layout.screen("a").unwrap().width, 1920.0);
+    }
+
+    #[test]
+    fn enter_is_inset_from_the_edge() {
+        let layout =
+            layout(vec![screen("a", 0.0, 1920.0), screen("b", 1920.0, 1280.0)]);
+        let crossing = layout.cross("a", Edge::Right, 1919.0, 500.0).unwrap();
+
+        assert_eq!(crossing.device_id, "b");
+        assert_eq!(
+            layout.screen("b").unwrap().edge_at(crossing.x, crossing.y),
+            None
+        );
+    }
+}
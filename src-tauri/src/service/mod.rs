@@ -1,8 +1,14 @@
 pub mod client;
+pub mod clipboard;
 pub mod codec;
 pub mod handler;
+pub mod input;
+pub mod layout;
 pub mod protocols;
+pub mod sender;
 pub mod server;
+pub mod switch;
+pub mod transfer;
 
 use anyhow::{Result, anyhow};
 use parking_lot::RwLock;
@@ -11,6 +17,21 @@ use std::sync::Arc;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 
+use crate::{config, core};
+use protocols::base::{DeviceInfo, OsType};
+
+/// 本机设备信息，连接建立后通过 `Init` 发送给对端
+pub fn local_device_info() -> DeviceInfo {
+    DeviceInfo {
+        id: config::system::config().unwrap_or_default().id(),
+        name: config::network::get_config().hostname(),
+        os: OsType::current(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        caps: clipboard::capabilities(),
+        displays: core::display::local_displays(),
+    }
+}
+
 #[derive(Debug)]
 pub struct ServiceControl {
     service_name: String,
@@ -2,7 +2,10 @@ use crate::{service::protocols::input, util};
 use rkyv::{Archive, Deserialize, Serialize};
 use std::{collections::HashMap, fmt::Debug};
 
-use super::clipboard::Clipboard;
+use super::{
+    clipboard::{Clipboard, Selection},
+    transfer::Transfer,
+};
 
 /// 基础设备信息
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -12,6 +15,18 @@ pub struct DeviceInfo {
     pub os: OsType,
     pub version: String,
     pub caps: Vec<String>, // 简化 capabilities
+    pub displays: Vec<DisplayInfo>,
+}
+
+/// 显示器信息，坐标与尺寸均为物理像素
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DisplayInfo {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: f64, // 缩放比例
+    pub primary: bool,
 }
 
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -22,6 +37,30 @@ pub enum OsType {
     Unknown,
 }
 
+impl OsType {
+    /// 当前运行的系统类型
+    pub fn current() -> Self {
+        if cfg!(target_os = "windows") {
+            Self::Win
+        } else if cfg!(target_os = "macos") {
+            Self::Mac
+        } else if cfg!(target_os = "linux") {
+            Self::Nix
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// 服务端集中管理的配置，`version` 只增不减，内容为 JSON
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ManagedConfig {
+    pub version: u64,
+    pub layout: String,
+    pub settings: String, // 接收方设备的设置
+    pub policy: String,   // 剪贴板过滤策略
+}
+
 /// 统一状态信息
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct StatusInfo {
@@ -52,12 +91,25 @@ pub enum PacketData {
     Ping,             // 心跳检测
     Pong,             // 心跳响应
 
+    // 显示器
+    Displays(Vec<DisplayInfo>), // 显示器配置变化
+
+    // 配置同步
+    Managed(ManagedConfig), // 服务端下发的集中管理配置
+
     // 输入事件
-    Mouse(input::Mouse),  // 鼠标事件
-    Key(input::Keyboard), // 键盘事件
+    Mouse(input::Mouse),         // 鼠标事件
+    Key(input::Keyboard),        // 键盘事件
+    Repeat(input::RepeatPolicy), // 键盘自动重复策略
+    Locks(input::LockState),     // 锁定键状态
+    PointerLock(bool),           // 目标端指针是否被应用锁定
 
     // 剪贴板
-    Clip(Clipboard), // 剪贴板数据
+    Clip(Clipboard),    // 剪贴板数据
+    Primary(Clipboard), // PRIMARY 选区数据，仅发送给声明支持的设备
+
+    // 文件传输，`to` 为接收消息的设备 id，不是服务端时由服务端转发
+    File { to: String, msg: Transfer },
 }
 
 impl DataPacket {
@@ -78,3 +130,13 @@ impl DataPacket {
         Self::new(device_id, PacketData::Fail(msg.into()))
     }
 }
+
+impl PacketData {
+    /// 按选区包装剪贴板消息
+    pub fn clipboard(selection: Selection, message: Clipboard) -> Self {
+        match selection {
+            Selection::Clipboard => PacketData::Clip(message),
+            Selection::Primary => PacketData::Primary(message),
+        }
+    }
+}
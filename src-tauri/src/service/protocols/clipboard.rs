@@ -1,39 +1,88 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
 /// 剪贴板内容类型
+///
+/// 同一条剪贴板内容可以同时包含多种类型，例如网页复制的 HTML 与纯文本
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum ClipType {
     // 简化名称
-    Text,  // 纯文本
-    Rich,  // 富文本
+    Text,  // 纯文本，UTF-8
+    Rich,  // 富文本，UTF-8 编码的 HTML 片段
     Img,   // 图片
     Files, // 文件列表
+    Rtf,   // RTF 文档
+}
+
+/// 剪贴板选区
+#[derive(
+    Archive, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy,
+)]
+pub enum Selection {
+    Clipboard, // 常规剪贴板
+    Primary,   // X11 选中即复制、中键粘贴的选区
+}
+
+/// 剪贴板内容压缩算法
+#[derive(
+    Archive, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy,
+)]
+pub enum Compression {
+    None,
+    Zstd,
+    Lz4, // 内容前 4 字节为解压后大小，小端序
 }
 
 /// 剪贴板内容
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct ClipData {
     // 简化名称
-    pub ty: ClipType,   // 使用更短的字段名
-    pub data: Vec<u8>,  // 使用字节数组存储
-    pub ts: u64,        // 时间戳
-    pub compress: bool, // 是否压缩
+    pub ty: ClipType,      // 使用更短的字段名
+    pub data: Vec<u8>,     // 使用字节数组存储
+    pub ts: u64,           // 时间戳
+    pub comp: Compression, // 压缩算法
+}
+
+/// 剪贴板内容摘要
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ClipMeta {
+    pub ty: ClipType,
+    pub size: u64, // 未压缩的大小
+}
+
+/// 剪贴板历史中的一条内容
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ClipEntry {
+    pub items: Vec<ClipData>, // 同一内容的多种表示
+    pub ts: u64,
+    pub origin: String, // 来源设备 id
 }
 
 /// 剪贴板消息
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum Clipboard {
-    /// 更新内容
-    Set { data: ClipData, force: bool },
+    /// 更新内容，`items` 为同一内容的多种表示
+    Set { items: Vec<ClipData>, force: bool },
 
-    /// 获取内容
+    /// 只告知可提供的内容，对端需要时按哈希用 `Get` 获取
+    Offer { items: Vec<ClipMeta>, hash: u64, ts: u64 },
+
+    /// 获取内容，指定 `since` 时获取该序号之后的历史
     Get {
         types: Option<Vec<ClipType>>,
-        since: Option<u64>, // 上次同步时间
+        since: Option<u64>, // 上次同步到的历史序号
+        hash: Option<u64>,  // 获取 `Offer` 提供的内容
     },
 
+    /// 历史内容，对应指定了 `since` 的 `Get`，分页发送
+    ///
+    /// `cursor` 为本页同步到的序号，`more` 为真时还有条目，需以 `cursor` 继续获取
+    History { entries: Vec<ClipEntry>, cursor: u64, more: bool },
+
     /// 内容数据
-    Data { items: Vec<ClipData> },
+    Data {
+        items: Vec<ClipData>,
+        hash: Option<u64>, // 对应 `Get` 的哈希
+    },
 
     /// 清除
     Clear,
@@ -42,8 +91,35 @@ pub enum Clipboard {
     Cleared,
 }
 
+impl ClipType {
+    /// 所有类型，同一内容有多种表示时按此顺序排列
+    pub const ALL: [ClipType; 5] = [
+        ClipType::Files,
+        ClipType::Img,
+        ClipType::Rich,
+        ClipType::Rtf,
+        ClipType::Text,
+    ];
+
+    /// 对应的 MIME 类型
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ClipType::Text => "text/plain;charset=utf-8",
+            ClipType::Rich => "text/html",
+            ClipType::Img => "image/png",
+            ClipType::Files => "text/uri-list",
+            ClipType::Rtf => "text/rtf",
+        }
+    }
+
+    /// 是否为文本格式
+    pub fn is_text(&self) -> bool {
+        matches!(self, ClipType::Text | ClipType::Rich | ClipType::Rtf)
+    }
+}
+
 impl ClipData {
-    pub fn new(ty: ClipType, data: Vec<u8>, compress: bool) -> Self {
+    pub fn new(ty: ClipType, data: Vec<u8>) -> Self {
         Self {
             ty,
             data,
@@ -51,22 +127,37 @@ impl ClipData {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_millis() as u64,
-            compress,
+            comp: Compression::None,
         }
     }
 
+    /// 内容摘要
+    pub fn meta(&self) -> ClipMeta {
+        ClipMeta { ty: self.ty.clone(), size: self.data.len() as u64 }
+    }
+
     /// 创建文本内容
     pub fn text(text: impl Into<String>) -> Self {
-        Self::new(ClipType::Text, text.into().into_bytes(), false)
+        Self::new(ClipType::Text, text.into().into_bytes())
+    }
+
+    /// 创建 HTML 富文本内容
+    pub fn html(html: impl Into<String>) -> Self {
+        Self::new(ClipType::Rich, html.into().into_bytes())
+    }
+
+    /// 创建 RTF 内容
+    pub fn rtf(rtf: Vec<u8>) -> Self {
+        Self::new(ClipType::Rtf, rtf)
     }
 
-    /// 创建压缩的图片内容
+    /// 创建图片内容
     pub fn image(data: Vec<u8>) -> Self {
-        Self::new(ClipType::Img, data, true)
+        Self::new(ClipType::Img, data)
     }
 
     /// 创建文件列表
     pub fn files(paths: Vec<String>) -> Self {
-        Self::new(ClipType::Files, paths.join("\n").into_bytes(), false)
+        Self::new(ClipType::Files, paths.join("\n").into_bytes())
     }
 }
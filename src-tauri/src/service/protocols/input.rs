@@ -1,6 +1,58 @@
 use rkyv::{Archive, Deserialize, Serialize};
 
-#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+/// 常用键码，`key_code` 统一采用 Linux evdev 键码
+pub mod key {
+    pub const ESC: u32 = 1;
+    pub const LEFT_CTRL: u32 = 29;
+    pub const LEFT_SHIFT: u32 = 42;
+    pub const RIGHT_SHIFT: u32 = 54;
+    pub const LEFT_ALT: u32 = 56;
+    pub const CAPS_LOCK: u32 = 58;
+    pub const NUM_LOCK: u32 = 69;
+    pub const SCROLL_LOCK: u32 = 70;
+    pub const RIGHT_CTRL: u32 = 97;
+    pub const RIGHT_ALT: u32 = 100;
+    pub const PAUSE: u32 = 119;
+    pub const LEFT: u32 = 105;
+    pub const RIGHT: u32 = 106;
+    pub const LEFT_META: u32 = 125;
+    pub const RIGHT_META: u32 = 126;
+
+    /// 是否为修饰键
+    pub fn is_modifier(key_code: u32) -> bool {
+        matches!(
+            key_code,
+            LEFT_CTRL
+                | LEFT_SHIFT
+                | RIGHT_SHIFT
+                | LEFT_ALT
+                | RIGHT_CTRL
+                | RIGHT_ALT
+                | LEFT_META
+                | RIGHT_META
+        )
+    }
+
+    /// 是否为锁定键
+    pub fn is_lock(key_code: u32) -> bool {
+        matches!(key_code, CAPS_LOCK | NUM_LOCK | SCROLL_LOCK)
+    }
+}
+
+#[derive(
+    Archive,
+    Serialize,
+    Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+)]
 pub struct KeyModifiers {
     pub shift: bool,
     pub ctrl: bool,
@@ -38,19 +90,73 @@ impl Keyboard {
     }
 }
 
-#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone, Copy)]
+/// 锁定键状态
+#[derive(
+    Archive, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default,
+)]
+pub struct LockState {
+    pub caps: bool,
+    pub num: bool,
+    pub scroll: bool,
+}
+
+/// 键盘自动重复策略
+#[derive(
+    Archive,
+    Serialize,
+    Deserialize,
+    serde::Serialize,
+    serde::Deserialize,
+    PartialEq,
+    Debug,
+    Clone,
+    Copy,
+)]
+pub enum RepeatPolicy {
+    /// 发送端抑制自动重复，由接收端按给定延迟与间隔在本地生成
+    Local { delay_ms: u32, interval_ms: u32 },
+    /// 转发发送端的重复事件，超时未收到重复或释放时由接收端停止
+    Forward { timeout_ms: u32 },
+}
+
+impl Default for RepeatPolicy {
+    fn default() -> Self {
+        Self::Local { delay_ms: 500, interval_ms: 33 }
+    }
+}
+
+#[derive(
+    Archive, Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy,
+)]
 pub enum MouseButton {
     Left,
     Right,
     Middle,
+    Back,    // 侧键 后退
+    Forward, // 侧键 前进
     Other(u8),
 }
 
+/// 滚动单位
+#[derive(
+    Archive, Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy,
+)]
+pub enum ScrollKind {
+    /// 滚轮，单位为格，高精度滚轮可产生小数
+    Wheel,
+    /// 触控板等连续滚动，单位为像素
+    Continuous,
+}
+
+/// 鼠标事件，`Move` 为绝对坐标，`Motion` 为原始相对位移
+///
+/// 滚动时 dx 向右为正，dy 向上为正
 #[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub enum Mouse {
     Move { x: f32, y: f32 },
+    Motion { dx: f32, dy: f32 },
     Button { button: MouseButton, pressed: bool },
-    Scroll(f32),
+    Scroll { dx: f32, dy: f32, kind: ScrollKind },
 }
 
 impl Mouse {
@@ -58,11 +164,21 @@ impl Mouse {
         Self::Move { x, y }
     }
 
+    pub fn motion(dx: f32, dy: f32) -> Self {
+        Self::Motion { dx, dy }
+    }
+
     pub fn button(button: MouseButton, pressed: bool) -> Self {
         Self::Button { button, pressed }
     }
 
-    pub fn scroll(amount: f32) -> Self {
-        Self::Scroll(amount)
+    /// 滚轮滚动，单位为格
+    pub fn scroll(dx: f32, dy: f32) -> Self {
+        Self::Scroll { dx, dy, kind: ScrollKind::Wheel }
+    }
+
+    /// 连续滚动，单位为像素
+    pub fn scroll_continuous(dx: f32, dy: f32) -> Self {
+        Self::Scroll { dx, dy, kind: ScrollKind::Continuous }
     }
 }
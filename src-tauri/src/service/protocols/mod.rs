@@ -1,3 +1,4 @@
 pub mod base;
 pub mod clipboard;
 pub mod input;
+pub mod transfer;
@@ -0,0 +1,50 @@
+use rkyv::{Archive, Deserialize, Serialize};
+
+/// 文件清单中的条目
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FileEntry {
+    pub path: String, // 以 `/` 分隔的相对路径，首段为复制的文件或目录名
+    pub dir: bool,
+    pub size: u64,
+    pub hash: u64, // 内容的 xxh3 校验和，目录为 0
+}
+
+/// 文件传输消息，`id` 由接收方生成
+///
+/// 接收方按条目逐个拉取，每次只允许发送方发送一定量的数据，处理后再继续拉取；
+/// 续传时从已接收的大小继续
+#[derive(Archive, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum Transfer {
+    /// 请求剪贴板内容 `hash` 中的文件
+    Request { id: u64, hash: u64 },
+
+    /// 文件清单，目录排在其中的文件之前
+    Manifest { id: u64, entries: Vec<FileEntry> },
+
+    /// 发送第 `index` 个条目从 `offset` 开始的 `len` 字节
+    ///
+    /// `offset` 为上次允许发送的末尾时追加额度，否则从 `offset` 重新发送
+    Pull { id: u64, index: u32, offset: u64, len: u64 },
+
+    /// 文件数据
+    Chunk { id: u64, index: u32, offset: u64, data: Vec<u8> },
+
+    /// 全部接收完成
+    Done { id: u64 },
+
+    /// 取消或出错，任一方均可发送
+    Abort { id: u64, reason: String },
+}
+
+impl Transfer {
+    pub fn id(&self) -> u64 {
+        match self {
+            Transfer::Request { id, .. }
+            | Transfer::Manifest { id, .. }
+            | Transfer::Pull { id, .. }
+            | Transfer::Chunk { id, .. }
+            | Transfer::Done { id }
+            | Transfer::Abort { id, .. } => *id,
+        }
+    }
+}
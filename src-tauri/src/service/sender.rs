@@ -0,0 +1,118 @@
+use anyhow::{Result, anyhow};
+use futures_util::{Sink, SinkExt};
+use spdlog::{debug, error};
+use tokio::{select, sync::mpsc, task::JoinHandle};
+
+use super::protocols::base::DataPacket;
+
+enum Outgoing {
+    Packet(DataPacket),
+    // 写完队列中已有的数据后关闭连接
+    Close,
+}
+
+/// 连接的发送端，由独立任务写入连接
+///
+/// 发送方只将数据包放入队列，不等待网络，可以在持有会话或路由锁时调用。
+/// 文件数据等大量数据放入单独的队列，控制消息总是优先写入，不会排在数据之后
+#[derive(Clone)]
+pub struct PacketSender {
+    tx: mpsc::UnboundedSender<Outgoing>,
+    bulk: mpsc::UnboundedSender<DataPacket>,
+}
+
+impl PacketSender {
+    /// 启动写入任务，返回发送端与任务句柄，写入出错或关闭后任务结束
+    pub fn spawn<W>(writer: W) -> (Self, JoinHandle<()>)
+    where
+        W: Sink<DataPacket, Error = anyhow::Error> + Unpin + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (bulk, bulk_rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(Self::run(writer, rx, bulk_rx));
+        (Self { tx, bulk }, task)
+    }
+
+    /// 将数据包放入发送队列
+    pub fn send(&self, packet: DataPacket) -> Result<()> {
+        self.tx
+            .send(Outgoing::Packet(packet))
+            .map_err(|_| anyhow!("Connection closed"))
+    }
+
+    /// 将大量数据放入数据队列，控制消息空闲时才写入
+    ///
+    /// 队列不限长度，由调用方控制排队的数据量
+    pub fn send_bulk(&self, packet: DataPacket) -> Result<()> {
+        self.bulk.send(packet).map_err(|_| anyhow!("Connection closed"))
+    }
+
+    /// 发送完已入队的数据后关闭连接
+    pub fn close(&self) {
+        let _ = self.tx.send(Outgoing::Close);
+    }
+
+    /// 写入任务是否已结束
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    async fn run<W>(
+        mut writer: W,
+        mut rx: mpsc::UnboundedReceiver<Outgoing>,
+        mut bulk: mpsc::UnboundedReceiver<DataPacket>,
+    ) where
+        W: Sink<DataPacket, Error = anyhow::Error> + Unpin,
+    {
+        loop {
+            let packet = select! {
+                biased;
+                outgoing = rx.recv() => match outgoing {
+                    Some(Outgoing::Packet(packet)) => packet,
+                    Some(Outgoing::Close) | None => break,
+                },
+                Some(packet) = bulk.recv() => packet,
+            };
+            if let Err(e) = writer.send(packet).await {
+                error!("Failed to write packet: {}", e);
+                break;
+            }
+        }
+        // 先关闭队列，之后的发送立即失败
+        rx.close();
+        bulk.close();
+        if let Err(e) = writer.close().await {
+            debug!("Failed to close connection: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::protocols::base::PacketData;
+
+    #[tokio::test]
+    async fn control_packets_are_written_before_bulk() {
+        let (written, mut rx) = mpsc::unbounded_channel();
+        let writer = Box::pin(futures_util::sink::unfold(
+            written,
+            |written: mpsc::UnboundedSender<DataPacket>, packet| async move {
+                written.send(packet)?;
+                Ok::<_, anyhow::Error>(written)
+            },
+        ));
+        let (sender, task) = PacketSender::spawn(writer);
+        let bulk = DataPacket::new("device", PacketData::Fail("bulk".into()));
+        let control =
+            DataPacket::new("device", PacketData::Fail("control".into()));
+        sender.send_bulk(bulk.clone()).unwrap();
+        sender.send(control.clone()).unwrap();
+
+        assert_eq!(rx.recv().await, Some(control));
+        assert_eq!(rx.recv().await, Some(bulk.clone()));
+        sender.close();
+        task.await.unwrap();
+        assert!(sender.send_bulk(bulk).is_err());
+    }
+}
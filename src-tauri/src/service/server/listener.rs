@@ -1,11 +1,19 @@
 use std::sync::Arc;
 
+use crate::config;
 use crate::service::ServiceControl;
 use crate::service::codec::DataPacketReader;
+use crate::service::input::capture;
+use crate::service::protocols::{
+    base::{DataPacket, PacketData},
+    clipboard::{Clipboard, Selection},
+};
+use crate::service::server::tcp::TcpServer;
+use crate::service::transfer::FileTransfers;
 use anyhow::Result;
 use futures_util::StreamExt;
 use parking_lot::RwLock;
-use spdlog::{error, info};
+use spdlog::{debug, error, info};
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
 use tokio::{select, time::Instant};
@@ -30,7 +38,11 @@ impl ServerListener {
         Self::default()
     }
 
-    pub async fn start(&self, mut reader: DataPacketReader) -> Result<()> {
+    pub async fn start(
+        &self,
+        mut reader: DataPacketReader,
+        session_key: String,
+    ) -> Result<()> {
         let last_activity = self.last_activity.clone();
         // 创建定时器
         let mut interval =
@@ -38,10 +50,13 @@ impl ServerListener {
         let mdns_start_logic =
             move |mut rx: oneshot::Receiver<bool>| -> Result<JoinHandle<()>> {
                 let task = tokio::spawn(async move {
+                    // 由对端断开或超时结束时需要移除会话
+                    let mut peer_closed = true;
                     loop {
                         select! {
                             _ = &mut rx => {
                                 info!("Received shutdown signal");
+                                peer_closed = false;
                                 break;
                             },
                             _ = interval.tick() => {
@@ -61,9 +76,14 @@ impl ServerListener {
                                     }
                                     Some(Ok(data)) => {
                                         // Message received
-                                        let mut last_activity_guard = last_activity.write();
-                                        *last_activity_guard = Instant::now();
-                                        info!("Received data: {:?}", data.ts);
+                                        {
+                                            let mut last_activity_guard = last_activity.write();
+                                            *last_activity_guard = Instant::now();
+                                        }
+                                        match data.deserialize() {
+                                            Ok(packet) => Self::handle_packet(&session_key, packet).await,
+                                            Err(e) => error!("{}", e),
+                                        }
                                     }
                                     Some(Err(e)) => {
                                         // Error occurred
@@ -76,6 +96,11 @@ impl ServerListener {
                     }
                     drop(reader);
                     drop(last_activity);
+                    if peer_closed {
+                        TcpServer::instance()
+                            .on_session_closed(&session_key)
+                            .await;
+                    }
                 });
                 Ok(task)
             };
@@ -83,6 +108,75 @@ impl ServerListener {
         Ok(())
     }
 
+    /// 处理客户端发来的数据包
+    async fn handle_packet(session_key: &str, packet: DataPacket) {
+        match packet.data {
+            PacketData::Init(device_info) => {
+                TcpServer::instance().on_device_init(session_key, device_info);
+            }
+            PacketData::Displays(displays) => {
+                TcpServer::instance().on_displays(session_key, displays).await;
+            }
+            PacketData::Locks(state) => {
+                // 将目标设备的锁定键状态同步到本机键盘指示灯
+                if config::device::get_config(&packet.d).mirror_lock_leds()
+                    && let Err(e) = capture::capture().set_leds(state)
+                {
+                    error!("Failed to set leds: {}", e);
+                }
+            }
+            PacketData::PointerLock(locked) => {
+                TcpServer::instance().on_pointer_lock(&packet.d, locked);
+            }
+            PacketData::Clip(message) => {
+                Self::spawn_clipboard(
+                    session_key,
+                    packet.d,
+                    Selection::Clipboard,
+                    message,
+                );
+            }
+            PacketData::Primary(message) => {
+                Self::spawn_clipboard(
+                    session_key,
+                    packet.d,
+                    Selection::Primary,
+                    message,
+                );
+            }
+            PacketData::File { to, msg } => {
+                // 按顺序交给传输任务，保证数据块的顺序，发往其他设备的消息直接转发
+                if to == config::system::config().unwrap_or_default().id() {
+                    FileTransfers::instance().dispatch(&packet.d, msg);
+                } else if let Err(e) = TcpServer::instance()
+                    .send_transfer(&to, &packet.d, msg)
+                    .await
+                {
+                    debug!(
+                        "addr: {} Failed to relay transfer: {}",
+                        session_key, e
+                    );
+                }
+            }
+            other => debug!("Received data: {:?}", other),
+        }
+    }
+
+    /// 处理剪贴板消息，可能需要等待其他设备返回内容，不阻塞本会话
+    fn spawn_clipboard(
+        session_key: &str,
+        origin: String,
+        selection: Selection,
+        message: Clipboard,
+    ) {
+        let session_key = session_key.to_string();
+        tokio::spawn(async move {
+            TcpServer::instance()
+                .on_clipboard(&session_key, &origin, selection, message)
+                .await;
+        });
+    }
+
     pub async fn shutdown(&self) -> Result<()> {
         self.service_control.stop().await?;
         Ok(())
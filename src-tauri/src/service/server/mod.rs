@@ -1,4 +1,5 @@
 pub mod listener;
 pub mod mdns;
+pub mod router;
 pub mod session;
 pub mod tcp;
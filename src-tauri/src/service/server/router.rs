@@ -0,0 +1,738 @@
+use std::sync::{
+    Arc, OnceLock,
+    atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Result, anyhow, bail};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, error, info, warn};
+use tauri::Emitter;
+use tokio::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::service::{
+    clipboard::sync::ClipboardSync,
+    input::{
+        capture,
+        cursor_lock::{FocusedWindow, LockReason, focus_probe},
+        hotkey::{HotkeyAction, HotkeyEngine, HotkeyOutcome},
+        inject::injector,
+        tracker::InputTracker,
+        typing,
+    },
+    layout::{self, Layout},
+    protocols::{
+        base::{DisplayInfo, PacketData},
+        clipboard::ClipType,
+        input::{KeyModifiers, Keyboard, Mouse},
+    },
+    server::tcp::TcpServer,
+    switch::{EdgeSwitcher, SwitchRules},
+};
+use crate::{config, constant, core};
+
+const FOCUS_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+// 输入文本前等待按键释放的最长时间与检查间隔
+const TYPING_IDLE_TIMEOUT: Duration = Duration::from_secs(3);
+const TYPING_IDLE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// 键盘和鼠标的当前目标
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Target {
+    /// 本机
+    Local,
+    /// 已连接的设备，值为设备 id
+    Remote(String),
+}
+
+/// 切换原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwitchReason {
+    Edge,
+    Hotkey,
+    Api,
+    // 目标设备断开，自动回到本机
+    Disconnected,
+}
+
+/// 目标切换事件
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetChanged {
+    pub from: Target,
+    pub to: Target,
+    pub reason: SwitchReason,
+}
+
+struct RouterState {
+    target: Target,
+    // 当前目标的显示器
+    displays: Vec<DisplayInfo>,
+    // 捕获到的输入，用于判断是否有按钮按下
+    tracker: InputTracker,
+    switcher: EdgeSwitcher,
+    hotkeys: HotkeyEngine,
+    // 手动将光标锁定在当前屏幕
+    manual_lock: bool,
+    // 光标在当前目标上的物理坐标
+    cursor: Option<(f64, f64)>,
+    // 上次发起前台窗口查询的时间
+    focus_checked: Option<Instant>,
+    // 已安排的边缘停留检查时间
+    dwell: Option<Instant>,
+}
+
+/// 服务端输入路由，决定捕获的输入发往本机还是某个会话
+///
+/// 路由与切换共用一把锁，切换过程中的输入会等待切换完成
+pub struct InputRouter {
+    state: Mutex<RouterState>,
+    // 当前目标的副本，供同步读取
+    current: RwLock<Target>,
+    // 光标锁定原因，未锁定时为空
+    lock: RwLock<Option<LockReason>>,
+    // 正在进行的文本输入的取消标记
+    typing: RwLock<Option<Arc<AtomicBool>>>,
+    // 最近一次查询到的前台窗口，由后台查询更新
+    focus: RwLock<Option<FocusedWindow>>,
+    // 是否有正在进行的前台窗口查询
+    focus_pending: AtomicBool,
+}
+
+impl InputRouter {
+    pub fn instance() -> &'static Self {
+        static INSTANCE: OnceLock<InputRouter> = OnceLock::new();
+        INSTANCE.get_or_init(|| InputRouter {
+            state: Mutex::new(RouterState {
+                target: Target::Local,
+                displays: core::display::local_displays(),
+                tracker: InputTracker::new(),
+                switcher: EdgeSwitcher::new(),
+                hotkeys: HotkeyEngine::new(),
+                manual_lock: false,
+                cursor: None,
+                focus_checked: None,
+                dwell: None,
+            }),
+            current: RwLock::new(Target::Local),
+            lock: RwLock::new(None),
+            typing: RwLock::new(None),
+            focus: RwLock::new(None),
+            focus_pending: AtomicBool::new(false),
+        })
+    }
+
+    pub fn target(&self) -> Target {
+        self.current.read().clone()
+    }
+
+    /// 切换目标
+    pub async fn switch_to(
+        &self,
+        target: Target,
+        reason: SwitchReason,
+    ) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.switch(&mut state, target, reason, None)
+    }
+
+    /// 光标锁定原因，未锁定时返回 `None`
+    pub fn cursor_lock(&self) -> Option<LockReason> {
+        self.lock.read().clone()
+    }
+
+    /// 手动锁定或解锁光标
+    pub async fn set_cursor_lock(&self, locked: bool) {
+        let mut state = self.state.lock().await;
+        state.manual_lock = locked;
+        self.update_lock(&mut state, Instant::now());
+    }
+
+    /// 切换手动锁定，返回切换后的锁定原因
+    pub async fn toggle_cursor_lock(&self) -> Option<LockReason> {
+        let mut state = self.state.lock().await;
+        state.manual_lock = !state.manual_lock;
+        self.update_lock(&mut state, Instant::now())
+    }
+
+    /// 将剪贴板文本作为按键输入到当前目标
+    pub async fn type_clipboard(&self) -> Result<()> {
+        let text = ClipboardSync::instance()
+            .items(Some(&[ClipType::Text]))
+            .await
+            .into_iter()
+            .next()
+            .map(|item| String::from_utf8_lossy(&item.data).into_owned())
+            .ok_or_else(|| anyhow!("Clipboard has no text"))?;
+        self.type_text(&text).await
+    }
+
+    /// 将文本作为按键输入到当前目标，输入完成后返回
+    ///
+    /// 先等待捕获的按键全部释放，避免触发热键时按住的修饰键与输入的字符组合。
+    /// 输入期间目标切换、取消或触发紧急热键时停止
+    pub async fn type_text(&self, text: &str) -> Result<()> {
+        let settings = config::clipboard::get_config().typing;
+        let count = text.chars().count();
+        if count > settings.max_chars {
+            bail!(
+                "Text too long to type: {} > {} characters",
+                count,
+                settings.max_chars
+            );
+        }
+        // 进入目标时已将本机锁定键状态同步到目标，捕获后端返回按锁定键时缓存的
+        // 状态，不查询 X 服务器
+        let caps_lock =
+            capture::capture().lock_state().is_some_and(|state| state.caps);
+        let (strokes, skipped) = typing::keystrokes(text, caps_lock);
+        if !skipped.is_empty() {
+            warn!(
+                "Skip {} characters that cannot be typed: {:?}",
+                skipped.len(),
+                skipped.iter().collect::<String>()
+            );
+        }
+        if strokes.is_empty() {
+            bail!("Text has no characters that can be typed");
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        {
+            let mut typing = self.typing.write();
+            if typing.is_some() {
+                bail!("Already typing");
+            }
+            *typing = Some(cancel.clone());
+        }
+        let delay = Duration::from_millis(settings.key_delay_ms as u64);
+        let result = self.type_strokes(strokes, delay, &cancel).await;
+        let mut typing = self.typing.write();
+        if typing.as_ref().is_some_and(|current| Arc::ptr_eq(current, &cancel))
+        {
+            *typing = None;
+        }
+        result
+    }
+
+    /// 取消正在进行的文本输入
+    pub fn cancel_typing(&self) {
+        if let Some(cancel) = self.typing.write().take() {
+            info!("Typing cancelled");
+            cancel.store(true, Ordering::SeqCst);
+        }
+    }
+
+    async fn type_strokes(
+        &self,
+        strokes: Vec<Vec<Keyboard>>,
+        delay: Duration,
+        cancel: &AtomicBool,
+    ) -> Result<()> {
+        let deadline = Instant::now() + TYPING_IDLE_TIMEOUT;
+        while !self.state.lock().await.tracker.is_idle() {
+            if Instant::now() >= deadline {
+                bail!("Keys still held, typing aborted");
+            }
+            tokio::time::sleep(TYPING_IDLE_INTERVAL).await;
+        }
+
+        let target = self.target();
+        info!("Typing {} characters to {:?}", strokes.len(), target);
+        let server = TcpServer::instance();
+        // 每个字符的按下与释放完整发送后才检查是否停止，不会留下按住的键
+        for events in strokes {
+            if cancel.load(Ordering::SeqCst) {
+                bail!("Typing cancelled");
+            }
+            if self.target() != target {
+                bail!("Input target changed while typing");
+            }
+            for event in events {
+                match &target {
+                    Target::Local => injector().key(&event)?,
+                    Target::Remote(device_id) => {
+                        server.send_input(device_id, PacketData::Key(event))?;
+                    }
+                }
+                tokio::time::sleep(delay).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// 路由一个捕获的输入事件
+    ///
+    /// 先匹配热键，热键不会转发到任何设备。返回 `false` 表示事件应由本机处理
+    pub async fn route(&self, data: PacketData) -> bool {
+        let mut state = self.state.lock().await;
+        if let PacketData::Key(event) = &data {
+            let settings = config::hotkey::get_config();
+            let outcome =
+                state.hotkeys.on_key(&settings, event, Instant::now());
+            if outcome != HotkeyOutcome::Pass {
+                self.release_modifiers(&mut state);
+            }
+            match outcome {
+                HotkeyOutcome::Pass => {}
+                HotkeyOutcome::Swallow => return true,
+                HotkeyOutcome::Fire(action) => {
+                    let emergency = action == HotkeyAction::Emergency;
+                    self.run_hotkey(&mut state, action);
+                    if emergency {
+                        // 其他会话与路由状态无关，释放锁后再释放
+                        drop(state);
+                        TcpServer::instance().release_all();
+                    }
+                    return true;
+                }
+            }
+        }
+        state.tracker.track(&data);
+        self.update_lock(&mut state, Instant::now());
+
+        let device_id = match state.target.clone() {
+            Target::Local => {
+                let PacketData::Mouse(Mouse::Move { x, y }) = data else {
+                    return false;
+                };
+                return self.on_cursor(&mut state, x, y);
+            }
+            Target::Remote(device_id) => device_id,
+        };
+        // 发送只放入会话的发送队列，持有路由状态时不等待网络
+        match TcpServer::instance().send_input(&device_id, data) {
+            // 目标设备的光标坐标，相对模式下没有坐标，不进行边缘切换
+            Ok(Some((x, y))) => {
+                self.on_cursor(&mut state, x, y);
+                true
+            }
+            Ok(None) => true,
+            Err(e) => {
+                warn!("Failed to route input to {}: {}", device_id, e);
+                self.fall_back(&mut state, SwitchReason::Disconnected);
+                false
+            }
+        }
+    }
+
+    /// 在当前目标上释放属于热键的修饰键，之后的实际释放不再转发
+    fn release_modifiers(&self, state: &mut RouterState) {
+        for key_code in state.hotkeys.take_released() {
+            let event = Keyboard::release(key_code, KeyModifiers::none());
+            state.tracker.track(&PacketData::Key(event.clone()));
+            let result = match &state.target {
+                Target::Remote(device_id) => TcpServer::instance()
+                    .send_input(device_id, PacketData::Key(event))
+                    .map(|_| ()),
+                Target::Local => injector().key(&event),
+            };
+            if let Err(e) = result {
+                warn!("Failed to release hotkey modifier: {}", e);
+            }
+        }
+    }
+
+    /// 记录光标在当前目标上的物理坐标并检查边缘，切换目标时返回 `true`
+    fn on_cursor(&self, state: &mut RouterState, x: f32, y: f32) -> bool {
+        let (x, y) = (x as f64, y as f64);
+        state.cursor = Some((x, y));
+        self.check_edge(state, x, y)
+    }
+
+    /// 设备断开时调用，若为当前目标则回到本机
+    pub async fn on_device_closed(&self, device_id: &str) {
+        let mut state = self.state.lock().await;
+        if state.target == Target::Remote(device_id.to_string()) {
+            self.fall_back(&mut state, SwitchReason::Disconnected);
+        }
+    }
+
+    fn run_hotkey(&self, state: &mut RouterState, action: HotkeyAction) {
+        info!("Hotkey triggered: {:?}", action);
+        let result = match action {
+            HotkeyAction::SwitchTo(device_id) => {
+                let target = Self::target_of(&device_id);
+                self.switch(state, target, SwitchReason::Hotkey, None)
+            }
+            HotkeyAction::Next => {
+                let target = Self::cycle(&state.target, 1);
+                self.switch(state, target, SwitchReason::Hotkey, None)
+            }
+            HotkeyAction::Previous => {
+                let target = Self::cycle(&state.target, -1);
+                self.switch(state, target, SwitchReason::Hotkey, None)
+            }
+            HotkeyAction::ToggleCursorLock => {
+                state.manual_lock = !state.manual_lock;
+                self.update_lock(state, Instant::now());
+                Ok(())
+            }
+            HotkeyAction::TogglePointerMode => match &state.target {
+                Target::Remote(device_id) => TcpServer::instance()
+                    .toggle_pointer_mode(device_id)
+                    .map(|_| ()),
+                Target::Local => {
+                    Err(anyhow!("Local target has no pointer mode"))
+                }
+            },
+            HotkeyAction::TypeClipboard => {
+                // 输入需要等待热键的修饰键释放，不能持有路由状态
+                tauri::async_runtime::spawn(async {
+                    if let Err(e) =
+                        InputRouter::instance().type_clipboard().await
+                    {
+                        warn!("Failed to type clipboard: {}", e);
+                    }
+                });
+                Ok(())
+            }
+            HotkeyAction::Emergency => {
+                self.cancel_typing();
+                state.manual_lock = false;
+                state.tracker = InputTracker::new();
+                self.update_lock(state, Instant::now());
+                // 其他会话上按下的输入由调用方在释放路由状态后释放
+                self.switch(state, Target::Local, SwitchReason::Hotkey, None)
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to run hotkey: {}", e);
+        }
+    }
+
+    /// 重新计算光标锁定状态，变化时通知托盘与前端
+    fn update_lock(
+        &self,
+        state: &mut RouterState,
+        now: Instant,
+    ) -> Option<LockReason> {
+        let reason = if state.manual_lock {
+            Some(LockReason::Manual)
+        } else if matches!(state.target, Target::Remote(_)) {
+            // 控制远程设备时本机前台窗口不代表光标所在的应用，只按输入状态判断
+            config::layout::get_config()
+                .cursor_lock
+                .evaluate(&state.tracker, None)
+        } else {
+            // 前台窗口查询需要与窗口系统往返，限制查询频率，
+            // 并在后台线程中进行，结果在之后的事件中生效
+            let stale = state.focus_checked.is_none_or(|checked| {
+                now.duration_since(checked) >= FOCUS_CHECK_INTERVAL
+            });
+            if stale && !self.focus_pending.swap(true, Ordering::AcqRel) {
+                state.focus_checked = Some(now);
+                tauri::async_runtime::spawn_blocking(|| {
+                    let router = InputRouter::instance();
+                    *router.focus.write() = focus_probe().focused_window();
+                    router.focus_pending.store(false, Ordering::Release);
+                });
+            }
+            config::layout::get_config()
+                .cursor_lock
+                .evaluate(&state.tracker, self.focus.read().as_ref())
+        };
+
+        if *self.lock.read() != reason {
+            info!("Cursor lock changed: {:?}", reason);
+            *self.lock.write() = reason.clone();
+            core::tray::Tray::instance().set_cursor_lock(reason.is_some());
+            if let Some(app_handle) =
+                core::handle::Handle::instance().app_handle()
+                && let Err(e) =
+                    app_handle.emit(constant::EVENT_CURSOR_LOCK, reason.clone())
+            {
+                error!("Failed to emit cursor lock: {}", e);
+            }
+        }
+        reason
+    }
+
+    /// 本机与已连接设备中相对当前目标偏移 `step` 的目标
+    fn cycle(current: &Target, step: isize) -> Target {
+        let mut targets = vec![Target::Local];
+        targets.extend(
+            TcpServer::instance()
+                .connected_devices()
+                .into_iter()
+                .map(Target::Remote),
+        );
+        let index =
+            targets.iter().position(|target| target == current).unwrap_or(0);
+        let next = (index as isize + step).rem_euclid(targets.len() as isize);
+        targets.swap_remove(next as usize)
+    }
+
+    /// 设备显示器配置变化后调用
+    ///
+    /// 布局坐标按当前显示器换算，边缘随之更新；光标所在显示器消失时移动到最近的显示器
+    pub async fn on_displays_changed(&self, device_id: &str) {
+        let mut state = self.state.lock().await;
+        if Self::device_id(&state.target) != device_id {
+            return;
+        }
+        state.displays = Self::displays(&state.target);
+        let Some((x, y)) = state.cursor.and_then(|(x, y)| {
+            layout::clamp_to_displays(&state.displays, x, y)
+        }) else {
+            return;
+        };
+
+        info!("Cursor moved to ({}, {}) after display change", x, y);
+        state.cursor = Some((x, y));
+        let (x, y) = (x as f32, y as f32);
+        let result = match &state.target {
+            Target::Local => injector().mouse(&Mouse::move_to(x, y)),
+            Target::Remote(device_id) => {
+                TcpServer::instance().warp(device_id, x, y)
+            }
+        };
+        if let Err(e) = result {
+            error!("Failed to move cursor: {}", e);
+        }
+    }
+
+    /// 检查光标是否越过边缘，越过时切换目标并返回 `true`
+    ///
+    /// `x`、`y` 为光标在当前目标上的物理坐标，按当前目标的显示器换算到布局坐标
+    fn check_edge(&self, state: &mut RouterState, x: f64, y: f64) -> bool {
+        if self.lock.read().is_some() {
+            return false;
+        }
+        let layout = config::layout::get_config();
+        let device_id = Self::device_id(&state.target);
+        let Some(screen) = layout.screen(&device_id) else {
+            return false;
+        };
+        let (lx, ly) = screen.from_physical(&state.displays, x, y);
+        // 按住鼠标按钮时始终不切换，与自动锁定规则无关
+        let Some(crossing) = state.switcher.on_move(
+            &layout,
+            &device_id,
+            lx,
+            ly,
+            state.tracker.has_button_pressed(),
+            Instant::now(),
+        ) else {
+            self.arm_dwell(state, &layout.switching);
+            return false;
+        };
+        self.cross(state, &layout, crossing, (x, y))
+    }
+
+    /// 光标停在边缘时安排停留检查，光标不再移动也能在停留时间到达后切换
+    fn arm_dwell(&self, state: &mut RouterState, rules: &SwitchRules) {
+        let Some(deadline) = state.switcher.next_deadline(rules) else {
+            return;
+        };
+        if state.dwell == Some(deadline) {
+            return;
+        }
+        state.dwell = Some(deadline);
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            InputRouter::instance().on_dwell(deadline).await;
+        });
+    }
+
+    /// 停留时间到达，光标仍停在同一边缘时切换
+    async fn on_dwell(&self, deadline: Instant) {
+        let mut state = self.state.lock().await;
+        // 之后又安排了新的检查
+        if state.dwell != Some(deadline) {
+            return;
+        }
+        state.dwell = None;
+        if self.lock.read().is_some() || state.tracker.has_button_pressed() {
+            return;
+        }
+        let Some(cursor) = state.cursor else {
+            return;
+        };
+        let layout = config::layout::get_config();
+        if let Some(crossing) =
+            state.switcher.poll(&layout.switching, Instant::now())
+        {
+            self.cross(&mut state, &layout, crossing, cursor);
+        }
+    }
+
+    /// 越过边缘进入 `crossing` 指向的设备，切换成功时返回 `true`
+    ///
+    /// `(x, y)` 为光标在当前目标上的物理坐标
+    fn cross(
+        &self,
+        state: &mut RouterState,
+        layout: &Layout,
+        crossing: layout::Crossing,
+        (x, y): (f64, f64),
+    ) -> bool {
+        let target = Self::target_of(&crossing.device_id);
+        let displays = Self::displays(&target);
+        // 切换器按相同条件恢复了上次位置
+        let restored = layout.switching.restore_position
+            && state.switcher.position(&crossing.device_id).is_some();
+        let position = Self::entry_position(
+            layout,
+            &state.displays,
+            &displays,
+            &crossing,
+            restored,
+            (x, y),
+        );
+        match self.switch(state, target, SwitchReason::Edge, position) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Failed to switch to {}: {}", crossing.device_id, e);
+                false
+            }
+        }
+    }
+
+    /// 进入目标设备时光标的物理坐标
+    fn entry_position(
+        layout: &Layout,
+        from: &[DisplayInfo],
+        to: &[DisplayInfo],
+        crossing: &layout::Crossing,
+        restored: bool,
+        (x, y): (f64, f64),
+    ) -> Option<(f64, f64)> {
+        let screen = layout.screen(&crossing.device_id)?;
+        // 恢复上次位置时坐标已由切换器给出，否则按显示器物理尺寸映射
+        if !restored
+            && let Some(position) =
+                layout::map_crossing(from, to, crossing.edge.opposite(), x, y)
+        {
+            return Some(position);
+        }
+        // 上次位置可能正在边缘上，离开设备时光标通常停在边缘
+        let (lx, ly) = screen.inset(crossing.x, crossing.y);
+        Some(screen.to_physical(to, lx, ly))
+    }
+
+    fn switch(
+        &self,
+        state: &mut RouterState,
+        target: Target,
+        reason: SwitchReason,
+        position: Option<(f64, f64)>,
+    ) -> Result<()> {
+        if state.target == target {
+            return Ok(());
+        }
+        let server = TcpServer::instance();
+        if let Target::Remote(device_id) = &target
+            && !server.is_connected(device_id)
+        {
+            return Err(anyhow!("Device {} not connected", device_id));
+        }
+
+        // 先释放旧目标上按下的输入，再进入新目标
+        match &state.target {
+            Target::Remote(device_id) => {
+                if let Err(e) = server.leave(device_id) {
+                    error!("Failed to leave {}: {}", device_id, e);
+                }
+            }
+            Target::Local => Self::release_local(&state.tracker),
+        }
+        let position = position.map(|(x, y)| (x as f32, y as f32));
+        match &target {
+            Target::Remote(device_id) => {
+                server.enter(device_id, position)?;
+            }
+            Target::Local => {
+                if let Some((x, y)) = position
+                    && let Err(e) = injector().mouse(&Mouse::move_to(x, y))
+                {
+                    error!("Failed to move local cursor: {}", e);
+                }
+            }
+        }
+
+        let from = std::mem::replace(&mut state.target, target.clone());
+        state.displays = Self::displays(&target);
+        state.cursor = position.map(|(x, y)| (x as f64, y as f64));
+        state.switcher.reset();
+        self.notify(from, target, reason);
+        Ok(())
+    }
+
+    /// 控制权离开本机前，通过注入后端释放本机按下的键和按钮
+    ///
+    /// 独占键鼠后本机应用收不到之后的释放事件，不释放会一直处于按下状态
+    fn release_local(tracker: &InputTracker) {
+        let injector = injector();
+        for data in tracker.clone().release_all() {
+            let result = match &data {
+                PacketData::Key(event) => injector.key(event),
+                PacketData::Mouse(event) => injector.mouse(event),
+                _ => continue,
+            };
+            if let Err(e) = result {
+                error!("Failed to release local input: {}", e);
+            }
+        }
+    }
+
+    /// 目标已不可用，直接回到本机
+    fn fall_back(&self, state: &mut RouterState, reason: SwitchReason) {
+        let from = std::mem::replace(&mut state.target, Target::Local);
+        state.displays = Self::displays(&Target::Local);
+        state.cursor = None;
+        state.switcher.reset();
+        self.notify(from, Target::Local, reason);
+    }
+
+    fn notify(&self, from: Target, to: Target, reason: SwitchReason) {
+        info!(
+            "Input target switched from {:?} to {:?} ({:?})",
+            from, to, reason
+        );
+        *self.current.write() = to.clone();
+        // 控制远端设备时独占本机键鼠
+        if let Err(e) = capture::capture().grab(to != Target::Local) {
+            error!("Failed to grab input: {}", e);
+        }
+        if let Some(app_handle) = core::handle::Handle::instance().app_handle()
+            && let Err(e) = app_handle.emit(
+                constant::EVENT_TARGET_CHANGED,
+                TargetChanged { from, to, reason },
+            )
+        {
+            error!("Failed to emit target changed: {}", e);
+        }
+    }
+
+    fn displays(target: &Target) -> Vec<DisplayInfo> {
+        match target {
+            Target::Local => core::display::local_displays(),
+            Target::Remote(device_id) => TcpServer::instance()
+                .device_info(device_id)
+                .map(|info| info.displays)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn target_of(device_id: &str) -> Target {
+        if device_id == Self::local_device_id() {
+            Target::Local
+        } else {
+            Target::Remote(device_id.to_string())
+        }
+    }
+
+    fn device_id(target: &Target) -> String {
+        match target {
+            Target::Local => Self::local_device_id(),
+            Target::Remote(device_id) => device_id.clone(),
+        }
+    }
+
+    fn local_device_id() -> String {
+        config::system::config().unwrap_or_default().id()
+    }
+}
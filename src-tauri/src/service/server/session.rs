@@ -1,39 +1,331 @@
-use crate::service::{codec::DataPacketWriter, protocols::base::DeviceInfo};
+use std::sync::Arc;
+
+use crate::config;
+use crate::service::input::{
+    capture,
+    pointer::{PointerMode, PointerState},
+    remap::KeyRemapper,
+    tracker::InputTracker,
+    tuning::TuningState,
+};
+use crate::service::protocols::{
+    base::PacketData,
+    input::{Keyboard, Mouse, RepeatPolicy},
+};
+use crate::service::{
+    self,
+    codec::DataPacketWriter,
+    protocols::base::{DeviceInfo, DisplayInfo},
+    sender::PacketSender,
+};
 use crate::service::{
     protocols::base::DataPacket, server::listener::ServerListener,
 };
-use futures_util::SinkExt;
+use spdlog::{error, info};
+use tokio::task::JoinHandle;
 
+/// 一条客户端连接的会话
+///
+/// 发送只放入连接的发送队列，所有方法都不等待网络，可以在持有会话表的引用时调用
 pub struct SessionContext {
     device_info: Option<DeviceInfo>,
-    server_listener: ServerListener,
-    writer: DataPacketWriter,
+    server_listener: Arc<ServerListener>,
+    sender: PacketSender,
+    writer_task: Option<JoinHandle<()>>,
+    remapper: KeyRemapper,
+    tracker: InputTracker,
+    repeat: RepeatPolicy,
+    // 目标端是否已收到当前的自动重复策略
+    repeat_synced: bool,
+    pointer: PointerState,
+    tuning: TuningState,
 }
 
 impl SessionContext {
     pub fn new(
         writer: DataPacketWriter,
-        server_listener: ServerListener,
+        server_listener: Arc<ServerListener>,
     ) -> Self {
-        SessionContext { device_info: None, writer, server_listener }
+        let (sender, writer_task) = PacketSender::spawn(writer);
+        SessionContext {
+            device_info: None,
+            sender,
+            writer_task: Some(writer_task),
+            server_listener,
+            remapper: KeyRemapper::default(),
+            tracker: InputTracker::new(),
+            repeat: RepeatPolicy::default(),
+            repeat_synced: false,
+            pointer: PointerState::default(),
+            tuning: TuningState::default(),
+        }
     }
 
     pub fn device_info(&self) -> Option<&DeviceInfo> {
         self.device_info.as_ref()
     }
 
+    /// 对端支持的能力，未完成握手时为空
+    pub fn caps(&self) -> &[String] {
+        self.device_info.as_ref().map_or(&[], |info| &info.caps)
+    }
+
     pub fn set_device_info(&mut self, device_info: DeviceInfo) {
+        self.tuning.set_displays(&device_info.displays);
         self.device_info = Some(device_info);
+        self.reload_settings();
+    }
+
+    /// 处理对端的握手，记录设备信息并回复本机设备信息
+    pub fn init(&mut self, device_info: DeviceInfo) -> anyhow::Result<()> {
+        info!(
+            "Device {} ({}) joined with {} display(s)",
+            device_info.name,
+            device_info.id,
+            device_info.displays.len()
+        );
+        self.set_device_info(device_info);
+        let data = PacketData::Init(service::local_device_info());
+        self.send(DataPacket::new(Self::local_device_id(), data))?;
+        self.push_managed_config()
+    }
+
+    /// 推送该设备的集中管理配置，未完成握手时忽略
+    pub fn push_managed_config(&self) -> anyhow::Result<()> {
+        let Some(device_info) = &self.device_info else {
+            return Ok(());
+        };
+        let data =
+            PacketData::Managed(config::sync::managed_config(&device_info.id)?);
+        self.send(DataPacket::new(Self::local_device_id(), data))
+    }
+
+    /// 重新加载设备设置，无需重连即可生效
+    pub fn reload_settings(&mut self) {
+        if let Some(device_info) = &self.device_info {
+            let settings = config::device::get_config(&device_info.id);
+            self.remapper.set_rules(settings.effective_remap(device_info));
+            self.tuning.set_tuning(settings.pointer());
+            if self.repeat != settings.repeat() {
+                self.repeat = settings.repeat();
+                self.repeat_synced = false;
+            }
+        }
+    }
+
+    pub fn send(&self, data: DataPacket) -> anyhow::Result<()> {
+        self.sender.send(data)
+    }
+
+    /// 发送文件数据等大量数据，控制消息优先
+    pub fn send_bulk(&self, data: DataPacket) -> anyhow::Result<()> {
+        self.sender.send_bulk(data)
+    }
+
+    /// 连接的发送端，用于在不持有会话的情况下发送
+    pub fn sender(&self) -> PacketSender {
+        self.sender.clone()
+    }
+
+    /// 发送键盘事件，发送前应用该设备的按键映射
+    pub fn send_key(&mut self, event: Keyboard) -> anyhow::Result<()> {
+        if !self.repeat_synced {
+            let data = PacketData::Repeat(self.repeat);
+            self.send(DataPacket::new(Self::local_device_id(), data))?;
+            self.repeat_synced = true;
+        }
+
+        let event = self.remapper.apply(event);
+        // 本地重复策略下由目标端生成重复，丢弃发送端的自动重复
+        if let (RepeatPolicy::Local { .. }, Keyboard::KeyPress { key_code, .. }) =
+            (self.repeat, &event)
+            && self.tracker.is_key_pressed(*key_code)
+        {
+            return Ok(());
+        }
+        self.send_input(PacketData::Key(event))
+    }
+
+    /// 发送鼠标事件，按当前指针模式转换后应用该设备的指针调节
+    ///
+    /// 返回发送绝对坐标后目标设备光标的物理坐标
+    pub fn send_mouse(
+        &mut self,
+        event: Mouse,
+    ) -> anyhow::Result<Option<(f32, f32)>> {
+        let Some(event) = self.pointer.apply(event) else {
+            return Ok(None);
+        };
+        let event = self.tuning.apply(event);
+        let position = match event {
+            Mouse::Move { x, y } => Some((x, y)),
+            _ => None,
+        };
+        self.send_input(PacketData::Mouse(event))?;
+        Ok(position)
+    }
+
+    pub fn pointer_mode(&self) -> PointerMode {
+        self.pointer.mode()
     }
 
-    pub async fn send(&mut self, data: DataPacket) -> anyhow::Result<()> {
-        self.writer.send(data).await?;
+    /// 手动设置指针模式
+    pub fn set_pointer_mode(&mut self, mode: PointerMode) {
+        info!("Pointer mode set to {:?}", mode);
+        self.pointer.set_mode(mode);
+        self.tuning.reset();
+    }
+
+    /// 手动切换指针模式，返回切换后的模式
+    pub fn toggle_pointer_mode(&mut self) -> PointerMode {
+        let mode = self.pointer.toggle();
+        self.tuning.reset();
+        info!("Pointer mode toggled to {:?}", mode);
+        mode
+    }
+
+    /// 目标端回报指针锁定状态时自动切换指针模式
+    pub fn on_pointer_lock(&mut self, locked: bool) {
+        let mode =
+            if locked { PointerMode::Relative } else { PointerMode::Absolute };
+        if self.pointer.mode() != mode {
+            info!("Target pointer lock {}, switching to {:?}", locked, mode);
+            self.pointer.set_mode(mode);
+            self.tuning.reset();
+        }
+    }
+
+    /// 控制权进入该设备时调用，将本机锁定键状态同步到目标设备
+    ///
+    /// 指定 `position` 时将目标设备的光标移动到该物理坐标
+    pub fn enter(
+        &mut self,
+        position: Option<(f32, f32)>,
+    ) -> anyhow::Result<()> {
+        if let Some(state) = capture::capture().lock_state() {
+            let data = PacketData::Locks(state);
+            self.send(DataPacket::new(Self::local_device_id(), data))?;
+        }
+        match position {
+            Some((x, y)) => self.warp(x, y),
+            None => {
+                self.pointer.reset();
+                self.tuning.reset();
+                Ok(())
+            }
+        }
+    }
+
+    /// 将目标设备的光标直接移动到物理坐标，不经过指针调节
+    ///
+    /// 相对模式下光标由目标应用控制，不移动
+    pub fn warp(&mut self, x: f32, y: f32) -> anyhow::Result<()> {
+        self.pointer.reset();
+        self.tuning.warp(x, y);
+        if self.pointer.mode() == PointerMode::Absolute {
+            self.send_input(PacketData::Mouse(Mouse::move_to(x, y)))?;
+        }
+        Ok(())
+    }
+
+    /// 对端显示器配置变化，返回原来的显示器，未完成握手时忽略
+    pub fn set_displays(
+        &mut self,
+        displays: Vec<DisplayInfo>,
+    ) -> Option<Vec<DisplayInfo>> {
+        let device_info = self.device_info.as_mut()?;
+        info!(
+            "Device {} now has {} display(s)",
+            device_info.id,
+            displays.len()
+        );
+        self.tuning.set_displays(&displays);
+        Some(std::mem::replace(&mut device_info.displays, displays))
+    }
+
+    /// 释放目标设备上所有仍处于按下状态的键和按钮
+    ///
+    /// 在控制权离开该设备或会话结束前调用
+    pub fn release_all(&mut self) -> anyhow::Result<()> {
+        self.remapper.reset();
+        for data in self.tracker.release_all() {
+            self.send(DataPacket::new(Self::local_device_id(), data))?;
+        }
         Ok(())
     }
 
+    fn send_input(&mut self, data: PacketData) -> anyhow::Result<()> {
+        self.tracker.track(&data);
+        self.send(DataPacket::new(Self::local_device_id(), data))
+    }
+
+    fn local_device_id() -> String {
+        config::system::config().unwrap_or_default().id()
+    }
+
+    /// 对端已断开，关闭发送队列
+    pub fn close(&self) {
+        self.sender.close();
+    }
+
+    /// 释放目标设备上按下的输入，发送完队列中的数据后关闭连接
     pub async fn shutdown(&mut self) -> anyhow::Result<()> {
-        self.writer.close().await?;
+        if let Err(e) = self.release_all() {
+            error!("Failed to release pressed input: {}", e);
+        }
+        self.sender.close();
+        if let Some(writer_task) = self.writer_task.take() {
+            writer_task.await?;
+        }
         self.server_listener.shutdown().await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    use super::*;
+    use crate::service::{
+        codec::DataPacketCodec, protocols::input::KeyModifiers,
+    };
+
+    #[tokio::test]
+    async fn local_repeat_drops_sender_auto_repeat() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream =
+            TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (peer, _) = listener.accept().await.unwrap();
+        let (writer, _) =
+            Framed::new(stream, DataPacketCodec::default()).split();
+        let mut session =
+            SessionContext::new(writer, Arc::new(ServerListener::new()));
+
+        let press = Keyboard::press(30, KeyModifiers::none(), Some('a'));
+        let release = Keyboard::release(30, KeyModifiers::none());
+        for event in
+            [press.clone(), press.clone(), release.clone(), press.clone()]
+        {
+            session.send_key(event).unwrap();
+        }
+        session.close();
+        session.writer_task.take().unwrap().await.unwrap();
+
+        let received = Framed::new(peer, DataPacketCodec::default())
+            .map(|frame| frame.unwrap().deserialize().unwrap().data)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            received,
+            vec![
+                PacketData::Repeat(RepeatPolicy::default()),
+                PacketData::Key(press.clone()),
+                PacketData::Key(release),
+                PacketData::Key(press),
+            ]
+        );
+    }
+}
@@ -1,14 +1,27 @@
+use std::collections::HashMap;
 use std::sync::{Arc, OnceLock};
 
 use super::session::SessionContext;
+use crate::service::clipboard::{
+    compress,
+    policy::{self, Direction},
+    primary,
+};
 use crate::service::codec::DataPacketCodec;
-use crate::service::server::listener::ServerListener;
-use crate::{constant, service::ServiceControl};
-use anyhow::Result;
-use dashmap::DashMap;
+use crate::service::input::pointer::PointerMode;
+use crate::service::protocols::{
+    base::{DataPacket, DeviceInfo, DisplayInfo, PacketData},
+    clipboard::{Clipboard, Selection},
+    transfer::Transfer,
+};
+use crate::service::server::{listener::ServerListener, router::InputRouter};
+use crate::service::transfer;
+use crate::{config, constant, service::ServiceControl};
+use anyhow::{Result, anyhow};
+use dashmap::{DashMap, mapref::one::RefMut};
 use futures_util::StreamExt;
 use parking_lot::RwLock;
-use spdlog::{error, info};
+use spdlog::{error, info, warn};
 use tokio::{net::TcpListener, select, sync::oneshot};
 use tokio_util::codec::Framed;
 
@@ -34,6 +47,366 @@ impl TcpServer {
         self.sessions.clone()
     }
 
+    /// 设备设置变更后刷新所有会话
+    pub fn reload_device_settings(&self) {
+        for mut session in self.sessions.iter_mut() {
+            session.reload_settings();
+        }
+    }
+
+    /// 对端连接后发来设备信息
+    pub fn on_device_init(&self, session_key: &str, device_info: DeviceInfo) {
+        let Some(mut session) = self.sessions.get_mut(session_key) else {
+            warn!("addr: {} Session not found for init", session_key);
+            return;
+        };
+        if let Err(e) = session.init(device_info) {
+            error!("addr: {} Failed to reply init: {}", session_key, e);
+        }
+    }
+
+    /// 会话结束，移除会话并通知输入路由
+    pub async fn on_session_closed(&self, session_key: &str) {
+        let Some((_, session)) = self.sessions.remove(session_key) else {
+            return;
+        };
+        session.close();
+        if let Some(device_info) = session.device_info() {
+            info!("Device {} disconnected", device_info.id);
+            InputRouter::instance().on_device_closed(&device_info.id).await;
+        }
+    }
+
+    fn session_key(&self, device_id: &str) -> Option<String> {
+        self.sessions
+            .iter()
+            .find(|session| {
+                session.device_info().is_some_and(|info| info.id == device_id)
+            })
+            .map(|session| session.key().clone())
+    }
+
+    /// 已连接并完成握手的设备 id，按 id 排序
+    pub fn connected_devices(&self) -> Vec<String> {
+        let mut devices: Vec<_> = self
+            .sessions
+            .iter()
+            .filter_map(|session| {
+                session.device_info().map(|info| info.id.clone())
+            })
+            .collect();
+        devices.sort();
+        devices
+    }
+
+    /// 释放所有设备上仍处于按下状态的输入
+    pub fn release_all(&self) {
+        for mut session in self.sessions.iter_mut() {
+            if let Err(e) = session.release_all() {
+                error!(
+                    "addr: {} Failed to release input: {}",
+                    session.key(),
+                    e
+                );
+            }
+        }
+    }
+
+    /// 设备是否已连接并完成握手
+    pub fn is_connected(&self, device_id: &str) -> bool {
+        self.session_key(device_id).is_some()
+    }
+
+    /// 向设备发送捕获的键盘或鼠标事件
+    ///
+    /// 返回发送绝对坐标后目标设备光标的物理坐标
+    pub fn send_input(
+        &self,
+        device_id: &str,
+        data: PacketData,
+    ) -> Result<Option<(f32, f32)>> {
+        let mut session = self.session_mut(device_id)?;
+        match data {
+            PacketData::Key(event) => session.send_key(event).map(|_| None),
+            PacketData::Mouse(event) => session.send_mouse(event),
+            other => Err(anyhow!("Not an input event: {:?}", other)),
+        }
+    }
+
+    /// 控制权进入设备
+    pub fn enter(
+        &self,
+        device_id: &str,
+        position: Option<(f32, f32)>,
+    ) -> Result<()> {
+        self.session_mut(device_id)?.enter(position)
+    }
+
+    /// 将设备的光标移动到物理坐标
+    pub fn warp(&self, device_id: &str, x: f32, y: f32) -> Result<()> {
+        self.session_mut(device_id)?.warp(x, y)
+    }
+
+    /// 对端发来新的显示器列表，按新的显示器调整布局后更新输入路由
+    pub async fn on_displays(
+        &self,
+        session_key: &str,
+        displays: Vec<DisplayInfo>,
+    ) {
+        let (device_id, previous) = {
+            let Some(mut session) = self.sessions.get_mut(session_key) else {
+                return;
+            };
+            let Some(previous) = session.set_displays(displays.clone()) else {
+                return;
+            };
+            let Some(device_info) = session.device_info() else {
+                return;
+            };
+            (device_info.id.clone(), previous)
+        };
+        if let Err(e) = config::layout::resize(&device_id, &previous, &displays)
+        {
+            warn!("Failed to resize screen of {}: {}", device_id, e);
+        }
+        InputRouter::instance().on_displays_changed(&device_id).await;
+    }
+
+    /// 设备 `origin` 发来剪贴板消息，写入本机后转发给其他设备
+    pub async fn on_clipboard(
+        &self,
+        session_key: &str,
+        origin: &str,
+        selection: Selection,
+        message: Clipboard,
+    ) {
+        let message = match compress::decompress_message(message).await {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("addr: {} Drop clipboard: {}", session_key, e);
+                return;
+            }
+        };
+        let Some(message) =
+            policy::filter(origin, Direction::Receive, &message)
+        else {
+            return;
+        };
+        let handled = primary::handle(selection, origin, message).await;
+        if let Some(reply) = handled.reply {
+            let device_id = config::system::config().unwrap_or_default().id();
+            self.send_clipboard(session_key, &device_id, selection, &reply)
+                .await;
+        }
+        if let Some(relay) = handled.relay {
+            // 保留来源设备 id，接收方据此处理冲突
+            self.broadcast_clipboard(
+                origin,
+                selection,
+                &relay,
+                Some(session_key),
+            )
+            .await;
+        }
+    }
+
+    /// 向会话发送剪贴板消息，按对端支持的算法压缩
+    pub async fn send_clipboard(
+        &self,
+        session_key: &str,
+        origin: &str,
+        selection: Selection,
+        message: &Clipboard,
+    ) {
+        let Some((sender, caps, device_id)) =
+            self.sessions.get(session_key).map(|session| {
+                let device_id =
+                    session.device_info().map(|info| info.id.clone());
+                (session.sender(), session.caps().to_vec(), device_id)
+            })
+        else {
+            return;
+        };
+        if !primary::supported_by(&caps, selection) {
+            return;
+        }
+        let Some(message) = policy::filter(
+            &device_id.unwrap_or_default(),
+            Direction::Send,
+            message,
+        ) else {
+            return;
+        };
+        let algorithm = compress::negotiate(&caps);
+        let message = match compress::compress_message(message, algorithm).await
+        {
+            Ok(message) => message,
+            Err(e) => {
+                error!(
+                    "addr: {} Failed to compress clipboard: {}",
+                    session_key, e
+                );
+                return;
+            }
+        };
+        let packet =
+            DataPacket::new(origin, PacketData::clipboard(selection, message));
+        if let Err(e) = sender.send(packet) {
+            error!("addr: {} Failed to send clipboard: {}", session_key, e);
+        }
+    }
+
+    /// 向设备 `device_id` 发送剪贴板消息
+    pub async fn send_clipboard_to(
+        &self,
+        device_id: &str,
+        origin: &str,
+        selection: Selection,
+        message: &Clipboard,
+    ) -> Result<()> {
+        let session_key = self
+            .session_key(device_id)
+            .ok_or_else(|| anyhow!("Device {} not connected", device_id))?;
+        self.send_clipboard(&session_key, origin, selection, message).await;
+        Ok(())
+    }
+
+    /// 向除 `exclude` 外的所有会话发送剪贴板消息，同一算法只压缩一次
+    ///
+    /// 先取出各会话的发送端，压缩时不持有会话表的引用
+    pub async fn broadcast_clipboard(
+        &self,
+        origin: &str,
+        selection: Selection,
+        message: &Clipboard,
+        exclude: Option<&str>,
+    ) {
+        let mut compressed = HashMap::new();
+        for (session_key, sender, caps, device_id) in self
+            .sessions
+            .iter()
+            .filter(|session| exclude != Some(session.key().as_str()))
+            .map(|session| {
+                let device_id =
+                    session.device_info().map(|info| info.id.clone());
+                let caps = session.caps().to_vec();
+                (session.key().clone(), session.sender(), caps, device_id)
+            })
+            .collect::<Vec<_>>()
+        {
+            if !primary::supported_by(&caps, selection) {
+                continue;
+            }
+            let Some(filtered) = policy::filter(
+                &device_id.unwrap_or_default(),
+                Direction::Send,
+                message,
+            ) else {
+                continue;
+            };
+            let algorithm = compress::negotiate(&caps);
+            // 历史消息按设备逐条过滤，与原消息不同时单独压缩，不缓存
+            let shared = filtered == *message;
+            let result = match compressed.get(&algorithm) {
+                Some(message) if shared => Ok(Clipboard::clone(message)),
+                _ => compress::compress_message(filtered, algorithm).await,
+            };
+            let message = match result {
+                Ok(message) => message,
+                Err(e) => {
+                    error!(
+                        "addr: {} Failed to compress clipboard: {}",
+                        session_key, e
+                    );
+                    continue;
+                }
+            };
+            if shared {
+                compressed.entry(algorithm).or_insert_with(|| message.clone());
+            }
+            let packet = DataPacket::new(
+                origin,
+                PacketData::clipboard(selection, message),
+            );
+            if let Err(e) = sender.send(packet) {
+                error!("addr: {} Failed to send clipboard: {}", session_key, e);
+            }
+        }
+    }
+
+    /// 向设备 `device_id` 发送来自设备 `origin` 的文件传输消息
+    pub async fn send_transfer(
+        &self,
+        device_id: &str,
+        origin: &str,
+        message: Transfer,
+    ) -> Result<()> {
+        let session = self.session_mut(device_id)?;
+        if !session.caps().iter().any(|cap| cap == transfer::CAP_FILES) {
+            return Err(anyhow!(
+                "{} does not support file transfer",
+                device_id
+            ));
+        }
+        let bulk = matches!(message, Transfer::Chunk { .. });
+        let data = PacketData::File { to: device_id.to_string(), msg: message };
+        let packet = DataPacket::new(origin, data);
+        if bulk { session.send_bulk(packet) } else { session.send(packet) }
+    }
+
+    /// 向所有已完成握手的设备推送集中管理的配置
+    pub fn push_managed_config(&self) {
+        for session in self.sessions.iter() {
+            if let Err(e) = session.push_managed_config() {
+                error!("addr: {} Failed to push config: {}", session.key(), e);
+            }
+        }
+    }
+
+    /// 向所有已连接设备发送数据
+    pub fn broadcast(&self, data: PacketData) {
+        let device_id = config::system::config().unwrap_or_default().id();
+        for session in self.sessions.iter() {
+            let packet = DataPacket::new(device_id.clone(), data.clone());
+            if let Err(e) = session.send(packet) {
+                error!("addr: {} Failed to broadcast: {}", session.key(), e);
+            }
+        }
+    }
+
+    /// 控制权离开设备，释放仍处于按下状态的输入
+    pub fn leave(&self, device_id: &str) -> Result<()> {
+        self.session_mut(device_id)?.release_all()
+    }
+
+    fn session_mut(
+        &self,
+        device_id: &str,
+    ) -> Result<RefMut<'_, String, SessionContext>> {
+        self.session_key(device_id)
+            .and_then(|key| self.sessions.get_mut(&key))
+            .ok_or_else(|| anyhow!("Device {} not connected", device_id))
+    }
+
+    /// 已连接设备的信息
+    pub fn device_info(&self, device_id: &str) -> Option<DeviceInfo> {
+        self.sessions.iter().find_map(|session| {
+            session.device_info().filter(|info| info.id == device_id).cloned()
+        })
+    }
+
+    /// 目标设备回报指针锁定状态
+    pub fn on_pointer_lock(&self, device_id: &str, locked: bool) {
+        if let Ok(mut session) = self.session_mut(device_id) {
+            session.on_pointer_lock(locked);
+        }
+    }
+
+    /// 手动切换设备的指针模式，返回切换后的模式
+    pub fn toggle_pointer_mode(&self, device_id: &str) -> Result<PointerMode> {
+        Ok(self.session_mut(device_id)?.toggle_pointer_mode())
+    }
+
     /// 是否正在运行
     fn is_running(&self) -> bool {
         self.service_control.is_running()
@@ -94,14 +467,16 @@ impl TcpServer {
 
                                     let framed = Framed::new(stream, DataPacketCodec::default());
                                     let (writer, reader) = framed.split();
-                                    let listener = ServerListener::new();
-                                    if let Err(e) = listener.start(reader).await {
+                                    let session_key = addr.to_string();
+                                    let server_listener = Arc::new(ServerListener::new());
+                                    let session_context = SessionContext::new(writer, server_listener.clone());
+                                    // 先登记会话再开始接收，保证握手时能找到会话；接收不持有会话表的引用
+                                    let sessions = Self::instance().sessions();
+                                    sessions.insert(session_key.clone(), session_context);
+                                    if let Err(e) = server_listener.start(reader, session_key.clone()).await {
                                         error!("Failed to start listener: {}", e);
-                                        drop(writer);
-                                        continue;
+                                        sessions.remove(&session_key);
                                     }
-                                    let session_context = SessionContext::new(writer, listener);
-                                    Self::instance().sessions().insert(addr.to_string(), session_context);
 
                             }
                                 Err(e) => {
@@ -123,21 +498,25 @@ impl TcpServer {
 
     // Stop server
     pub async fn stop(&self) -> Result<()> {
-        // 停止所有会话
-        for session_key in
-            self.sessions().iter().map(|s| s.key().clone()).collect::<Vec<_>>()
-        {
-            if let Some(mut session) = self.sessions().get_mut(&session_key) {
-                let r = session.shutdown().await;
-                if r.is_err() {
-                    error!(
-                        "addr: {} Failed to shutdown session: {}",
-                        session_key,
-                        r.unwrap_err()
-                    );
-                }
+        // 先停止接受新连接，再移出并停止所有会话
+        let result = self.service_control.stop().await;
+        let session_keys: Vec<_> =
+            self.sessions.iter().map(|s| s.key().clone()).collect();
+        for session_key in session_keys {
+            let Some((_, mut session)) = self.sessions.remove(&session_key)
+            else {
+                continue;
+            };
+            if let Err(e) = session.shutdown().await {
+                error!(
+                    "addr: {} Failed to shutdown session: {}",
+                    session_key, e
+                );
+            }
+            if let Some(device_info) = session.device_info() {
+                InputRouter::instance().on_device_closed(&device_info.id).await;
             }
         }
-        self.service_control.stop().await
+        result
     }
 }
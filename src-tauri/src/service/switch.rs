@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::{Duration, Instant};
+
+use crate::service::layout::{Crossing, Edge, Layout};
+
+/// 边缘切换规则
+///
+/// 同时启用停留与双击时满足任一条件即可切换，均未启用时碰到边缘立即切换
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SwitchRules {
+    // 角落死区大小，死区内的边缘不触发切换
+    pub corner_size: f64,
+    // 需要在边缘停留的时间，为 0 时不要求停留
+    pub dwell_ms: u32,
+    // 需要连续两次碰到同一边缘
+    pub double_tap: bool,
+    // 两次碰到边缘的最大间隔
+    pub double_tap_ms: u32,
+    // 回到设备时恢复光标上次所在位置
+    pub restore_position: bool,
+    // 最外侧边缘环绕到反方向最远的设备
+    pub wrap: bool,
+}
+
+impl Default for SwitchRules {
+    fn default() -> Self {
+        Self {
+            corner_size: 20.0,
+            dwell_ms: 0,
+            double_tap: false,
+            double_tap_ms: 400,
+            restore_position: true,
+            wrap: false,
+        }
+    }
+}
+
+/// 光标正停在边缘
+#[derive(Debug)]
+struct EdgeContact {
+    edge: Edge,
+    since: Instant,
+    crossing: Crossing,
+}
+
+/// 按切换规则判断光标是否应切换到相邻设备
+///
+/// 坐标均为设备内的布局坐标，同时记录光标在每台设备上最后的位置
+#[derive(Debug, Default)]
+pub struct EdgeSwitcher {
+    contact: Option<EdgeContact>,
+    // 上一次碰到边缘的时间
+    last_tap: Option<(Edge, Instant)>,
+    positions: HashMap<String, (f64, f64)>,
+}
+
+impl EdgeSwitcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 清除边缘状态，控制权切换后调用
+    pub fn reset(&mut self) {
+        self.contact = None;
+        self.last_tap = None;
+    }
+
+    /// 光标在设备上最后的位置
+    pub fn position(&self, device_id: &str) -> Option<(f64, f64)> {
+        self.positions.get(device_id).copied()
+    }
+
+    /// 处理光标在设备 `device_id` 上的新位置，满足规则时返回切换目标
+    ///
+    /// 按住鼠标按钮时不切换，避免拖动窗口时误切换
+    pub fn on_move(
+        &mut self,
+        layout: &Layout,
+        device_id: &str,
+        x: f64,
+        y: f64,
+        button_held: bool,
+        now: Instant,
+    ) -> Option<Crossing> {
+        self.positions.insert(device_id.to_string(), (x, y));
+        if button_held {
+            self.reset();
+            return None;
+        }
+
+        let rules = &layout.switching;
+        let Some(crossing) = Self::target(layout, device_id, x, y) else {
+            // 双击间隔从离开边缘时算起，第一次停留的时长不计入
+            if let Some(contact) = self.contact.take() {
+                self.last_tap = Some((contact.edge, now));
+            }
+            return None;
+        };
+
+        let edge = crossing.edge.opposite();
+        match &mut self.contact {
+            Some(contact) if contact.edge == edge => {
+                contact.crossing = crossing
+            }
+            _ => {
+                let window = Duration::from_millis(rules.double_tap_ms as u64);
+                let tapped = self.last_tap.take().is_some_and(|(last, at)| {
+                    last == edge && now.duration_since(at) <= window
+                });
+                if (rules.double_tap && tapped)
+                    || (!rules.double_tap && rules.dwell_ms == 0)
+                {
+                    return Some(self.finish(rules, crossing));
+                }
+                self.contact = Some(EdgeContact { edge, since: now, crossing });
+            }
+        }
+        self.poll(rules, now)
+    }
+
+    /// 下一次需要调用 [`EdgeSwitcher::poll`] 的时间
+    pub fn next_deadline(&self, rules: &SwitchRules) -> Option<Instant> {
+        if rules.dwell_ms == 0 {
+            return None;
+        }
+        self.contact.as_ref().map(|contact| {
+            contact.since + Duration::from_millis(rules.dwell_ms as u64)
+        })
+    }
+
+    /// 光标在边缘停留足够久时返回切换目标
+    pub fn poll(
+        &mut self,
+        rules: &SwitchRules,
+        now: Instant,
+    ) -> Option<Crossing> {
+        let deadline = self.next_deadline(rules)?;
+        if now < deadline {
+            return None;
+        }
+        let contact = self.contact.take()?;
+        Some(self.finish(rules, contact.crossing))
+    }
+
+    fn finish(
+        &mut self,
+        rules: &SwitchRules,
+        mut crossing: Crossing,
+    ) -> Crossing {
+        self.reset();
+        if rules.restore_position
+            && let Some((x, y)) = self.position(&crossing.device_id)
+        {
+            crossing.x = x;
+            crossing.y = y;
+        }
+        crossing
+    }
+
+    /// 当前位置所在边缘对应的切换目标，角落死区内返回 `None`
+    fn target(
+        layout: &Layout,
+        device_id: &str,
+        x: f64,
+        y: f64,
+    ) -> Option<Crossing> {
+        let screen = layout.screen(device_id)?;
+        let edge = screen.edge_at(x, y)?;
+        let (along, length) = match edge {
+            Edge::Left | Edge::Right => (y, screen.height),
+            Edge::Top | Edge::Bottom => (x, screen.width),
+        };
+        let corner = layout.switching.corner_size;
+        if along < corner || along > length - 1.0 - corner {
+            return None;
+        }
+        layout.cross(device_id, edge, x, y).or_else(|| {
+            layout
+                .switching
+                .wrap
+                .then(|| layout.wrap(device_id, edge, x, y))
+                .flatten()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::layout::Screen;
+
+    fn layout(switching: SwitchRules) -> Layout {
+        let screen = |device_id: &str, x: f64, width: f64| Screen {
+            device_id: device_id.into(),
+            x,
+            y: 0.0,
+            width,
+            height: 1080.0,
+        };
+        Layout {
+            screens: vec![
+                screen("a", 0.0, 1920.0),
+                screen("b", 1920.0, 1280.0),
+            ],
+            switching,
+            ..Default::default()
+        }
+    }
+
+    fn rules() -> SwitchRules {
+        SwitchRules { restore_position: false, ..Default::default() }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn corners_are_dead_zones() {
+        let layout = layout(rules());
+        let mut switcher = EdgeSwitcher::new();
+        let now = Instant::now();
+        let mut at = |y| switcher.on_move(&layout, "a", 1919.0, y, false, now);
+
+        assert_eq!(at(19.0), None);
+        assert_eq!(at(1060.0), None);
+        let crossing = at(20.0).unwrap();
+        assert_eq!(
+            (crossing.device_id.as_str(), crossing.edge),
+            ("b", Edge::Left)
+        );
+        assert!(at(1059.0).is_some());
+    }
+
+    #[test]
+    fn dwell_fires_through_poll_without_moving() {
+        let layout = layout(SwitchRules { dwell_ms: 100, ..rules() });
+        let mut switcher = EdgeSwitcher::new();
+        let start = Instant::now();
+
+        assert_eq!(
+            switcher.on_move(&layout, "a", 1919.0, 500.0, false, start),
+            None
+        );
+        assert_eq!(
+            switcher.next_deadline(&layout.switching),
+            Some(start + ms(100))
+        );
+        assert_eq!(switcher.poll(&layout.switching, start + ms(99)), None);
+        let crossing =
+            switcher.poll(&layout.switching, start + ms(100)).unwrap();
+        assert_eq!(crossing.device_id, "b");
+        assert_eq!(switcher.next_deadline(&layout.switching), None);
+    }
+
+    #[test]
+    fn double_tap_window_starts_when_leaving_the_edge() {
+        let layout = layout(SwitchRules { double_tap: true, ..rules() });
+        let tap = |second: u64| {
+            let mut switcher = EdgeSwitcher::new();
+            let start = Instant::now();
+            let on_move = |switcher: &mut EdgeSwitcher, x, at| {
+                switcher.on_move(&layout, "a", x, 500.0, false, start + ms(at))
+            };
+            assert_eq!(on_move(&mut switcher, 1919.0, 0), None);
+            // 第一次停留超过了双击间隔
+            assert_eq!(on_move(&mut switcher, 1919.0, 1000), None);
+            assert_eq!(on_move(&mut switcher, 1900.0, 1000), None);
+            on_move(&mut switcher, 1919.0, 1000 + second)
+        };
+
+        assert!(tap(400).is_some());
+        assert_eq!(tap(401), None);
+    }
+
+    #[test]
+    fn held_button_blocks_switching() {
+        let layout = layout(SwitchRules { dwell_ms: 100, ..rules() });
+        let mut switcher = EdgeSwitcher::new();
+        let now = Instant::now();
+
+        assert_eq!(
+            switcher.on_move(&layout, "a", 1919.0, 500.0, true, now),
+            None
+        );
+        assert_eq!(switcher.next_deadline(&layout.switching), None);
+        assert_eq!(switcher.poll(&layout.switching, now + ms(100)), None);
+
+        let layout = self::layout(rules());
+        assert_eq!(
+            switcher.on_move(&layout, "a", 1919.0, 500.0, true, now),
+            None
+        );
+        assert!(
+            switcher.on_move(&layout, "a", 1919.0, 500.0, false, now).is_some()
+        );
+    }
+
+    #[test]
+    fn restore_position_returns_to_the_last_position() {
+        let now = Instant::now();
+        let mut switcher = EdgeSwitcher::new();
+        let restore = layout(SwitchRules { restore_position: true, ..rules() });
+        switcher.on_move(&restore, "b", 640.0, 300.0, false, now);
+
+        let crossing =
+            switcher.on_move(&restore, "a", 1919.0, 500.0, false, now).unwrap();
+        assert_eq!((crossing.x, crossing.y), (640.0, 300.0));
+        assert_eq!(switcher.position("a"), Some((1919.0, 500.0)));
+
+        let crossing = switcher
+            .on_move(&layout(rules()), "a", 1919.0, 500.0, false, now)
+            .unwrap();
+        assert_eq!((crossing.x, crossing.y), (1.0, 500.0));
+    }
+
+    #[test]
+    fn wrap_enters_the_farthest_device() {
+        let now = Instant::now();
+        let mut switcher = EdgeSwitcher::new();
+
+        assert_eq!(
+            switcher.on_move(&layout(rules()), "a", 0.0, 500.0, false, now),
+            None
+        );
+        let wrap = layout(SwitchRules { wrap: true, ..rules() });
+        let crossing =
+            switcher.on_move(&wrap, "a", 0.0, 500.0, false, now).unwrap();
+        assert_eq!(
+            (crossing.device_id.as_str(), crossing.edge),
+            ("b", Edge::Right)
+        );
+        assert_eq!((crossing.x, crossing.y), (1278.0, 500.0));
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Result, anyhow, bail};
+use spdlog::{debug, info, warn};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt as _,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    config,
+    service::protocols::transfer::{FileEntry, Transfer},
+};
+
+use super::{ConflictPolicy, FileTransfers, TransferState, manifest, send};
+
+// 对端在此时间内没有响应时重发请求，对端重连后从断点继续
+const STALL_TIMEOUT: Duration = Duration::from_secs(10);
+// 超过此时间没有任何进展时放弃
+const RESUME_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+// 每次允许对端发送的字节数，处理完一半后追加
+const WINDOW: u64 = 1024 * 1024;
+// 未完成的临时目录保留的时间，期间再次接收同一内容时续传
+const STAGING_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// 临时目录名的前缀
+const STAGING_PREFIX: &str = ".transfer-";
+
+/// 发给接收任务的消息
+#[derive(Debug)]
+pub enum Inbound {
+    Manifest(Vec<FileEntry>),
+    Chunk {
+        index: u32,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    /// 对端中止
+    Abort(String),
+    /// 本机取消
+    Cancel,
+}
+
+/// 从设备 `peer` 接收剪贴板内容 `hash` 中的文件，返回移动到下载目录后的路径
+///
+/// 文件先写入下载目录下按对端与内容哈希命名的临时目录，全部接收并校验后再按冲突
+/// 策略移动到下载目录。对端无响应时定期重新请求，连接恢复后从已接收的大小继续；
+/// 对端长时间无响应或读写出错时保留临时目录，之后再次接收同一内容时续传，
+/// 取消、对端中止或内容无效时删除
+pub async fn run(
+    id: u64,
+    peer: String,
+    hash: u64,
+    rx: mpsc::Receiver<Inbound>,
+) -> Result<Vec<PathBuf>> {
+    let dir = FileTransfers::instance().download_dir()?;
+    let staging = dir.join(staging_name(&peer, hash));
+    let mut receiver = Receiver {
+        id,
+        peer,
+        rx,
+        staging: staging.clone(),
+        dir: dir.clone(),
+        progressed: Instant::now(),
+        aborted: false,
+        discard: false,
+    };
+    let result = receiver.receive(hash).await;
+    if let Err(e) = &result
+        && !receiver.aborted
+    {
+        let abort = Transfer::Abort { id, reason: e.to_string() };
+        if let Err(e) = send(&receiver.peer, abort).await {
+            debug!("Failed to abort transfer {:x}: {}", id, e);
+        }
+    }
+    let discard = result.is_ok() || receiver.discard;
+    tokio::task::spawn_blocking(move || {
+        if discard
+            && staging.exists()
+            && let Err(e) = fs::remove_dir_all(&staging)
+        {
+            warn!("Failed to remove {}: {}", staging.display(), e);
+        }
+        prune_staging(&dir);
+    })
+    .await?;
+    result
+}
+
+/// 接收设备 `peer` 剪贴板内容 `hash` 的临时目录名，同一内容总是使用同一目录
+fn staging_name(peer: &str, hash: u64) -> String {
+    format!("{}{:x}-{:x}", STAGING_PREFIX, xxh3_64(peer.as_bytes()), hash)
+}
+
+/// 删除超过保留时间的临时目录
+fn prune_staging(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| {
+                SystemTime::now().duration_since(modified).ok()
+            })
+            .is_some_and(|age| age > STAGING_TTL);
+        if expired
+            && entry.file_name().to_string_lossy().starts_with(STAGING_PREFIX)
+            && let Err(e) = fs::remove_dir_all(entry.path())
+        {
+            warn!("Failed to remove {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+/// 续传的起始位置，已有内容超过条目大小时从头接收
+async fn resume_offset(file: &File, size: u64) -> Result<u64> {
+    let len = file.metadata().await?.len();
+    if len <= size {
+        return Ok(len);
+    }
+    file.set_len(0).await?;
+    Ok(0)
+}
+
+/// 接收方允许对端发送的范围
+#[derive(Debug)]
+struct Window {
+    index: u32,
+    size: u64,
+    // 允许发送到的位置
+    granted: u64,
+}
+
+impl Window {
+    /// 从 `offset` 开始接收大小为 `size` 的第 `index` 个条目
+    fn new(index: u32, offset: u64, size: u64) -> Self {
+        Self { index, size, granted: (offset + WINDOW).min(size) }
+    }
+
+    /// 从 `offset` 开始拉取到已允许的位置，开始接收或重新请求时发送
+    fn pull(&self, id: u64, offset: u64) -> Transfer {
+        Transfer::Pull {
+            id,
+            index: self.index,
+            offset,
+            len: self.granted.saturating_sub(offset),
+        }
+    }
+
+    /// 已接收到 `offset`，剩余额度不足一半时追加额度
+    fn extend(&mut self, id: u64, offset: u64) -> Option<Transfer> {
+        if self.granted >= self.size
+            || self.granted.saturating_sub(offset) > WINDOW / 2
+        {
+            return None;
+        }
+        let granted = (self.granted + WINDOW).min(self.size);
+        let pull = Transfer::Pull {
+            id,
+            index: self.index,
+            offset: self.granted,
+            len: granted - self.granted,
+        };
+        self.granted = granted;
+        Some(pull)
+    }
+}
+
+struct Receiver {
+    id: u64,
+    peer: String,
+    rx: mpsc::Receiver<Inbound>,
+    dir: PathBuf,
+    // 接收中的文件所在的临时目录
+    staging: PathBuf,
+    // 上次收到对端数据的时间
+    progressed: Instant,
+    // 对端已中止，无需再通知对端
+    aborted: bool,
+    // 出错后无需续传，删除临时目录
+    discard: bool,
+}
+
+impl Receiver {
+    async fn receive(&mut self, hash: u64) -> Result<Vec<PathBuf>> {
+        let request = Transfer::Request { id: self.id, hash };
+        self.send(&request).await;
+        let entries = loop {
+            if let Inbound::Manifest(entries) = self.recv(&request).await? {
+                break entries;
+            }
+        };
+
+        let settings = config::clipboard::get_config().files;
+        let total: u64 = entries.iter().map(|entry| entry.size).sum();
+        if total > settings.max_size {
+            self.discard = true;
+            bail!("Files too large: {} > {} bytes", total, settings.max_size);
+        }
+        let paths = entries
+            .iter()
+            .map(|entry| manifest::local_path(&entry.path))
+            .collect::<Result<Vec<_>>>()
+            .inspect_err(|_| self.discard = true)?;
+        info!(
+            "Receiving {} entries ({} bytes) from {}",
+            entries.len(),
+            total,
+            self.peer
+        );
+        FileTransfers::instance().update(self.id, |progress| {
+            progress.state = TransferState::Active;
+            progress.files = entries.iter().filter(|entry| !entry.dir).count();
+            progress.bytes = total;
+        });
+
+        tokio::fs::create_dir_all(&self.staging).await?;
+        let mut base = 0;
+        for (index, (entry, path)) in entries.iter().zip(&paths).enumerate() {
+            let path = self.staging.join(path);
+            if entry.dir {
+                tokio::fs::create_dir_all(&path).await?;
+                continue;
+            }
+            FileTransfers::instance().update(self.id, |progress| {
+                progress.current = Some(entry.path.clone());
+            });
+            self.receive_file(index as u32, entry, &path, base).await?;
+            base += entry.size;
+            FileTransfers::instance().update(self.id, |progress| {
+                progress.files_done += 1;
+            });
+        }
+
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for path in &paths {
+            if let Some(root) = path.iter().next().map(PathBuf::from)
+                && !roots.contains(&root)
+            {
+                roots.push(root);
+            }
+        }
+        let (staging, dir) = (self.staging.clone(), self.dir.clone());
+        let placed = tokio::task::spawn_blocking(move || {
+            roots
+                .iter()
+                .map(|root| place(&staging, &dir, root, settings.conflict))
+                .collect::<Result<Vec<_>>>()
+        })
+        .await??;
+        self.send(&Transfer::Done { id: self.id }).await;
+        Ok(placed)
+    }
+
+    /// 接收第 `index` 个条目，已有部分内容时从其大小继续，校验失败时重新接收一次
+    async fn receive_file(
+        &mut self,
+        index: u32,
+        entry: &FileEntry,
+        path: &Path,
+        base: u64,
+    ) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        for _ in 0..2 {
+            let mut file =
+                OpenOptions::new().create(true).append(true).open(path).await?;
+            let mut offset = resume_offset(&file, entry.size).await?;
+            let mut window = Window::new(index, offset, entry.size);
+            if offset < entry.size {
+                self.send(&window.pull(self.id, offset)).await;
+            }
+            while offset < entry.size {
+                let pull = window.pull(self.id, offset);
+                let Inbound::Chunk { index: i, offset: o, data } =
+                    self.recv(&pull).await?
+                else {
+                    continue;
+                };
+                // 重新请求前已发出的数据块
+                if i != index || o != offset {
+                    continue;
+                }
+                if offset + data.len() as u64 > entry.size {
+                    self.discard = true;
+                    bail!("Unexpected data for {}", entry.path);
+                }
+                file.write_all(&data).await?;
+                offset += data.len() as u64;
+                FileTransfers::instance().update(self.id, |progress| {
+                    progress.bytes_done = base + offset;
+                });
+                if let Some(pull) = window.extend(self.id, offset) {
+                    self.send(&pull).await;
+                }
+            }
+            file.flush().await?;
+            drop(file);
+
+            let file = path.to_path_buf();
+            let checksum =
+                tokio::task::spawn_blocking(move || manifest::checksum(&file))
+                    .await??;
+            if checksum == entry.hash {
+                return Ok(());
+            }
+            warn!("Checksum mismatch for {}, receiving again", entry.path);
+            tokio::fs::remove_file(path).await?;
+        }
+        self.discard = true;
+        bail!("Checksum mismatch for {}", entry.path)
+    }
+
+    /// 发送请求，失败时等待超时后重发
+    async fn send(&self, message: &Transfer) {
+        if let Err(e) = send(&self.peer, message.clone()).await {
+            debug!("Failed to send transfer {:x}: {}", self.id, e);
+        }
+    }
+
+    /// 等待对端的数据，超时未收到时重发 `retry`
+    async fn recv(&mut self, retry: &Transfer) -> Result<Inbound> {
+        loop {
+            match tokio::time::timeout(STALL_TIMEOUT, self.rx.recv()).await {
+                Ok(Some(Inbound::Abort(reason))) => {
+                    self.aborted = true;
+                    self.discard = true;
+                    bail!("Aborted by {}: {}", self.peer, reason);
+                }
+                Ok(Some(Inbound::Cancel)) | Ok(None) => {
+                    self.discard = true;
+                    bail!("Cancelled");
+                }
+                Ok(Some(inbound)) => {
+                    if self.progressed.elapsed() >= STALL_TIMEOUT {
+                        FileTransfers::instance().update(self.id, |progress| {
+                            progress.state = TransferState::Active;
+                        });
+                    }
+                    self.progressed = Instant::now();
+                    return Ok(inbound);
+                }
+                Err(_) if self.progressed.elapsed() >= RESUME_TIMEOUT => {
+                    bail!("{} not responding", self.peer);
+                }
+                Err(_) => {
+                    debug!(
+                        "Transfer {:x} stalled, resend {:?}",
+                        self.id, retry
+                    );
+                    FileTransfers::instance().update(self.id, |progress| {
+                        progress.state = TransferState::Stalled;
+                    });
+                    self.send(retry).await;
+                }
+            }
+        }
+    }
+}
+
+/// 将临时目录中接收完成的文件或目录移动到下载目录，返回最终路径
+fn place(
+    staging: &Path,
+    dir: &Path,
+    root: &Path,
+    conflict: ConflictPolicy,
+) -> Result<PathBuf> {
+    let staged = staging.join(root);
+    let mut target = dir.join(root);
+    if target.symlink_metadata().is_ok() {
+        match conflict {
+            ConflictPolicy::Rename => target = free_name(dir, root)?,
+            ConflictPolicy::Overwrite => {
+                if target.is_dir() {
+                    fs::remove_dir_all(&target)?;
+                } else {
+                    fs::remove_file(&target)?;
+                }
+            }
+            ConflictPolicy::Skip => {
+                info!("Keep existing {}", target.display());
+                return Ok(target);
+            }
+        }
+    }
+    fs::rename(&staged, &target)?;
+    Ok(target)
+}
+
+/// 添加序号直到名称不冲突，如 `name (1).txt`
+fn free_name(dir: &Path, root: &Path) -> Result<PathBuf> {
+    let stem = root
+        .file_stem()
+        .ok_or_else(|| anyhow!("Invalid name {}", root.display()))?
+        .to_string_lossy();
+    let extension = root
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| path.symlink_metadata().is_err())
+        .ok_or_else(|| anyhow!("No free name for {}", root.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_extends_when_half_is_received() {
+        let size = 3 * WINDOW;
+        let mut window = Window::new(2, 0, size);
+
+        assert_eq!(
+            window.pull(1, 0),
+            Transfer::Pull { id: 1, index: 2, offset: 0, len: WINDOW }
+        );
+        assert_eq!(window.extend(1, WINDOW / 4), None);
+        assert_eq!(
+            window.extend(1, WINDOW / 2),
+            Some(Transfer::Pull {
+                id: 1,
+                index: 2,
+                offset: WINDOW,
+                len: WINDOW
+            })
+        );
+        // 重新请求时只拉取到已允许的位置，与对端的额度一致
+        assert_eq!(
+            window.pull(1, WINDOW / 2),
+            Transfer::Pull {
+                id: 1,
+                index: 2,
+                offset: WINDOW / 2,
+                len: WINDOW * 3 / 2
+            }
+        );
+        assert!(window.extend(1, 2 * WINDOW).is_some());
+        assert_eq!(window.extend(1, size), None);
+    }
+
+    #[tokio::test]
+    async fn resume_from_received_size() {
+        let dir = std::env::temp_dir()
+            .join(format!("sync-pointer-resume-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("partial");
+        tokio::fs::write(&path, b"12345").await.unwrap();
+
+        let file = File::options().append(true).open(&path).await.unwrap();
+        assert_eq!(resume_offset(&file, 10).await.unwrap(), 5);
+        assert_eq!(
+            Window::new(0, 5, 10).pull(1, 5),
+            Transfer::Pull { id: 1, index: 0, offset: 5, len: 5 }
+        );
+        // 已有内容比条目大时从头接收
+        assert_eq!(resume_offset(&file, 3).await.unwrap(), 0);
+        assert_eq!(file.metadata().await.unwrap().len(), 0);
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[test]
+    fn staging_is_keyed_by_peer_and_hash() {
+        assert_eq!(staging_name("a", 1), staging_name("a", 1));
+        assert_ne!(staging_name("a", 1), staging_name("b", 1));
+        assert_ne!(staging_name("a", 1), staging_name("a", 2));
+        assert!(staging_name("../a", 1).starts_with(STAGING_PREFIX));
+        assert!(!staging_name("../a", 1).contains('/'));
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    fs::{self, File},
+    io::Read as _,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow, bail};
+use spdlog::warn;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::service::protocols::transfer::FileEntry;
+
+// 计算校验和时每次读取的大小
+const READ_SIZE: usize = 256 * 1024;
+
+/// 为复制的文件与目录生成清单，返回条目与对应的本地路径
+///
+/// 目录递归展开并按名称排序，符号链接不跟随，避免循环与越出复制范围
+pub fn build(roots: &[PathBuf]) -> Result<(Vec<FileEntry>, Vec<PathBuf>)> {
+    let mut entries = Vec::new();
+    let mut paths = Vec::new();
+    for root in roots {
+        let name = root
+            .file_name()
+            .ok_or_else(|| anyhow!("Invalid path {}", root.display()))?
+            .to_string_lossy()
+            .into_owned();
+        walk(root, name, &mut entries, &mut paths)?;
+    }
+    Ok((entries, paths))
+}
+
+fn walk(
+    path: &Path,
+    relative: String,
+    entries: &mut Vec<FileEntry>,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        entries.push(FileEntry {
+            path: relative.clone(),
+            dir: true,
+            size: 0,
+            hash: 0,
+        });
+        paths.push(path.to_path_buf());
+        let mut children = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        children.sort();
+        for child in children {
+            let Some(name) = child.file_name() else {
+                continue;
+            };
+            let relative = format!("{}/{}", relative, name.to_string_lossy());
+            walk(&child, relative, entries, paths)?;
+        }
+    } else if metadata.is_file() {
+        entries.push(FileEntry {
+            path: relative,
+            dir: false,
+            size: metadata.len(),
+            hash: checksum(path)?,
+        });
+        paths.push(path.to_path_buf());
+    } else {
+        warn!("Skip special file {}", path.display());
+    }
+    Ok(())
+}
+
+/// 文件内容的 xxh3 校验和
+pub fn checksum(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0; READ_SIZE];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.digest());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// 将对端的相对路径转为本地路径，拒绝绝对路径与 `..` 等越出目录的路径
+///
+/// 每一段都必须是普通的名称，空段与 `.` 等会被路径忽略的段同样拒绝
+pub fn local_path(relative: &str) -> Result<PathBuf> {
+    let path: PathBuf = relative.split('/').collect();
+    let valid = path.components().count() == relative.split('/').count()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !valid {
+        bail!("Invalid path in manifest: {:?}", relative);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use xxhash_rust::xxh3::xxh3_64;
+
+    use super::*;
+
+    #[test]
+    fn checksum_matches_content_hash() {
+        let dir = std::env::temp_dir()
+            .join(format!("sync-pointer-checksum-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // 超过单次读取的大小，覆盖分段计算
+        let data: Vec<u8> =
+            (0..READ_SIZE * 2 + 7).map(|i| (i % 251) as u8).collect();
+        let path = dir.join("data");
+        fs::write(&path, &data).unwrap();
+
+        assert_eq!(checksum(&path).unwrap(), xxh3_64(&data));
+        let (entries, paths) = build(std::slice::from_ref(&dir)).unwrap();
+        assert!(entries[0].dir);
+        assert_eq!(entries[1].hash, xxh3_64(&data));
+        assert_eq!(paths[1], path);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn local_path_rejects_traversal() {
+        assert_eq!(
+            local_path("dir/file.txt").unwrap(),
+            PathBuf::from("dir").join("file.txt")
+        );
+        for path in
+            ["", "../file", "dir/../../file", "/etc/passwd", "dir/./file"]
+        {
+            assert!(local_path(path).is_err(), "{:?}", path);
+        }
+    }
+}
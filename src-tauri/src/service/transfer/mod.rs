@@ -0,0 +1,502 @@
+pub mod incoming;
+pub mod manifest;
+pub mod outgoing;
+
+use std::{collections::HashMap, path::PathBuf, sync::OnceLock};
+
+use anyhow::{Result, anyhow};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use spdlog::{debug, error, info, warn};
+use tauri::Emitter as _;
+use tokio::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::{self, network::ServiceType},
+    constant, core,
+    service::{
+        client::tcp::TcpClient,
+        clipboard::sync::ClipboardSync,
+        protocols::transfer::{FileEntry, Transfer},
+        server::tcp::TcpServer,
+    },
+};
+
+use incoming::Inbound;
+use outgoing::Outgoing;
+
+/// 支持文件传输的能力，通过 `DeviceInfo::caps` 告知对端
+pub const CAP_FILES: &str = "file-transfer";
+
+// 同一传输两次进度事件的最小间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// 最多保留的已结束传输记录
+const MAX_FINISHED: usize = 50;
+// 接收方超过此时间没有请求时丢弃发送记录
+const OUTGOING_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// 接收的文件与下载目录中已有文件同名时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// 添加序号，如 `name (1).txt`
+    #[default]
+    Rename,
+    /// 替换已有的文件或目录
+    Overwrite,
+    /// 保留已有的文件或目录
+    Skip,
+}
+
+/// 文件传输设置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferSettings {
+    // 接收对端复制的文件
+    pub enabled: bool,
+    // 下载目录，为空时使用系统的下载目录
+    pub download_dir: Option<PathBuf>,
+    pub conflict: ConflictPolicy,
+    // 一次复制的文件总字节数上限，超过时不接收
+    pub max_size: u64,
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            download_dir: None,
+            conflict: ConflictPolicy::default(),
+            max_size: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// 传输方向
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TransferDirection {
+    Send,
+    Receive,
+}
+
+/// 传输状态
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TransferState {
+    /// 等待文件清单
+    Pending,
+    Active,
+    /// 对端无响应，等待重连后续传
+    Stalled,
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl TransferState {
+    fn finished(&self) -> bool {
+        matches!(
+            self,
+            TransferState::Completed
+                | TransferState::Failed(_)
+                | TransferState::Cancelled
+        )
+    }
+}
+
+/// 传输进度，变化时通过 `file-transfer` 事件通知界面
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransferProgress {
+    pub id: u64,
+    // 对端设备 id
+    pub peer: String,
+    pub direction: TransferDirection,
+    pub state: TransferState,
+    // 文件数，不含目录
+    pub files: usize,
+    pub files_done: usize,
+    pub bytes: u64,
+    pub bytes_done: u64,
+    // 正在传输的文件的相对路径
+    pub current: Option<String>,
+    // 接收完成后下载目录中的路径
+    pub paths: Vec<String>,
+}
+
+impl TransferProgress {
+    fn new(id: u64, peer: &str, direction: TransferDirection) -> Self {
+        Self {
+            id,
+            peer: peer.to_string(),
+            direction,
+            state: TransferState::Pending,
+            files: 0,
+            files_done: 0,
+            bytes: 0,
+            bytes_done: 0,
+            current: None,
+            paths: Vec::new(),
+        }
+    }
+}
+
+struct Tracked {
+    progress: TransferProgress,
+    // 上次发出事件的时间
+    emitted: Option<Instant>,
+}
+
+/// 接收中的传输
+struct Receiving {
+    peer: String,
+    hash: u64,
+    tx: mpsc::Sender<Inbound>,
+}
+
+/// 剪贴板中的文件传输
+///
+/// 对端复制文件后，本机收到其剪贴板内容时按哈希请求文件清单，再逐个文件分块拉取，
+/// 每个文件接收完成后校验。连接断开后接收方定期重新请求，对端重新连接后从断点续传。
+/// 全部接收后移动到下载目录，并将本地路径写入剪贴板。
+///
+/// 对端的消息由单独的任务按顺序处理，不阻塞连接；接收方按窗口拉取数据，限制排队的
+/// 数据量。作为客户端时所有消息经由服务端转发。传输进度只保存在内存中，未完成的
+/// 文件保留在临时目录中，再次接收同一内容时续传
+pub struct FileTransfers {
+    // 交给处理任务的消息
+    dispatch: mpsc::UnboundedSender<(String, Transfer)>,
+    incoming: Mutex<HashMap<u64, Receiving>>,
+    // 按接收方设备 id 与传输 id 索引
+    outgoing: Mutex<HashMap<(String, u64), Outgoing>>,
+    // 进度记录，按开始时间排列
+    progress: Mutex<Vec<Tracked>>,
+    // 系统的下载目录
+    default_dir: RwLock<Option<PathBuf>>,
+}
+
+impl FileTransfers {
+    pub fn instance() -> &'static Self {
+        static INSTANCE: OnceLock<FileTransfers> = OnceLock::new();
+        INSTANCE.get_or_init(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tauri::async_runtime::spawn(Self::handle_messages(rx));
+            FileTransfers {
+                dispatch: tx,
+                incoming: Mutex::new(HashMap::new()),
+                outgoing: Mutex::new(HashMap::new()),
+                progress: Mutex::new(Vec::new()),
+                default_dir: RwLock::new(None),
+            }
+        })
+    }
+
+    /// 将设备 `origin` 发来的传输消息交给处理任务，不等待处理完成
+    pub fn dispatch(&self, origin: &str, message: Transfer) {
+        if self.dispatch.send((origin.to_string(), message)).is_err() {
+            error!("File transfer handler stopped");
+        }
+    }
+
+    async fn handle_messages(
+        mut rx: mpsc::UnboundedReceiver<(String, Transfer)>,
+    ) {
+        while let Some((origin, message)) = rx.recv().await {
+            Self::instance().handle(&origin, message).await;
+        }
+    }
+
+    /// 设置系统的下载目录，未配置下载目录时使用
+    pub fn set_default_dir(&self, dir: PathBuf) {
+        *self.default_dir.write() = Some(dir);
+    }
+
+    /// 接收文件的目录
+    pub fn download_dir(&self) -> Result<PathBuf> {
+        config::clipboard::get_config()
+            .files
+            .download_dir
+            .or_else(|| self.default_dir.read().clone())
+            .ok_or_else(|| anyhow!("No download directory"))
+    }
+
+    /// 开始接收设备 `peer` 剪贴板内容 `hash` 中的文件，未启用或已在接收时忽略
+    pub fn receive(&self, peer: &str, hash: u64) {
+        if !config::clipboard::get_config().files.enabled {
+            debug!("File transfer disabled, ignore files from {}", peer);
+            return;
+        }
+        // 前端以 number 表示 id，限制在 2^53 以内
+        let id = rand::random::<u64>() >> 11;
+        let (tx, rx) = mpsc::channel(16);
+        {
+            let mut incoming = self.incoming.lock();
+            if incoming.values().any(|r| r.peer == peer && r.hash == hash) {
+                return;
+            }
+            incoming.insert(id, Receiving { peer: peer.to_string(), hash, tx });
+        }
+        info!("Receive files {:x} from {} as {:x}", hash, peer, id);
+        self.track(TransferProgress::new(id, peer, TransferDirection::Receive));
+        let peer = peer.to_string();
+        tauri::async_runtime::spawn(async move {
+            let this = Self::instance();
+            let result = incoming::run(id, peer, hash, rx).await;
+            this.incoming.lock().remove(&id);
+            match result {
+                Ok(paths) => {
+                    let paths: Vec<String> = paths
+                        .iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .collect();
+                    info!("Received files: {:?}", paths);
+                    this.update(id, |progress| {
+                        progress.state = TransferState::Completed;
+                        progress.current = None;
+                        progress.paths = paths.clone();
+                    });
+                    if let Err(e) =
+                        ClipboardSync::instance().apply_files(hash, paths).await
+                    {
+                        error!("Failed to write received files: {}", e);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to receive files {:x}: {}", id, e);
+                    this.update(id, |progress| {
+                        if !progress.state.finished() {
+                            progress.state =
+                                TransferState::Failed(e.to_string());
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    /// 取消传输，返回是否存在
+    pub async fn cancel(&self, id: u64) -> bool {
+        let tx = self.incoming.lock().get(&id).map(|r| r.tx.clone());
+        if let Some(tx) = tx {
+            self.update(id, |progress| {
+                progress.state = TransferState::Cancelled;
+            });
+            let _ = tx.send(Inbound::Cancel).await;
+            return true;
+        }
+        let peer = self
+            .outgoing
+            .lock()
+            .keys()
+            .find(|(_, outgoing_id)| *outgoing_id == id)
+            .map(|(peer, _)| peer.clone());
+        let Some(peer) = peer else {
+            return false;
+        };
+        self.finish_outgoing(&peer, id, TransferState::Cancelled);
+        let abort = Transfer::Abort { id, reason: "Cancelled".to_string() };
+        if let Err(e) = send(&peer, abort).await {
+            debug!("Failed to abort transfer {:x}: {}", id, e);
+        }
+        true
+    }
+
+    /// 所有传输的进度
+    pub fn list(&self) -> Vec<TransferProgress> {
+        self.progress.lock().iter().map(|t| t.progress.clone()).collect()
+    }
+
+    /// 处理设备 `origin` 发来的传输消息
+    ///
+    /// 数据块按到达顺序交给接收任务，对端每次只发送接收任务允许的数据量
+    async fn handle(&self, origin: &str, message: Transfer) {
+        match message {
+            Transfer::Request { id, hash } => self.serve(origin, id, hash),
+            Transfer::Pull { id, index, offset, len } => {
+                match self.outgoing.lock().get_mut(&(origin.to_string(), id)) {
+                    Some(outgoing) => {
+                        outgoing.pull(origin, id, index, offset, len)
+                    }
+                    None => debug!("Unknown transfer {:x} from {}", id, origin),
+                }
+            }
+            Transfer::Done { id } => {
+                self.finish_outgoing(origin, id, TransferState::Completed);
+            }
+            Transfer::Abort { id, reason } => {
+                self.finish_outgoing(
+                    origin,
+                    id,
+                    TransferState::Failed(reason.clone()),
+                );
+                self.deliver(origin, id, Inbound::Abort(reason)).await;
+            }
+            Transfer::Manifest { id, entries } => {
+                self.deliver(origin, id, Inbound::Manifest(entries)).await;
+            }
+            Transfer::Chunk { id, index, offset, data } => {
+                let chunk = Inbound::Chunk { index, offset, data };
+                self.deliver(origin, id, chunk).await;
+            }
+        }
+    }
+
+    /// 交给接收任务
+    async fn deliver(&self, origin: &str, id: u64, inbound: Inbound) {
+        let tx = self
+            .incoming
+            .lock()
+            .get(&id)
+            .filter(|r| r.peer == origin)
+            .map(|r| r.tx.clone());
+        if let Some(tx) = tx {
+            let _ = tx.send(inbound).await;
+        }
+    }
+
+    /// 响应设备 `peer` 的文件请求，生成清单后发送
+    fn serve(&self, peer: &str, id: u64, hash: u64) {
+        self.outgoing.lock().retain(|_, outgoing| {
+            let alive = outgoing.touched.elapsed() < OUTGOING_TTL;
+            if !alive {
+                outgoing.stop();
+            }
+            alive
+        });
+        let peer = peer.to_string();
+        // 重新请求清单时沿用已有的发送记录
+        let existing = self
+            .outgoing
+            .lock()
+            .get(&(peer.clone(), id))
+            .map(|outgoing| outgoing.entries.clone());
+        tauri::async_runtime::spawn(async move {
+            let this = Self::instance();
+            let entries = match existing {
+                Some(entries) => Ok(entries),
+                None => this.prepare(&peer, id, hash).await,
+            };
+            let message = match entries {
+                Ok(entries) => Transfer::Manifest { id, entries },
+                Err(e) => {
+                    warn!("Failed to send files to {}: {}", peer, e);
+                    Transfer::Abort { id, reason: e.to_string() }
+                }
+            };
+            if let Err(e) = send(&peer, message).await {
+                debug!("Failed to reply transfer {:x}: {}", id, e);
+            }
+        });
+    }
+
+    /// 生成剪贴板内容 `hash` 中文件的清单并记录
+    async fn prepare(
+        &self,
+        peer: &str,
+        id: u64,
+        hash: u64,
+    ) -> Result<Vec<FileEntry>> {
+        if !config::device::get_config(peer).clipboard().send {
+            return Err(anyhow!("Clipboard sending disabled"));
+        }
+        let roots = ClipboardSync::instance()
+            .files(hash)
+            .ok_or_else(|| anyhow!("Clipboard files {:x} are gone", hash))?;
+        let (entries, paths) =
+            tokio::task::spawn_blocking(move || manifest::build(&roots))
+                .await??;
+        let mut progress =
+            TransferProgress::new(id, peer, TransferDirection::Send);
+        progress.state = TransferState::Active;
+        progress.files = entries.iter().filter(|entry| !entry.dir).count();
+        progress.bytes = entries.iter().map(|entry| entry.size).sum();
+        info!(
+            "Send {} entries ({} bytes) to {}",
+            entries.len(),
+            progress.bytes,
+            peer
+        );
+        self.track(progress);
+        self.outgoing.lock().insert(
+            (peer.to_string(), id),
+            Outgoing::new(entries.clone(), paths),
+        );
+        Ok(entries)
+    }
+
+    /// 结束发送，记录最终状态
+    pub fn finish_outgoing(&self, peer: &str, id: u64, state: TransferState) {
+        let Some(mut outgoing) =
+            self.outgoing.lock().remove(&(peer.to_string(), id))
+        else {
+            return;
+        };
+        outgoing.stop();
+        self.update(id, |progress| {
+            if state == TransferState::Completed {
+                progress.files_done = progress.files;
+                progress.bytes_done = progress.bytes;
+            }
+            progress.current = None;
+            progress.state = state;
+        });
+    }
+
+    fn track(&self, progress: TransferProgress) {
+        let mut tracked = self.progress.lock();
+        let finished =
+            tracked.iter().filter(|t| t.progress.state.finished()).count();
+        if finished >= MAX_FINISHED
+            && let Some(index) =
+                tracked.iter().position(|t| t.progress.state.finished())
+        {
+            tracked.remove(index);
+        }
+        emit(&progress);
+        tracked.push(Tracked { progress, emitted: Some(Instant::now()) });
+    }
+
+    /// 更新进度，状态变化时立即通知界面，其他变化限制频率
+    pub fn update(&self, id: u64, f: impl FnOnce(&mut TransferProgress)) {
+        let mut tracked = self.progress.lock();
+        let Some(tracked) = tracked.iter_mut().find(|t| t.progress.id == id)
+        else {
+            return;
+        };
+        let state = tracked.progress.state.clone();
+        f(&mut tracked.progress);
+        let due = tracked
+            .emitted
+            .is_none_or(|emitted| emitted.elapsed() >= PROGRESS_INTERVAL);
+        if tracked.progress.state != state || due {
+            emit(&tracked.progress);
+            tracked.emitted = Some(Instant::now());
+        }
+    }
+}
+
+fn emit(progress: &TransferProgress) {
+    if let Some(handle) = core::handle::Handle::instance().app_handle()
+        && let Err(e) = handle.emit(constant::EVENT_FILE_TRANSFER, progress)
+    {
+        debug!("Failed to emit file transfer: {}", e);
+    }
+}
+
+/// 向设备 `peer` 发送传输消息，作为客户端时经由服务端转发
+async fn send(peer: &str, message: Transfer) -> Result<()> {
+    match config::network::get_config().service_type() {
+        ServiceType::Server => {
+            TcpServer::instance()
+                .send_transfer(peer, &local_device_id(), message)
+                .await
+        }
+        ServiceType::Client => {
+            TcpClient::instance().send_transfer(peer, message).await
+        }
+    }
+}
+
+fn local_device_id() -> String {
+    config::system::config().unwrap_or_default().id()
+}
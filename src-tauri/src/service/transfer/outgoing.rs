@@ -0,0 +1,171 @@
+use std::{io::SeekFrom, path::PathBuf};
+
+use anyhow::Result;
+use spdlog::{debug, warn};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt as _, AsyncSeekExt as _},
+    sync::watch,
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::service::protocols::transfer::{FileEntry, Transfer};
+
+use super::{FileTransfers, TransferState, send};
+
+// 每个数据块的大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// 发送给一个接收方的文件
+pub struct Outgoing {
+    pub entries: Vec<FileEntry>,
+    // 条目对应的本地路径
+    pub paths: Vec<PathBuf>,
+    // 正在发送的条目
+    pub stream: Option<JoinHandle<()>>,
+    // 正在发送的条目序号与接收方允许发送到的位置
+    credit: Option<(u32, watch::Sender<u64>)>,
+    // 上次收到接收方请求的时间
+    pub touched: Instant,
+}
+
+impl Outgoing {
+    pub fn new(entries: Vec<FileEntry>, paths: Vec<PathBuf>) -> Self {
+        Self {
+            entries,
+            paths,
+            stream: None,
+            credit: None,
+            touched: Instant::now(),
+        }
+    }
+
+    /// 发送第 `index` 个条目从 `offset` 开始的 `len` 字节
+    ///
+    /// `offset` 为正在发送的条目允许发送的末尾时追加额度，否则替换正在发送的条目
+    pub fn pull(
+        &mut self,
+        peer: &str,
+        id: u64,
+        index: u32,
+        offset: u64,
+        len: u64,
+    ) {
+        self.touched = Instant::now();
+        if let (Some(stream), Some((current, credit))) =
+            (&self.stream, &self.credit)
+            && !stream.is_finished()
+            && *current == index
+            && *credit.borrow() == offset
+        {
+            credit.send_replace(offset + len);
+            return;
+        }
+        self.stop();
+        let (Some(entry), Some(path)) =
+            (self.entries.get(index as usize), self.paths.get(index as usize))
+        else {
+            warn!("Transfer {:x} has no entry {}", id, index);
+            return;
+        };
+        let previous = &self.entries[..index as usize];
+        let (credit, limit) = watch::channel(offset + len);
+        let stream = Stream {
+            peer: peer.to_string(),
+            id,
+            index,
+            path: path.clone(),
+            size: entry.size,
+            base: previous.iter().map(|entry| entry.size).sum(),
+            limit,
+        };
+        FileTransfers::instance().update(id, |progress| {
+            progress.current = Some(entry.path.clone());
+            progress.files_done = previous.iter().filter(|e| !e.dir).count();
+        });
+        self.stream = Some(tokio::spawn(stream.run(offset)));
+        self.credit = Some((index, credit));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            stream.abort();
+        }
+        self.credit = None;
+    }
+}
+
+/// 发送单个文件的任务
+struct Stream {
+    peer: String,
+    id: u64,
+    index: u32,
+    path: PathBuf,
+    size: u64,
+    // 之前条目的总大小，用于计算进度
+    base: u64,
+    // 接收方允许发送到的位置
+    limit: watch::Receiver<u64>,
+}
+
+impl Stream {
+    async fn run(mut self, mut offset: u64) {
+        let mut file = match self.open(offset).await {
+            Ok(file) => file,
+            Err(e) => return self.abort(e).await,
+        };
+        let mut buffer = vec![0; CHUNK_SIZE];
+        while offset < self.size {
+            let limit = *self.limit.borrow_and_update();
+            if offset >= limit {
+                // 等待接收方追加额度，发送记录结束时停止
+                if self.limit.changed().await.is_err() {
+                    return;
+                }
+                continue;
+            }
+            let len =
+                (self.size.min(limit) - offset).min(CHUNK_SIZE as u64) as usize;
+            if let Err(e) = file.read_exact(&mut buffer[..len]).await {
+                return self.abort(e.into()).await;
+            }
+            let chunk = Transfer::Chunk {
+                id: self.id,
+                index: self.index,
+                offset,
+                data: buffer[..len].to_vec(),
+            };
+            // 连接断开时等待接收方重新请求
+            if let Err(e) = send(&self.peer, chunk).await {
+                debug!("Transfer {:x} interrupted: {}", self.id, e);
+                return;
+            }
+            offset += len as u64;
+            FileTransfers::instance().update(self.id, |progress| {
+                progress.bytes_done = self.base + offset;
+            });
+        }
+    }
+
+    async fn open(&self, offset: u64) -> Result<File> {
+        let mut file = File::open(&self.path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        Ok(file)
+    }
+
+    /// 文件无法读取，通知接收方并结束传输
+    async fn abort(&self, error: anyhow::Error) {
+        warn!("Failed to send {}: {}", self.path.display(), error);
+        let reason = format!("Failed to read file: {}", error);
+        let abort = Transfer::Abort { id: self.id, reason: reason.clone() };
+        if let Err(e) = send(&self.peer, abort).await {
+            debug!("Failed to abort transfer {:x}: {}", self.id, e);
+        }
+        FileTransfers::instance().finish_outgoing(
+            &self.peer,
+            self.id,
+            TransferState::Failed(reason),
+        );
+    }
+}
@@ -1,14 +1,15 @@
 import logo from '@/assets/logo.png';
 import { AppstoreOutlined, GithubFilled, SettingOutlined } from '@ant-design/icons';
 import { PageContainer, ProLayout } from '@ant-design/pro-components';
+import { listen } from '@tauri-apps/api/event';
 import { openUrl } from '@tauri-apps/plugin-opener';
 import { Avatar, ConfigProvider, Spin } from 'antd';
 import { ThemeProvider } from 'antd-style';
 import enUS from 'antd/lib/locale/en_US';
 import zhCN from 'antd/lib/locale/zh_CN';
-import { lazy, Suspense, useState } from 'react';
+import { lazy, Suspense, useEffect, useState } from 'react';
 import { useTranslation } from 'react-i18next';
-import { Navigate, NavLink, Route, Routes } from 'react-router';
+import { Navigate, NavLink, Route, Routes, useNavigate } from 'react-router';
 import { useSnapshot } from 'valtio';
 import './App.css';
 import NetworkSettings from './pages/settings/network-settings';
@@ -37,6 +38,18 @@ function App() {
   const { t } = useTranslation();
   const [location, setLocation] = useState('/');
   const systemSettings = useSnapshot(systemSettingsStore.state);
+  const navigate = useNavigate();
+
+  // 响应托盘菜单的页面跳转
+  useEffect(() => {
+    const unlisten = listen<string>('navigate', (event) => {
+      setLocation(event.payload);
+      navigate(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [navigate]);
 
   return (
     <ConfigProvider locale={systemSettings.locale === 'zh-CN' ? zhCN : enUS}>
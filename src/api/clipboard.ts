@@ -0,0 +1,49 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * 剪贴板历史变化事件
+ */
+export const CLIPBOARD_HISTORY_EVENT = 'clipboard-history';
+
+/**
+ * 剪贴板历史条目摘要
+ */
+export interface HistorySummary {
+  id: number;
+  ts: number;
+  // 来源设备 id
+  origin: string;
+  // 各表示的 MIME 类型
+  types: string[];
+  size: number;
+  // 文本内容的开头，图片等内容为 `null`
+  preview: string | null;
+}
+
+/**
+ * 获取剪贴板历史，新的在前
+ */
+export async function listClipboardHistory(): Promise<HistorySummary[]> {
+  return invoke('list_clipboard_history');
+}
+
+/**
+ * 按文本内容或来源设备搜索剪贴板历史
+ */
+export async function searchClipboardHistory(query: string): Promise<HistorySummary[]> {
+  return invoke('search_clipboard_history', { query });
+}
+
+/**
+ * 将历史条目重新复制到剪贴板
+ */
+export async function recopyClipboardHistory(id: number): Promise<void> {
+  return invoke('recopy_clipboard_history', { id });
+}
+
+/**
+ * 删除剪贴板历史条目，不指定 `id` 时清空
+ */
+export async function deleteClipboardHistory(id?: number): Promise<void> {
+  return invoke('delete_clipboard_history', { id });
+}
@@ -0,0 +1,80 @@
+import { invoke } from '@tauri-apps/api/core';
+
+export type Edge = 'Left' | 'Right' | 'Top' | 'Bottom';
+
+export interface Screen {
+  device_id: string;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface SwitchRules {
+  corner_size: number;
+  dwell_ms: number;
+  double_tap: boolean;
+  double_tap_ms: number;
+  restore_position: boolean;
+  wrap: boolean;
+}
+
+export interface CursorLockRules {
+  fullscreen: boolean;
+  apps: string[];
+  button_held: boolean;
+  keys: number[];
+}
+
+export interface Layout {
+  screens: Screen[];
+  switching: SwitchRules;
+  cursor_lock: CursorLockRules;
+}
+
+export interface Crossing {
+  device_id: string;
+  edge: Edge;
+  x: number;
+  y: number;
+}
+
+/**
+ * 获取屏幕布局
+ */
+export async function getLayout(): Promise<Layout> {
+  return invoke('get_layout');
+}
+
+/**
+ * 整体替换屏幕布局
+ */
+export async function setLayout(layout: Layout): Promise<void> {
+  return invoke('set_layout', { layout });
+}
+
+/**
+ * 放置或移动设备，与其他设备重叠时失败
+ */
+export async function placeScreen(screen: Screen): Promise<Layout> {
+  return invoke('place_screen', { screen });
+}
+
+/**
+ * 从布局中移除设备
+ */
+export async function removeScreen(deviceId: string): Promise<Layout> {
+  return invoke('remove_screen', { deviceId });
+}
+
+/**
+ * 查询从设备边缘离开时进入的设备与位置
+ */
+export async function crossEdge(
+  deviceId: string,
+  edge: Edge,
+  x: number,
+  y: number,
+): Promise<Crossing | null> {
+  return invoke('cross_edge', { deviceId, edge, x, y });
+}
@@ -0,0 +1,69 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * 输入目标，`Remote` 的值为设备 id
+ */
+export type Target = 'Local' | { Remote: string };
+
+export type SwitchReason = 'Edge' | 'Hotkey' | 'Api' | 'Disconnected';
+
+/**
+ * `target-changed` 事件内容
+ */
+export interface TargetChanged {
+  from: Target;
+  to: Target;
+  reason: SwitchReason;
+}
+
+/**
+ * 获取当前输入目标
+ */
+export async function getInputTarget(): Promise<Target> {
+  return invoke('get_input_target');
+}
+
+/**
+ * 切换输入目标
+ */
+export async function switchInputTarget(target: Target): Promise<void> {
+  return invoke('switch_input_target', { target });
+}
+
+/**
+ * 光标锁定原因，`App` 的值为窗口类名，`Key` 的值为键码
+ */
+export type LockReason =
+  | 'Manual'
+  | 'Fullscreen'
+  | 'Button'
+  | { App: string }
+  | { Key: number };
+
+/**
+ * 获取光标锁定原因，未锁定时为 null
+ */
+export async function getCursorLock(): Promise<LockReason | null> {
+  return invoke('get_cursor_lock');
+}
+
+/**
+ * 将剪贴板文本作为按键输入到当前目标，输入完成后返回
+ */
+export async function typeClipboard(): Promise<void> {
+  return invoke('type_clipboard');
+}
+
+/**
+ * 取消正在进行的按键输入
+ */
+export async function cancelTyping(): Promise<void> {
+  return invoke('cancel_typing');
+}
+
+/**
+ * 手动锁定或解锁光标
+ */
+export async function setCursorLock(locked: boolean): Promise<void> {
+  return invoke('set_cursor_lock', { locked });
+}
@@ -0,0 +1,18 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * 已应用的服务端配置
+ */
+export interface ManagedConfig {
+  version: number;
+  server_id: string;
+  // 由服务端管理的配置项
+  keys: string[];
+}
+
+/**
+ * 获取已应用的服务端配置，未由服务端管理时为 `null`
+ */
+export async function getManagedConfig(): Promise<ManagedConfig | null> {
+  return invoke('get_managed_config');
+}
@@ -0,0 +1,56 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * 文件传输进度变化事件
+ */
+export const FILE_TRANSFER_EVENT = 'file-transfer';
+
+/**
+ * 接收的文件与下载目录中已有文件同名时的处理方式
+ */
+export type ConflictPolicy = 'Rename' | 'Overwrite' | 'Skip';
+
+/**
+ * 传输状态
+ */
+export type TransferState =
+  | 'Pending'
+  | 'Active'
+  | 'Stalled'
+  | 'Completed'
+  | { Failed: string }
+  | 'Cancelled';
+
+/**
+ * 文件传输进度
+ */
+export interface TransferProgress {
+  id: number;
+  // 对端设备 id
+  peer: string;
+  direction: 'Send' | 'Receive';
+  state: TransferState;
+  // 文件数，不含目录
+  files: number;
+  files_done: number;
+  bytes: number;
+  bytes_done: number;
+  // 正在传输的文件的相对路径
+  current: string | null;
+  // 接收完成后下载目录中的路径
+  paths: string[];
+}
+
+/**
+ * 获取文件传输的进度，包括最近结束的传输
+ */
+export async function listFileTransfers(): Promise<TransferProgress[]> {
+  return invoke('list_file_transfers');
+}
+
+/**
+ * 取消文件传输
+ */
+export async function cancelFileTransfer(id: number): Promise<void> {
+  return invoke('cancel_file_transfer', { id });
+}
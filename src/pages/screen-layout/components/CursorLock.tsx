@@ -0,0 +1,51 @@
+import { getCursorLock, LockReason, setCursorLock } from '@/api/router';
+import { listen } from '@tauri-apps/api/event';
+import { Switch, Tag } from 'antd';
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+
+function reasonKey(reason: LockReason) {
+  if (typeof reason === 'string') {
+    return reason.toLowerCase();
+  }
+  return 'App' in reason ? 'app' : 'key';
+}
+
+function CursorLock() {
+  const { t } = useTranslation();
+  const [reason, setReason] = useState<LockReason | null>(null);
+
+  // 同步后端的光标锁定状态
+  useEffect(() => {
+    getCursorLock().then(setReason);
+    const unlisten = listen<LockReason | null>('cursor-lock', (event) => {
+      setReason(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  return (
+    <div
+      className={`
+        flex
+        items-center
+        gap-2
+      `}
+    >
+      <span>{t('screen-layout.cursor-lock.label')}</span>
+      <Switch
+        checked={reason !== null}
+        onChange={(checked) => setCursorLock(checked)}
+      />
+      {reason !== null && (
+        <Tag color="orange">
+          {t(`screen-layout.cursor-lock.reason.${reasonKey(reason)}`)}
+        </Tag>
+      )}
+    </div>
+  );
+}
+
+export default CursorLock;
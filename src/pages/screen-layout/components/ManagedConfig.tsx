@@ -0,0 +1,39 @@
+import { getManagedConfig, ManagedConfig as Managed } from '@/api/sync';
+import { listen } from '@tauri-apps/api/event';
+import { Tag, Tooltip } from 'antd';
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
+
+function ManagedConfig() {
+  const { t } = useTranslation();
+  const [managed, setManaged] = useState<Managed | null>(null);
+
+  useEffect(() => {
+    getManagedConfig().then(setManaged);
+    const unlisten = listen<Managed | null>('managed-config', (event) => {
+      setManaged(event.payload);
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  if (managed === null) {
+    return null;
+  }
+
+  // 由服务端管理的配置项
+  const fields = managed.keys.map((key) =>
+    t(`screen-layout.managed.keys.${key}`, { defaultValue: key }),
+  );
+
+  return (
+    <Tooltip title={`${t('screen-layout.managed.fields')}: ${fields.join(', ')}`}>
+      <Tag color="blue">
+        {t('screen-layout.managed.label', { version: managed.version })}
+      </Tag>
+    </Tooltip>
+  );
+}
+
+export default ManagedConfig;
@@ -4,7 +4,9 @@ import { DndContext, MouseSensor, useSensor, useSensors } from '@dnd-kit/core';
 import { Alert } from 'antd';
 import { useTranslation } from 'react-i18next';
 import { useSnapshot } from 'valtio';
+import CursorLock from './components/CursorLock';
 import DeviceGrid from './components/DeviceGrid';
+import ManagedConfig from './components/ManagedConfig';
 
 function ScreenLayout() {
   const { t } = useTranslation();
@@ -34,6 +36,8 @@ function ScreenLayout() {
       ) : (
         <div className={`h-6`} />
       )}
+      <CursorLock />
+      {networkSettings.serviceType === 'client' && <ManagedConfig />}
       <div className={`mt-10`}>
         <DndContext
           sensors={sensors}